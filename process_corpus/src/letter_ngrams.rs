@@ -0,0 +1,61 @@
+use std::{collections::HashMap, io::Write};
+use anyhow::Result;
+
+// Each line of a letter n-gram file is "<n-gram> <count>", in descending
+// count order. The counts are how often each run of n letters appears inside
+// a word, weighted by the word's frequency. Words are split at apostrophes
+// and hyphens, and n-grams never cross from one word to the next.
+
+/// Counts the letter n-grams of length `n` in `entries`.
+pub fn count_ngrams(entries: &[(&String, &u64)], n: usize) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for entry in entries {
+        for part in entry.0.split(|c: char| !c.is_ascii_lowercase()) {
+            if part.len() < n {
+                continue;
+            }
+            for i in 0..=part.len() - n {
+                *counts.entry(part[i..i + n].to_string()).or_insert(0) += *entry.1;
+            }
+        }
+    }
+    counts
+}
+
+/// Writes the letter n-grams of length `n` in `entries`.
+pub fn write_ngrams<W: Write>(entries: &[(&String, &u64)], n: usize, writer: &mut W) -> Result<()> {
+    let counts = count_ngrams(entries, n);
+    let mut counts = counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (ngram, count) in counts {
+        writeln!(writer, "{} {}", ngram, count)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_ngrams() {
+        let (there, the, dont) = ("there".to_string(), "the".to_string(), "don't".to_string());
+        let entries = vec![(&there, &10), (&the, &30), (&dont, &5)];
+        let counts = count_ngrams(&entries, 3);
+        assert_eq!(Some(&40), counts.get("the"));
+        assert_eq!(Some(&10), counts.get("ere"));
+        assert_eq!(Some(&5), counts.get("don"));
+        // the, her, ere and don - "t" is too short
+        assert_eq!(4, counts.len());
+    }
+
+    #[test]
+    fn test_write_ngrams() -> Result<()> {
+        let (there, the) = ("there".to_string(), "the".to_string());
+        let entries = vec![(&there, &10), (&the, &30)];
+        let mut bytes = vec![];
+        write_ngrams(&entries, 4, &mut bytes)?;
+        assert_eq!("here 10\nther 10\n", String::from_utf8(bytes)?);
+        Ok(())
+    }
+}
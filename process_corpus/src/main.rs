@@ -1,10 +1,21 @@
-use std::{collections::HashMap, fs::File, io::{self, BufRead, Write}};
+use std::{collections::{HashMap, HashSet}, fs::{self, File}, io::{self, BufRead, Write}};
 use anyhow::{anyhow, Result};
 
+mod letter_ngrams;
+mod word_bigrams;
+mod word_index;
+
 type WordFrequency = HashMap<String, u64>;
 
 const WRITE_FST_FILE: bool = true;
+const WRITE_INDEX_FILE: bool = true;
+const WRITE_NGRAM_FILES: bool = true;
+const WRITE_BIGRAM_FILE: bool = true;
 const FREQUENCY_CUTOFF: u64 = 10000;
+// Two-word phrases are only any use as puzzles if they're common, and only
+// the first 10000 lines of the bigram file get read anyway.
+const BIGRAM_FREQUENCY_CUTOFF: u64 = 100000;
+const MAX_BIGRAMS: usize = 10000;
 
 fn main() -> Result<()> {
     println!("Hello, world!");
@@ -40,6 +51,43 @@ fn main() -> Result<()> {
         }
         fst_builder.finish()?;
     }
+    if WRITE_INDEX_FILE {
+        let filtered_entries = entries.iter().filter(|e| *e.1 >= FREQUENCY_CUTOFF).copied().collect::<Vec<_>>();
+        let mut writer = io::BufWriter::new(File::create("../data/processed/word_index.bin")?);
+        word_index::write_index(&filtered_entries, &mut writer)?;
+        writer.flush()?;
+    }
+    if WRITE_NGRAM_FILES {
+        let filtered_entries = entries.iter().filter(|e| *e.1 >= FREQUENCY_CUTOFF).copied().collect::<Vec<_>>();
+        for (n, name) in [(3, "trigrams"), (4, "quadgrams")] {
+            let mut writer = io::BufWriter::new(File::create(format!("../data/processed/letter_{}.txt", name))?);
+            letter_ngrams::write_ngrams(&filtered_entries, n, &mut writer)?;
+            writer.flush()?;
+        }
+    }
+    if WRITE_BIGRAM_FILE {
+        // the 2-gram files are a separate (and much bigger) download, so only
+        // use them if they're there
+        let mut paths = fs::read_dir("../data/raw")?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().to_string_lossy().into_owned())
+            .filter(|path| path.rsplit('/').next().is_some_and(|name| name.starts_with("2-") && !name.ends_with(".gz")))
+            .collect::<Vec<_>>();
+        paths.sort();
+        if paths.is_empty() {
+            println!("no 2-gram files in ../data/raw, skipping word_bigrams.txt");
+        } else {
+            let words = entries.iter().filter(|e| *e.1 >= FREQUENCY_CUTOFF).map(|e| e.0.as_str()).collect::<HashSet<_>>();
+            let mut bigrams = word_bigrams::BigramFrequency::new();
+            for path in &paths {
+                println!("parsing {}, have {} bigrams so far...", path, bigrams.len());
+                word_bigrams::parse_bigram_file(path, &words, BIGRAM_FREQUENCY_CUTOFF, &mut bigrams)?;
+            }
+            let mut writer = io::BufWriter::new(File::create("../data/processed/word_bigrams.txt")?);
+            word_bigrams::write_bigrams(&bigrams, MAX_BIGRAMS, &mut writer)?;
+            writer.flush()?;
+        }
+    }
     Ok(())
 }
 
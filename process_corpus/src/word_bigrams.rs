@@ -0,0 +1,94 @@
+use std::{collections::{HashMap, HashSet}, fs::File, io::{self, BufRead, Write}};
+use anyhow::{anyhow, Result};
+
+// Each line of the word bigram file is "<first word> <second word> <count>",
+// in descending count order, for two-word phrases where both words are in the
+// word list. search_corpus's puzzle generator picks phrases from it.
+
+pub type BigramFrequency = HashMap<(String, String), u64>;
+
+/// Adds the count from a line of a 2-gram file to `bigrams`, if both words
+/// are in `words` and it was seen at least `cutoff` times.
+pub fn parse_bigram_line(line: &str, words: &HashSet<&str>, cutoff: u64, bigrams: &mut BigramFrequency) -> Result<()> {
+    let mut parts = line.split('\t');
+    let ngram = parts.next().ok_or_else(|| anyhow!("no bigram"))?;
+    // the untagged line already counts all the part-of-speech tagged ones
+    if ngram.contains('_') {
+        return Ok(());
+    }
+    let ngram = ngram.to_ascii_lowercase();
+    let (first, second) = match ngram.split(' ').collect::<Vec<_>>()[..] {
+        [first, second] => (first, second),
+        _ => return Ok(()),
+    };
+    if !words.contains(first) || !words.contains(second) {
+        return Ok(());
+    }
+    let mut count = 0;
+    for entry in parts {
+        // each entry is "<year>,<match count>,<volume count>"
+        let entry_count = entry.split(',').nth(1).ok_or_else(|| anyhow!("no count in entry {}", entry))?;
+        count += entry_count.trim().parse::<u64>().map_err(|e| anyhow!("couldn't parse count: {}", e))?;
+    }
+    if count >= cutoff {
+        *bigrams.entry((first.to_string(), second.to_string())).or_insert(0) += count;
+    }
+    Ok(())
+}
+
+pub fn parse_bigram_file(path: &str, words: &HashSet<&str>, cutoff: u64, bigrams: &mut BigramFrequency) -> Result<()> {
+    let mut line = String::new();
+    let mut reader = io::BufReader::new(File::open(path)?);
+    while reader.read_line(&mut line)? > 0 {
+        parse_bigram_line(line.trim_end(), words, cutoff, bigrams)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Writes the `max_bigrams` most common bigrams.
+pub fn write_bigrams<W: Write>(bigrams: &BigramFrequency, max_bigrams: usize, writer: &mut W) -> Result<()> {
+    let mut bigrams = bigrams.iter().collect::<Vec<_>>();
+    bigrams.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for ((first, second), count) in bigrams.into_iter().take(max_bigrams) {
+        writeln!(writer, "{} {} {}", first, second, count)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> HashSet<&'static str> {
+        ["of", "the", "tree"].iter().copied().collect()
+    }
+
+    #[test]
+    fn test_parse_bigram_line() -> Result<()> {
+        let mut bigrams = BigramFrequency::new();
+        parse_bigram_line("of the\t1960,100,5\t1961,50,3", &words(), 0, &mut bigrams)?;
+        parse_bigram_line("Of the\t1960,10,1", &words(), 0, &mut bigrams)?;
+        assert_eq!(Some(&160), bigrams.get(&("of".to_string(), "the".to_string())));
+        // tagged, not in the word list, below the cutoff, not two words
+        parse_bigram_line("of_ADP the_DET\t1960,100,5", &words(), 0, &mut bigrams)?;
+        parse_bigram_line("of xyzzy\t1960,100,5", &words(), 0, &mut bigrams)?;
+        parse_bigram_line("the tree\t1960,100,5", &words(), 1000, &mut bigrams)?;
+        parse_bigram_line("of the tree\t1960,100,5", &words(), 0, &mut bigrams)?;
+        assert_eq!(1, bigrams.len());
+        assert!(parse_bigram_line("the tree\t1960", &words(), 0, &mut bigrams).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_bigrams() -> Result<()> {
+        let mut bigrams = BigramFrequency::new();
+        bigrams.insert(("the".to_string(), "tree".to_string()), 5);
+        bigrams.insert(("of".to_string(), "the".to_string()), 100);
+        bigrams.insert(("in".to_string(), "the".to_string()), 50);
+        let mut bytes = vec![];
+        write_bigrams(&bigrams, 2, &mut bytes)?;
+        assert_eq!("of the 100\nin the 50\n", String::from_utf8(bytes)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::Write;
+use anyhow::{anyhow, Result};
+
+// The index file looks like this (all integers little-endian):
+//   magic "WIDX", version u32
+//   alphabet length u32, alphabet bytes
+//   group count u32, then for each group: word length u32, word count u32, data offset u64
+// and the data for each group is
+//   the words, concatenated (they're all the same length, so no separators)
+//   the frequencies, one u64 per word
+//   for each position, for each alphabet character, a bitset (as u64s) of
+//   which words have that character at that position
+// Words in a group are in descending frequency order.
+pub const MAGIC: &[u8; 4] = b"WIDX";
+pub const VERSION: u32 = 1;
+// Must match WORD_ALPHABET in search_corpus.
+pub const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz'-";
+
+fn alphabet_index(c: u8) -> Result<usize> {
+    ALPHABET
+        .iter()
+        .position(|a| *a == c)
+        .ok_or_else(|| anyhow!("character {} not in index alphabet", c as char))
+}
+
+/// Writes the index for `entries`, which must already be sorted by descending frequency.
+pub fn write_index<W: Write>(entries: &[(&String, &u64)], writer: &mut W) -> Result<()> {
+    let mut max_length = 0;
+    for entry in entries {
+        max_length = max_length.max(entry.0.len());
+    }
+    let mut groups: Vec<Vec<(&String, &u64)>> = vec![vec![]; max_length + 1];
+    for entry in entries {
+        groups[entry.0.len()].push(*entry);
+    }
+    let groups = groups
+        .into_iter()
+        .enumerate()
+        .filter(|(_, words)| !words.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut header = vec![];
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(ALPHABET.len() as u32).to_le_bytes());
+    header.extend_from_slice(ALPHABET);
+    header.extend_from_slice(&(groups.len() as u32).to_le_bytes());
+    let header_len = header.len() + groups.len() * 16;
+
+    let mut data = vec![];
+    for (length, words) in &groups {
+        header.extend_from_slice(&(*length as u32).to_le_bytes());
+        header.extend_from_slice(&(words.len() as u32).to_le_bytes());
+        header.extend_from_slice(&((header_len + data.len()) as u64).to_le_bytes());
+        for word in words {
+            data.extend_from_slice(word.0.as_bytes());
+        }
+        for word in words {
+            data.extend_from_slice(&word.1.to_le_bytes());
+        }
+        let blocks = words.len().div_ceil(64);
+        let mut bitsets = vec![0u64; length * ALPHABET.len() * blocks];
+        for (i, word) in words.iter().enumerate() {
+            for (position, c) in word.0.bytes().enumerate() {
+                let start = (position * ALPHABET.len() + alphabet_index(c)?) * blocks;
+                bitsets[start + i / 64] |= 1 << (i % 64);
+            }
+        }
+        for block in bitsets {
+            data.extend_from_slice(&block.to_le_bytes());
+        }
+    }
+    writer.write_all(&header)?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    #[test]
+    fn test_write_index_groups_by_length() -> Result<()> {
+        let (the, and, a) = ("the".to_string(), "and".to_string(), "a".to_string());
+        let entries = vec![(&the, &30), (&and, &20), (&a, &10)];
+        let mut bytes = vec![];
+        write_index(&entries, &mut bytes)?;
+        assert_eq!(MAGIC, &bytes[0..4]);
+        let groups_start = 12 + ALPHABET.len();
+        assert_eq!(2, read_u32(&bytes, groups_start));
+        // length 1 group first, with one word
+        assert_eq!(1, read_u32(&bytes, groups_start + 4));
+        assert_eq!(1, read_u32(&bytes, groups_start + 8));
+        // then length 3, with two words in frequency order
+        assert_eq!(3, read_u32(&bytes, groups_start + 20));
+        assert_eq!(2, read_u32(&bytes, groups_start + 24));
+        let offset = u64::from_le_bytes(bytes[groups_start + 28..groups_start + 36].try_into()?) as usize;
+        assert_eq!(b"theand", &bytes[offset..offset + 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_index_bitsets() -> Result<()> {
+        let (the, tie) = ("the".to_string(), "tie".to_string());
+        let entries = vec![(&the, &30), (&tie, &20)];
+        let mut bytes = vec![];
+        write_index(&entries, &mut bytes)?;
+        let groups_start = 12 + ALPHABET.len();
+        let offset = u64::from_le_bytes(bytes[groups_start + 12..groups_start + 20].try_into()?) as usize;
+        let bitsets = offset + 6 + 16;
+        let bitset = |position: usize, c: u8| {
+            let start = bitsets + (position * ALPHABET.len() + alphabet_index(c).unwrap()) * 8;
+            u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap())
+        };
+        assert_eq!(0b11, bitset(0, b't'));
+        assert_eq!(0b01, bitset(1, b'h'));
+        assert_eq!(0b10, bitset(1, b'i'));
+        assert_eq!(0b00, bitset(2, b'a'));
+        Ok(())
+    }
+}
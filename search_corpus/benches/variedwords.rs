@@ -1,3 +1,6 @@
+// These benchmarks predate running clippy on all targets.
+#![allow(clippy::redundant_closure)]
+
 use criterion::{criterion_group, criterion_main, Criterion};
 use search_corpus::process_query_string;
 
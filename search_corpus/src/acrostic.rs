@@ -0,0 +1,451 @@
+use crate::{parse_min_frequency, Dictionary, PatternMode, QueryLimits, WorkBudget};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// An acrostic: the quotation is made of numbered cells, and each clue
+/// answer uses some of the same cells. The first letters of the clue
+/// answers spell out the source (usually the author and title).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Acrostic {
+    quote: Vec<Vec<u16>>,
+    clues: Vec<Vec<u16>>,
+}
+
+/// Parses groups of cell numbers separated by `/`, with the numbers in each
+/// group separated by spaces or commas.
+fn parse_cells(cells: &str) -> Result<Vec<Vec<u16>>, String> {
+    cells
+        .split('/')
+        .map(|group| {
+            group
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|cell| !cell.is_empty())
+                .map(|cell| match cell.parse::<u16>() {
+                    Ok(n) if n > 0 => Ok(n),
+                    _ => Err(format!("Invalid cell {}", cell)),
+                })
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .filter(|group| !matches!(group, Ok(g) if g.is_empty()))
+        .collect()
+}
+
+impl Acrostic {
+    /// Parses the quotation's words and the clue answers, both written as
+    /// groups of cell numbers (see `parse_cells()`). Each cell can only be
+    /// in the quotation once and in the clue answers once, and every cell
+    /// in a clue answer must be in the quotation.
+    pub fn parse(quote: &str, clues: &str) -> Result<Acrostic, String> {
+        let quote = parse_cells(quote)?;
+        let clues = parse_cells(clues)?;
+        if quote.is_empty() || clues.is_empty() {
+            return Err("Acrostic needs a quotation and clues".to_string());
+        }
+        let mut quote_cells = HashSet::new();
+        for cell in quote.iter().flatten() {
+            if !quote_cells.insert(*cell) {
+                return Err(format!("Cell {} is in the quotation twice", cell));
+            }
+        }
+        let mut clue_cells = HashSet::new();
+        for cell in clues.iter().flatten() {
+            if !clue_cells.insert(*cell) {
+                return Err(format!("Cell {} is in the clues twice", cell));
+            }
+            if !quote_cells.contains(cell) {
+                return Err(format!("Cell {} isn't in the quotation", cell));
+            }
+        }
+        Ok(Acrostic { quote, clues })
+    }
+
+    /// The clue answers and then the quotation's words.
+    fn entries(&self) -> Vec<Vec<u16>> {
+        self.clues
+            .iter()
+            .chain(self.quote.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn fill(cells: &[u16], letters: &BTreeMap<u16, char>) -> String {
+        cells
+            .iter()
+            .map(|cell| letters.get(cell).copied().unwrap_or('?'))
+            .collect()
+    }
+
+    /// The clue answers with `letters` filled in (and `?` for the rest).
+    pub fn clue_answers(&self, letters: &BTreeMap<u16, char>) -> Vec<String> {
+        self.clues
+            .iter()
+            .map(|clue| Acrostic::fill(clue, letters))
+            .collect()
+    }
+
+    /// The quotation with `letters` filled in (and `?` for the rest).
+    pub fn quotation(&self, letters: &BTreeMap<u16, char>) -> String {
+        self.quote
+            .iter()
+            .map(|word| Acrostic::fill(word, letters))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The first letters of the clue answers.
+    pub fn source(&self, letters: &BTreeMap<u16, char>) -> String {
+        self.clues
+            .iter()
+            .map(|clue| letters.get(&clue[0]).copied().unwrap_or('?'))
+            .collect()
+    }
+}
+
+/// Parses known letters written like `3=e,117=q`.
+pub fn parse_known(known: &str) -> Result<Vec<(u16, char)>, String> {
+    known
+        .split(',')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (cell, letter) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid known letter {}", pair))?;
+            let cell = cell
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("Invalid cell {}", cell))?;
+            let mut letters = letter.trim().chars();
+            match (letters.next(), letters.next()) {
+                (Some(letter), None) if letter.is_ascii_alphabetic() => {
+                    Ok((cell, letter.to_ascii_lowercase()))
+                }
+                _ => Err(format!("Invalid letter {}", letter)),
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcrosticResult {
+    /// The letters worked out - all of them if `solved`, and otherwise the
+    /// ones every remaining possibility agrees on.
+    pub letters: BTreeMap<u16, char>,
+    pub solved: bool,
+    pub truncated: bool,
+}
+
+struct Solver<'a> {
+    entries: Vec<Vec<u16>>,
+    candidates: Vec<Vec<(String, u64)>>,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+}
+
+impl<'a> Solver<'a> {
+    /// Drops the candidates that don't fit `letters`, and fills in any
+    /// letters that all of an entry's remaining candidates agree on, until
+    /// nothing changes. Letters found in a clue answer carry over to the
+    /// quotation and the other way round. Returns `None` if some entry has
+    /// no candidates left.
+    fn propagate(
+        &mut self,
+        letters: &mut BTreeMap<u16, char>,
+        mut live: Vec<Vec<usize>>,
+    ) -> Option<Vec<Vec<usize>>> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (entry, candidates) in live.iter_mut().enumerate() {
+                let cells = &self.entries[entry];
+                let mut remaining = vec![];
+                for candidate in candidates.iter() {
+                    if !self.budget.charge() {
+                        self.truncated = true;
+                        return None;
+                    }
+                    let word = self.candidates[entry][*candidate].0.as_bytes();
+                    if cells
+                        .iter()
+                        .zip(word)
+                        .all(|(cell, c)| letters.get(cell).is_none_or(|l| *l == *c as char))
+                    {
+                        remaining.push(*candidate);
+                    }
+                }
+                if remaining.is_empty() {
+                    return None;
+                }
+                for (i, cell) in cells.iter().enumerate() {
+                    if letters.contains_key(cell) {
+                        continue;
+                    }
+                    let letter = self.candidates[entry][remaining[0]].0.as_bytes()[i];
+                    if remaining
+                        .iter()
+                        .all(|c| self.candidates[entry][*c].0.as_bytes()[i] == letter)
+                    {
+                        letters.insert(*cell, letter as char);
+                        changed = true;
+                    }
+                }
+                *candidates = remaining;
+            }
+        }
+        Some(live)
+    }
+
+    fn solve(
+        &mut self,
+        letters: &BTreeMap<u16, char>,
+        live: &[Vec<usize>],
+    ) -> Option<BTreeMap<u16, char>> {
+        // fill in the entry with the fewest options first
+        let entry = match (0..self.entries.len())
+            .filter(|entry| live[*entry].len() > 1)
+            .min_by_key(|entry| live[*entry].len())
+        {
+            Some(entry) => entry,
+            // every entry is down to one word, which propagating filled in
+            None => return Some(letters.clone()),
+        };
+        for candidate in &live[entry] {
+            let mut new_letters = letters.clone();
+            let word = &self.candidates[entry][*candidate].0;
+            for (cell, c) in self.entries[entry].iter().zip(word.chars()) {
+                new_letters.insert(*cell, c);
+            }
+            let mut new_live = live.to_vec();
+            new_live[entry] = vec![*candidate];
+            if let Some(new_live) = self.propagate(&mut new_letters, new_live) {
+                if let Some(solution) = self.solve(&new_letters, &new_live) {
+                    return Some(solution);
+                }
+            }
+            if self.truncated {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Solves an acrostic, using only words with at least `min_frequency`.
+/// Each clue answer and quotation word gets its candidates from a Crossword
+/// search, and the letters they agree on are shared between them. Entries
+/// are tried most common word first, so the solution found favors common
+/// words.
+pub fn solve_acrostic(
+    dictionary: &Dictionary,
+    acrostic: &Acrostic,
+    known: &[(u16, char)],
+    min_frequency: u64,
+    limits: &QueryLimits,
+) -> Result<AcrosticResult, String> {
+    let mut letters = BTreeMap::new();
+    for (cell, letter) in known {
+        // every cell is in the quotation
+        if !acrostic.quote.iter().flatten().any(|c| c == cell) {
+            return Err(format!("Cell {} isn't in the acrostic", cell));
+        }
+        if letters.insert(*cell, *letter).is_some_and(|l| l != *letter) {
+            return Err(format!("Conflicting known letter for {}", cell));
+        }
+    }
+    let entries = acrostic.entries();
+    // one budget for finding the candidates and solving
+    let mut budget = WorkBudget::new(limits);
+    let mut candidates = vec![];
+    for cells in &entries {
+        let pattern = Acrostic::fill(cells, &letters);
+        let results =
+            dictionary.search_with_budget(PatternMode::Crossword, &pattern, "", &mut budget)?;
+        if results.truncated {
+            return Ok(AcrosticResult {
+                letters,
+                solved: false,
+                truncated: true,
+            });
+        }
+        candidates.push(
+            results
+                .words
+                .into_iter()
+                .filter(|(_, frequency)| *frequency >= min_frequency)
+                .collect::<Vec<_>>(),
+        );
+    }
+    let mut solver = Solver {
+        entries,
+        candidates,
+        budget,
+        truncated: false,
+    };
+    let all = solver
+        .candidates
+        .iter()
+        .map(|c| (0..c.len()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut propagated = letters.clone();
+    let solution = solver
+        .propagate(&mut propagated, all)
+        .and_then(|live| solver.solve(&propagated, &live));
+    Ok(match solution {
+        Some(letters) => AcrosticResult {
+            letters,
+            solved: true,
+            truncated: false,
+        },
+        None => AcrosticResult {
+            letters: if solver.truncated {
+                letters
+            } else {
+                propagated
+            },
+            solved: false,
+            truncated: solver.truncated,
+        },
+    })
+}
+
+/// Handles a query string with mode `Acrostic`, where `pattern` is the
+/// quotation and `clues` the clue answers (see `Acrostic::parse()`),
+/// `known` is the known letters (see `parse_known()`) and `min_frequency`
+/// is optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let acrostic = Acrostic::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+        query_parts
+            .get("clues")
+            .ok_or_else(|| String::from("Internal error - no clues specified!"))?,
+    )?;
+    let known = parse_known(query_parts.get("known").map_or("", |k| k.as_str()))?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let result = solve_acrostic(
+        &Dictionary::open()?,
+        &acrostic,
+        &known,
+        min_frequency,
+        &QueryLimits::default(),
+    )?;
+    if result.truncated {
+        return Err("Query took too long - try giving more letters".to_string());
+    }
+    let mut letters = json::JsonValue::new_object();
+    for (cell, letter) in &result.letters {
+        letters[cell.to_string()] = letter.to_string().into();
+    }
+    Ok(json::object! {
+        "quote" => acrostic.quotation(&result.letters),
+        "clues" => acrostic.clue_answers(&result.letters),
+        "source" => acrostic.source(&result.letters),
+        "letters" => letters,
+        "solved" => result.solved
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "there is the tree", with clue answers tie, she, tree and her
+    const QUOTE: &str = "1 2 3 4 5 / 6 7 / 8 9 10 / 11 12 13 14";
+    const CLUES: &str = "1 6 3 / 7 2 5 / 8 4 10 13 / 9 14 12";
+
+    #[test]
+    fn test_parse() {
+        let acrostic = Acrostic::parse(QUOTE, CLUES).unwrap();
+        assert_eq!(4, acrostic.quote.len());
+        assert_eq!(vec![7, 2, 5], acrostic.clues[1]);
+        assert_eq!(8, acrostic.entries().len());
+        // 6 in the clues twice
+        assert!(Acrostic::parse(QUOTE, "1 6 / 6 2").is_err());
+        // 15 isn't in the quotation
+        assert!(Acrostic::parse(QUOTE, "1 15").is_err());
+        assert!(Acrostic::parse("1 2 / 2", "1").is_err());
+        assert!(Acrostic::parse("1 x", "1").is_err());
+        assert!(Acrostic::parse(QUOTE, "").is_err());
+    }
+
+    #[test]
+    fn test_parse_known() {
+        assert_eq!(Ok(vec![(3, 'e'), (117, 'q')]), parse_known("3=e, 117=Q"));
+        assert!(parse_known("0=a").is_err());
+        assert!(parse_known("3=ab").is_err());
+    }
+
+    #[test]
+    fn test_solve_acrostic() {
+        let dictionary = Dictionary::open().unwrap();
+        let acrostic = Acrostic::parse(QUOTE, CLUES).unwrap();
+        let result = solve_acrostic(
+            &dictionary,
+            &acrostic,
+            &[(1, 't'), (7, 's'), (11, 't')],
+            0,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(result.solved);
+        assert_eq!("there is the tree", acrostic.quotation(&result.letters));
+        assert_eq!(
+            vec!["tie", "she", "tree", "her"],
+            acrostic.clue_answers(&result.letters)
+        );
+        assert_eq!("tsth", acrostic.source(&result.letters));
+    }
+
+    #[test]
+    fn test_solve_acrostic_no_solution() {
+        let dictionary = Dictionary::open().unwrap();
+        let acrostic = Acrostic::parse(QUOTE, CLUES).unwrap();
+        let result = solve_acrostic(
+            &dictionary,
+            &acrostic,
+            &[(1, 'x'), (2, 'x'), (3, 'x')],
+            0,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!result.solved);
+        assert!(!result.truncated);
+        assert!(solve_acrostic(
+            &dictionary,
+            &acrostic,
+            &[(1, 't'), (1, 'h')],
+            0,
+            &QueryLimits::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_known_letter_outside_acrostic() {
+        let acrostic = Acrostic::parse(QUOTE, CLUES).unwrap();
+        assert!(solve_acrostic(
+            &Dictionary::open().unwrap(),
+            &acrostic,
+            &[(1, 't'), (15, 'e')],
+            0,
+            &QueryLimits::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_candidates_and_solving_share_a_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        let acrostic = Acrostic::parse(QUOTE, CLUES).unwrap();
+        let known = [(1, 't'), (7, 's'), (11, 't')];
+        // enough for finding the candidates or for solving, but not both
+        let limits = QueryLimits {
+            max_scanned_entries: 3000,
+            ..QueryLimits::default()
+        };
+        let result = solve_acrostic(&dictionary, &acrostic, &known, 0, &limits).unwrap();
+        assert!(result.truncated);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{
+    letter_stats::letter_outcomes,
+    simulator::{Action, Wedge, Wheel, CONSONANTS_BY_FREQUENCY, HOUSE_MINIMUM, VOWELS, VOWEL_COST},
+    Dictionary, PatternMode, QueryLimits,
+};
+use std::collections::HashMap;
+
+/// What the player can see when deciding what to do.
+pub struct BoardState<'a> {
+    /// The board, with `?` for letters that haven't been revealed. This can
+    /// be a single word or a whole phrase, as long as it lines up with the
+    /// candidates.
+    pub pattern: &'a str,
+    /// Every letter that has been called so far.
+    pub guessed_letters: &'a str,
+    pub round_bank: u32,
+}
+
+/// The expected value of each action, along with the one to take.
+///
+/// Values are the expected winnings if the player tries to solve right
+/// after the action, counting nothing if their turn ends first. Each
+/// candidate is assumed to be the answer with probability proportional to
+/// its frequency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Advice {
+    pub solve: f64,
+    /// The value of spinning, and the consonant to call if it lands on cash.
+    pub spin: Option<(f64, char)>,
+    /// The value of buying the best vowel, if the player can afford one.
+    pub buy_vowel: Option<(f64, char)>,
+    pub recommended: Action,
+}
+
+fn winnings(bank: u32) -> f64 {
+    bank.max(HOUSE_MINIMUM) as f64
+}
+
+/// The expected value of calling `letter` and, if it's there, adding
+/// `value_per_letter` for each one before solving.
+fn value_of_calling(
+    state: &BoardState,
+    candidates: &[(String, u64)],
+    total: f64,
+    letter: char,
+    bank: u32,
+    value_per_letter: u32,
+) -> f64 {
+    letter_outcomes(state.pattern, candidates, letter)
+        .iter()
+        .filter(|outcome| !outcome.positions.is_empty())
+        .map(|outcome| {
+            let new_bank = bank + value_per_letter * outcome.positions.len() as u32;
+            // once the letters are revealed, the best guess is the most
+            // common remaining candidate
+            candidates[outcome.best_candidate].1 as f64 / total * winnings(new_bank)
+        })
+        .sum()
+}
+
+fn best_letter<F: Fn(char) -> f64>(
+    state: &BoardState,
+    letters: &str,
+    value: F,
+) -> Option<(f64, char)> {
+    letters
+        .chars()
+        .filter(|c| !state.guessed_letters.contains(*c))
+        .map(|c| (value(c), c))
+        .fold(None, |best, candidate| match best {
+            Some(best) if best.0 >= candidate.0 => Some(best),
+            _ => Some(candidate),
+        })
+}
+
+/// Works out whether to spin, buy a vowel or solve, given the candidates
+/// (from a WheelOfFortune search) for what's on the board.
+pub fn advise(state: &BoardState, candidates: &[(String, u64)], wheel: &Wheel) -> Advice {
+    let total = candidates.iter().map(|c| c.1).sum::<u64>() as f64;
+    if candidates.is_empty() || total == 0.0 {
+        // Nothing to go on, so get more letters up.
+        return Advice {
+            solve: 0.0,
+            spin: Some((0.0, 't')),
+            buy_vowel: None,
+            recommended: Action::Spin,
+        };
+    }
+    let best = &candidates
+        .iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .unwrap();
+    let solve = best.1 as f64 / total * winnings(state.round_bank);
+
+    let any_hidden_consonants = candidates.iter().any(|(word, _)| {
+        state
+            .pattern
+            .chars()
+            .zip(word.chars())
+            .any(|(p, w)| p == '?' && CONSONANTS_BY_FREQUENCY.contains(w))
+    });
+    let spin = if any_hidden_consonants && !wheel.wedges.is_empty() {
+        let wedge_probability = 1.0 / wheel.wedges.len() as f64;
+        best_letter(state, CONSONANTS_BY_FREQUENCY, |consonant| {
+            wheel
+                .wedges
+                .iter()
+                .map(|wedge| match wedge {
+                    // either way the turn is over
+                    Wedge::Bankrupt | Wedge::LoseATurn => 0.0,
+                    Wedge::Cash(value) => value_of_calling(
+                        state,
+                        candidates,
+                        total,
+                        consonant,
+                        state.round_bank,
+                        *value,
+                    ),
+                })
+                .sum::<f64>()
+                * wedge_probability
+        })
+    } else {
+        None
+    };
+
+    let buy_vowel = if state.round_bank >= VOWEL_COST {
+        best_letter(state, VOWELS, |vowel| {
+            value_of_calling(
+                state,
+                candidates,
+                total,
+                vowel,
+                state.round_bank - VOWEL_COST,
+                0,
+            )
+        })
+    } else {
+        None
+    };
+
+    let mut recommended = (solve, Action::Solve(best.0.clone()));
+    if let Some((value, _)) = spin {
+        if value > recommended.0 {
+            recommended = (value, Action::Spin);
+        }
+    }
+    if let Some((value, vowel)) = buy_vowel {
+        if value > recommended.0 {
+            recommended = (value, Action::BuyVowel(vowel));
+        }
+    }
+    Advice {
+        solve,
+        spin,
+        buy_vowel,
+        recommended: recommended.1,
+    }
+}
+
+fn letter_value(value: Option<(f64, char)>) -> json::JsonValue {
+    match value {
+        Some((value, letter)) => json::object! { "value" => value, "letter" => letter.to_string() },
+        None => json::JsonValue::Null,
+    }
+}
+
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let pattern = query_parts
+        .get("pattern")
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?
+        .to_ascii_lowercase();
+    let mut guessed_letters = query_parts
+        .get("guessed")
+        .map(|g| g.to_ascii_lowercase())
+        .unwrap_or_default();
+    // anything showing on the board has been called, even if the caller
+    // left it out
+    guessed_letters.extend(pattern.chars().filter(|c| c.is_ascii_lowercase()));
+    let round_bank = match query_parts.get("bank") {
+        Some(bank) => bank
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid bank {}", bank))?,
+        None => 0,
+    };
+    let results = Dictionary::open()?.search(
+        PatternMode::WheelOfFortune,
+        &pattern,
+        &guessed_letters,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    let state = BoardState {
+        pattern: &pattern,
+        guessed_letters: &guessed_letters,
+        round_bank,
+    };
+    let advice = advise(&state, &results.words, &Wheel::default());
+    let recommended = match advice.recommended {
+        Action::Solve(guess) => json::object! { "action" => "solve", "guess" => guess },
+        Action::Spin => json::object! { "action" => "spin" },
+        Action::BuyVowel(vowel) => {
+            json::object! { "action" => "buy_vowel", "letter" => vowel.to_string() }
+        }
+    };
+    Ok(json::object! {
+        "solve" => advice.solve,
+        "spin" => letter_value(advice.spin),
+        "buy_vowel" => letter_value(advice.buy_vowel),
+        "recommended" => recommended
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(words: &[(&str, u64)]) -> Vec<(String, u64)> {
+        words.iter().map(|(w, f)| (w.to_string(), *f)).collect()
+    }
+
+    #[test]
+    fn test_solve_when_only_one_candidate() {
+        let state = BoardState {
+            pattern: "th?",
+            guessed_letters: "th",
+            round_bank: 2000,
+        };
+        let advice = advise(&state, &candidates(&[("the", 100)]), &Wheel::default());
+        assert_eq!(2000.0, advice.solve);
+        assert_eq!(None, advice.spin);
+        // buying the e would just cost money
+        assert_eq!(Some((1750.0, 'e')), advice.buy_vowel);
+        assert_eq!(Action::Solve("the".to_string()), advice.recommended);
+    }
+
+    #[test]
+    fn test_spin_for_more_money_when_answer_is_known() {
+        let state = BoardState {
+            pattern: "t?e",
+            guessed_letters: "te",
+            round_bank: 2000,
+        };
+        let advice = advise(&state, &candidates(&[("the", 100)]), &Wheel::default());
+        assert_eq!(2000.0, advice.solve);
+        assert_eq!('h', advice.spin.unwrap().1);
+        assert_eq!(Action::Spin, advice.recommended);
+    }
+
+    #[test]
+    fn test_spin_when_consonant_splits_candidates() {
+        // Calling n or t tells the two apart, so spinning is worth it even
+        // though we might hit a bankrupt.
+        let state = BoardState {
+            pattern: "?e?",
+            guessed_letters: "e",
+            round_bank: 0,
+        };
+        let advice = advise(
+            &state,
+            &candidates(&[("ten", 50), ("net", 50)]),
+            &Wheel::default(),
+        );
+        assert_eq!(500.0, advice.solve);
+        assert!(advice.spin.unwrap().0 > advice.solve);
+        assert_eq!(None, advice.buy_vowel);
+        assert_eq!(Action::Spin, advice.recommended);
+    }
+
+    #[test]
+    fn test_buy_vowel_when_only_vowel_splits_candidates() {
+        let state = BoardState {
+            pattern: "t?n",
+            guessed_letters: "tn",
+            round_bank: 3000,
+        };
+        let advice = advise(
+            &state,
+            &candidates(&[("tan", 40), ("ten", 30), ("tin", 30)]),
+            &Wheel::default(),
+        );
+        // no consonants left to call
+        assert_eq!(None, advice.spin);
+        let (value, vowel) = advice.buy_vowel.unwrap();
+        assert_eq!('a', vowel);
+        assert_eq!(0.4 * 2750.0, value);
+        // solving now is worth 0.4 * 3000, which beats buying a vowel
+        assert_eq!(Action::Solve("tan".to_string()), advice.recommended);
+    }
+
+    #[test]
+    fn test_bankrupt_wheel_makes_spinning_worthless() {
+        let state = BoardState {
+            pattern: "?e?",
+            guessed_letters: "e",
+            round_bank: 5000,
+        };
+        let wheel = Wheel {
+            wedges: vec![Wedge::Bankrupt],
+        };
+        let advice = advise(&state, &candidates(&[("hen", 50), ("pet", 50)]), &wheel);
+        assert_eq!(0.0, advice.spin.unwrap().0);
+        assert_ne!(Action::Spin, advice.recommended);
+    }
+
+    #[test]
+    fn test_no_candidates() {
+        let state = BoardState {
+            pattern: "???",
+            guessed_letters: "",
+            round_bank: 0,
+        };
+        assert_eq!(
+            Action::Spin,
+            advise(&state, &[], &Wheel::default()).recommended
+        );
+    }
+}
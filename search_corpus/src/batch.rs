@@ -0,0 +1,189 @@
+use crate::{
+    query_cache::QueryCache, results_to_json, Dictionary, PatternMode, QueryLimits, SearchResults,
+    WorkBudget,
+};
+use std::{convert::TryFrom, sync::Arc};
+
+// More than enough for a whole puzzle's worth of words.
+pub const MAX_BATCH_QUERIES: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchQuery {
+    pub mode: PatternMode,
+    pub pattern: String,
+    pub absent_letters: String,
+}
+
+/// Runs all of `queries` against one shared copy of the dictionary, only
+/// searching once for queries that are the same. Each query succeeds or
+/// fails on its own, but they share `limits`, so once those run out the
+/// rest of the queries fail.
+pub fn search_batch(
+    queries: &[BatchQuery],
+    limits: &QueryLimits,
+) -> Result<Vec<Result<Arc<SearchResults>, String>>, String> {
+    if queries.len() > MAX_BATCH_QUERIES {
+        return Err(format!(
+            "Too many queries (the limit is {})",
+            MAX_BATCH_QUERIES
+        ));
+    }
+    let mut cache = QueryCache::new(Dictionary::open()?, queries.len());
+    let mut budget = WorkBudget::new(limits);
+    Ok(queries
+        .iter()
+        .map(|query| {
+            if budget.is_exhausted() {
+                return Err("Query took too long".to_string());
+            }
+            cache.search_with_budget(
+                query.mode,
+                &query.pattern,
+                &query.absent_letters,
+                &mut budget,
+            )
+        })
+        .collect())
+}
+
+fn parse_batch_query(value: &json::JsonValue) -> Result<BatchQuery, String> {
+    let mode = value["mode"]
+        .as_str()
+        .ok_or_else(|| String::from("Internal error - no mode specified!"))?;
+    let mode =
+        PatternMode::try_from(mode).map_err(|_| String::from("Internal error - invalid mode!"))?;
+    let pattern = value["pattern"]
+        .as_str()
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?;
+    let absent_letters = value["absent_letters"].as_str().unwrap_or("");
+    Ok(BatchQuery {
+        mode,
+        pattern: pattern.to_string(),
+        absent_letters: absent_letters.to_string(),
+    })
+}
+
+/// Handles a batch request, whose body is a JSON array of objects with the
+/// same fields as the query string for a single request. The response is
+/// an array with, for each query, either the same array a single request
+/// would return or an object with an "error" field.
+pub fn process_batch_json(body: &str) -> Result<json::JsonValue, String> {
+    let request = json::parse(body).map_err(|e| e.to_string())?;
+    if !request.is_array() {
+        return Err("Internal error - batch request must be an array!".to_string());
+    }
+    let parsed = request.members().map(parse_batch_query).collect::<Vec<_>>();
+    let queries = parsed
+        .iter()
+        .filter_map(|query| query.as_ref().ok())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut results = search_batch(&queries, &QueryLimits::default())?.into_iter();
+    Ok(json::JsonValue::Array(
+        parsed
+            .into_iter()
+            .map(|query| {
+                query
+                    .and_then(|_| results.next().unwrap())
+                    .and_then(|result| results_to_json(&result))
+                    .unwrap_or_else(|e| json::object! { "error" => e })
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_batch() {
+        let queries = vec![
+            BatchQuery {
+                mode: PatternMode::WheelOfFortune,
+                pattern: "t?e".to_string(),
+                absent_letters: "h".to_string(),
+            },
+            BatchQuery {
+                mode: PatternMode::Cryptogram,
+                pattern: "ABC".to_string(),
+                absent_letters: "".to_string(),
+            },
+        ];
+        let results = search_batch(&queries, &QueryLimits::default()).unwrap();
+        assert_eq!(2, results.len());
+        assert_eq!("tie", results[0].as_ref().unwrap().words[0].0);
+        assert_eq!("the", results[1].as_ref().unwrap().words[0].0);
+    }
+
+    #[test]
+    fn test_search_batch_shares_repeated_queries() {
+        let query = BatchQuery {
+            mode: PatternMode::WheelOfFortune,
+            pattern: "t?e".to_string(),
+            absent_letters: "h".to_string(),
+        };
+        let results = search_batch(&[query.clone(), query], &QueryLimits::default()).unwrap();
+        assert!(Arc::ptr_eq(
+            results[0].as_ref().unwrap(),
+            results[1].as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_search_batch_shares_limits() {
+        let limits = QueryLimits {
+            max_scanned_entries: 2,
+            ..QueryLimits::default()
+        };
+        let queries = ["t?e", "a?d", "?he"]
+            .iter()
+            .map(|pattern| BatchQuery {
+                mode: PatternMode::Crossword,
+                pattern: pattern.to_string(),
+                absent_letters: "".to_string(),
+            })
+            .collect::<Vec<_>>();
+        let results = search_batch(&queries, &limits).unwrap();
+        assert!(results[0].as_ref().unwrap().truncated);
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_search_batch_too_many_queries() {
+        let query = BatchQuery {
+            mode: PatternMode::Crossword,
+            pattern: "t?e".to_string(),
+            absent_letters: "".to_string(),
+        };
+        let queries = vec![query; MAX_BATCH_QUERIES + 1];
+        assert!(search_batch(&queries, &QueryLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_process_batch_json() {
+        let result = process_batch_json(
+            r#"[{"mode": "WheelOfFortune", "pattern": "t?e", "absent_letters": "h"},
+                {"mode": "NotARealMode", "pattern": "t?e"},
+                {"mode": "Crossword", "pattern": "t?e?"},
+                {"mode": "Crossword", "pattern": "t!e"}]"#,
+        )
+        .unwrap();
+        assert_eq!(4, result.len());
+        assert_eq!("tie", result[0][0]["word"].to_string());
+        assert!(result[1]["error"].is_string());
+        let words = result[2]
+            .members()
+            .map(|x| x["word"].to_string())
+            .collect::<Vec<String>>();
+        assert!(words.contains(&"tree".to_string()));
+        assert!(result[3]["error"].is_string());
+    }
+
+    #[test]
+    fn test_process_batch_json_not_an_array() {
+        assert!(process_batch_json(r#"{"mode": "Crossword"}"#).is_err());
+        assert!(process_batch_json("not json").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{fst_walk::FstCursor, parse_usize_param, Dictionary, QueryLimits, WorkBudget};
+use memmap::Mmap;
+use std::collections::HashMap;
+
+pub const DEFAULT_MIN_WORD_LENGTH: usize = 3;
+// Big enough for any real board, small enough that a search can't run away.
+const MAX_GRID_SIZE: usize = 8;
+
+/// A square grid of letters. Each cell is usually one letter, but the Q
+/// cube is `qu`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    size: usize,
+    cells: Vec<String>,
+}
+
+impl Grid {
+    /// Parses a grid written as rows separated by commas or whitespace, like
+    /// `abcd,efgh,ijkl,mnqp`. `q` and `qu` both mean the Qu cube.
+    pub fn parse(grid: &str) -> Result<Grid, String> {
+        let rows = grid
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                let row = row.to_ascii_lowercase();
+                let mut cells = vec![];
+                let mut chars = row.chars().peekable();
+                while let Some(c) = chars.next() {
+                    if !c.is_ascii_lowercase() {
+                        return Err("Disallowed characters in grid".to_string());
+                    }
+                    if c == 'q' {
+                        if chars.peek() == Some(&'u') {
+                            chars.next();
+                        }
+                        cells.push("qu".to_string());
+                    } else {
+                        cells.push(c.to_string());
+                    }
+                }
+                Ok(cells)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let size = rows.len();
+        if size == 0 || size > MAX_GRID_SIZE {
+            return Err(format!(
+                "Grid must have between 1 and {} rows",
+                MAX_GRID_SIZE
+            ));
+        }
+        if rows.iter().any(|row| row.len() != size) {
+            return Err("Grid must be square".to_string());
+        }
+        Ok(Grid {
+            size,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The letters in the cell at `index` (which is `row * size + column`).
+    pub fn cell(&self, index: usize) -> &str {
+        &self.cells[index]
+    }
+
+    /// The cells next to `index`, including diagonally.
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let (row, column) = ((index / self.size) as isize, (index % self.size) as isize);
+        let size = self.size as isize;
+        (-1..=1)
+            .flat_map(move |dr| (-1..=1).map(move |dc| (row + dr, column + dc)))
+            .filter(move |(r, c)| {
+                (*r, *c) != (row, column) && *r >= 0 && *r < size && *c >= 0 && *c < size
+            })
+            .map(move |(r, c)| (r * size + c) as usize)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridWord {
+    pub word: String,
+    pub frequency: u64,
+    /// The cells the word goes through, in order.
+    pub path: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GridResults {
+    /// Sorted by descending frequency, then descending length.
+    pub words: Vec<GridWord>,
+    pub truncated: bool,
+}
+
+struct GridSearch<'a> {
+    grid: &'a Grid,
+    min_length: usize,
+    budget: WorkBudget<'a>,
+    visited: Vec<bool>,
+    path: Vec<usize>,
+    word: String,
+    found: HashMap<String, GridWord>,
+    truncated: bool,
+}
+
+impl<'a> GridSearch<'a> {
+    fn visit<'f>(&mut self, index: usize, cursor: &FstCursor<'f, Mmap>) {
+        if self.truncated {
+            return;
+        }
+        if !self.budget.charge() {
+            self.truncated = true;
+            return;
+        }
+        let cell = self.grid.cell(index);
+        // no word starts with this, so there's no point going any further
+        let cursor = match cursor.step(cell.as_bytes()) {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        self.visited[index] = true;
+        self.path.push(index);
+        self.word.push_str(cell);
+        if let Some(frequency) = cursor.frequency() {
+            if self.word.len() >= self.min_length && !self.found.contains_key(&self.word) {
+                self.found.insert(
+                    self.word.clone(),
+                    GridWord {
+                        word: self.word.clone(),
+                        frequency,
+                        path: self.path.clone(),
+                    },
+                );
+            }
+        }
+        let neighbors = self.grid.neighbors(index).collect::<Vec<_>>();
+        for neighbor in neighbors {
+            if !self.visited[neighbor] {
+                self.visit(neighbor, &cursor);
+            }
+        }
+        self.word.truncate(self.word.len() - cell.len());
+        self.path.pop();
+        self.visited[index] = false;
+    }
+}
+
+/// Finds every dictionary word at least `min_length` letters long that can
+/// be traced through adjacent cells of `grid` without using a cell twice.
+pub fn solve_grid(
+    dictionary: &Dictionary,
+    grid: &Grid,
+    min_length: usize,
+    limits: &QueryLimits,
+) -> Result<GridResults, String> {
+    let map = dictionary.fst_map()?;
+    let root = FstCursor::new(map.as_fst());
+    let mut search = GridSearch {
+        grid,
+        min_length,
+        budget: WorkBudget::new(limits),
+        visited: vec![false; grid.cells.len()],
+        path: vec![],
+        word: String::new(),
+        found: HashMap::new(),
+        truncated: false,
+    };
+    for index in 0..grid.cells.len() {
+        search.visit(index, &root);
+    }
+    let mut words = search.found.into_values().collect::<Vec<_>>();
+    words.sort_by(|a, b| {
+        b.frequency
+            .cmp(&a.frequency)
+            .then(b.word.len().cmp(&a.word.len()))
+            .then(a.word.cmp(&b.word))
+    });
+    Ok(GridResults {
+        words,
+        truncated: search.truncated,
+    })
+}
+
+/// Handles a query string with mode `Boggle`, where `pattern` is the grid
+/// (see `Grid::parse()`) and `min_length` is optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let grid = Grid::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    )?;
+    let min_length = parse_usize_param(query_parts, "min_length", DEFAULT_MIN_WORD_LENGTH)?;
+    let results = solve_grid(
+        &Dictionary::open()?,
+        &grid,
+        min_length,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .words
+            .iter()
+            .map(|w| {
+                json::object! {
+                    "word" => w.word.clone(),
+                    "frequency" => w.frequency,
+                    "path" => w.path.clone()
+                }
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = Grid::parse("tque,hea\nrex").unwrap();
+        assert_eq!(3, grid.size());
+        assert_eq!("qu", grid.cell(1));
+        assert_eq!("x", grid.cell(8));
+        assert_eq!(grid, Grid::parse("TQE HEA REX").unwrap());
+        assert!(Grid::parse("ab,c").is_err());
+        assert!(Grid::parse("a1,bc").is_err());
+        assert!(Grid::parse("").is_err());
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let grid = Grid::parse("abc,def,ghi").unwrap();
+        let mut corner = grid.neighbors(0).collect::<Vec<_>>();
+        corner.sort_unstable();
+        assert_eq!(vec![1, 3, 4], corner);
+        assert_eq!(8, grid.neighbors(4).count());
+    }
+
+    #[test]
+    fn test_solve_grid() {
+        let dictionary = Dictionary::open().unwrap();
+        // t h e
+        // x r e
+        // x x x
+        let grid = Grid::parse("the,xre,xxx").unwrap();
+        let results = solve_grid(&dictionary, &grid, 3, &QueryLimits::default()).unwrap();
+        assert!(!results.truncated);
+        let there = results.words.iter().find(|w| w.word == "there").unwrap();
+        assert_eq!(vec![0, 1, 2, 4, 5], there.path);
+        let tree = results.words.iter().find(|w| w.word == "tree").unwrap();
+        assert_eq!(vec![0, 4, 2, 5], tree.path);
+        assert!(results.words.iter().all(|w| w.word.len() >= 3));
+        assert!(results
+            .words
+            .windows(2)
+            .all(|w| w[0].frequency >= w[1].frequency));
+        // "he" is a word, but too short
+        assert!(!results.words.iter().any(|w| w.word == "he"));
+    }
+
+    #[test]
+    fn test_solve_grid_no_reused_cells() {
+        let dictionary = Dictionary::open().unwrap();
+        // "tee" would need the e twice
+        let grid = Grid::parse("te,xx").unwrap();
+        let results = solve_grid(&dictionary, &grid, 2, &QueryLimits::default()).unwrap();
+        assert!(!results.words.iter().any(|w| w.word == "tee"));
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{fst_walk::FstCursor, Dictionary, QueryLimits, WorkBudget};
+use memmap::Mmap;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+};
+
+const MAX_CIPHERTEXT_LENGTH: usize = 5000;
+const MAX_VIGENERE_KEY_LENGTH: usize = 12;
+// How many of the likeliest Vigenère key lengths to try cracking.
+const KEY_LENGTHS_TRIED: usize = 3;
+// Words shorter than this found inside a longer run of letters are more
+// likely to be chance than a real word.
+const MIN_EMBEDDED_WORD_LENGTH: usize = 3;
+// Only this many decryptions are returned from a query.
+const MAX_RESULTS_RETURNED: usize = 10;
+
+// How often each letter appears in English text.
+const ENGLISH_FREQUENCIES: [f64; 26] = [
+    0.0817, 0.0149, 0.0278, 0.0425, 0.1270, 0.0223, 0.0202, 0.0609, 0.0697, 0.0015, 0.0077, 0.0403,
+    0.0241, 0.0675, 0.0751, 0.0193, 0.0010, 0.0599, 0.0633, 0.0906, 0.0276, 0.0098, 0.0236, 0.0015,
+    0.0197, 0.0007,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    /// Each letter shifted along the alphabet by the same amount.
+    Caesar,
+    /// Each letter x becomes ax + b (mod 26).
+    Affine,
+    /// The alphabet reversed.
+    Atbash,
+    /// Each letter shifted by the next letter of a repeating keyword.
+    Vigenere,
+}
+
+/// Simplest first - Caesar and Atbash are both affine ciphers too, and
+/// Caesar is a Vigenère cipher with a one letter key.
+pub const ALL_CIPHERS: [Cipher; 4] = [
+    Cipher::Caesar,
+    Cipher::Atbash,
+    Cipher::Affine,
+    Cipher::Vigenere,
+];
+
+impl TryFrom<&str> for Cipher {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "Caesar" => Ok(Cipher::Caesar),
+            "Affine" => Ok(Cipher::Affine),
+            "Atbash" => Ok(Cipher::Atbash),
+            "Vigenere" => Ok(Cipher::Vigenere),
+            _ => Err(format!("Unrecognized cipher {}", value)),
+        }
+    }
+}
+
+impl Cipher {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cipher::Caesar => "Caesar",
+            Cipher::Affine => "Affine",
+            Cipher::Atbash => "Atbash",
+            Cipher::Vigenere => "Vigenere",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decryption {
+    pub cipher: Cipher,
+    /// The shift for Caesar, `a,b` for affine, the keyword for Vigenère and
+    /// empty for Atbash.
+    pub key: String,
+    pub plaintext: String,
+    /// The fraction of letters that are in dictionary words.
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrackResults {
+    /// The decryptions, best first.
+    pub decryptions: Vec<Decryption>,
+    /// Whether cracking gave up before trying every key.
+    pub truncated: bool,
+}
+
+/// A key to try, before decrypting with it.
+enum CandidateKey {
+    Affine(u8, u8),
+    Vigenere(Vec<u8>),
+}
+
+/// Applies `decrypt` (from 0 for a to 25 for z) to each letter of
+/// `ciphertext` in turn, lowercasing them and leaving everything else alone.
+fn map_letters(ciphertext: &str, mut decrypt: impl FnMut(u8) -> u8) -> String {
+    ciphertext
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphabetic() {
+                (decrypt(c.to_ascii_lowercase() as u8 - b'a') + b'a') as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn modular_inverse(a: u8) -> Option<u8> {
+    (1..26).find(|x| (a as usize * *x as usize) % 26 == 1)
+}
+
+/// Decrypts an affine cipher where each letter x was encrypted as
+/// `ax + b`. A Caesar shift is `a = 1` and Atbash is `a = b = 25`.
+pub fn decrypt_affine(ciphertext: &str, a: u8, b: u8) -> Result<String, String> {
+    let inverse = modular_inverse(a % 26).ok_or_else(|| format!("{} has no inverse mod 26", a))?;
+    Ok(map_letters(ciphertext, |c| {
+        ((inverse as usize * (c as usize + 26 - (b % 26) as usize)) % 26) as u8
+    }))
+}
+
+/// Decrypts a Vigenère cipher with `key` (0 for a and so on), which only
+/// moves on at letters.
+pub fn decrypt_vigenere(ciphertext: &str, key: &[u8]) -> Result<String, String> {
+    if key.is_empty() {
+        return Err("Vigenère key can't be empty".to_string());
+    }
+    let mut position = 0;
+    Ok(map_letters(ciphertext, |c| {
+        let shift = key[position % key.len()] % 26;
+        position += 1;
+        (c + 26 - shift) % 26
+    }))
+}
+
+fn index_of_coincidence(letters: &[u8]) -> f64 {
+    if letters.len() < 2 {
+        return 0.0;
+    }
+    let mut counts = [0usize; 26];
+    for letter in letters {
+        counts[*letter as usize] += 1;
+    }
+    let pairs = counts
+        .iter()
+        .map(|n| n * n.saturating_sub(1))
+        .sum::<usize>();
+    pairs as f64 / (letters.len() * (letters.len() - 1)) as f64
+}
+
+/// The likeliest Vigenère key lengths, best first. Each length is scored by
+/// the average index of coincidence of the letters it would shift by the
+/// same amount (which is high if they're all shifted the same), boosted by
+/// how many of the distances between repeated trigrams it divides (Kasiski
+/// examination).
+pub fn estimate_key_lengths(letters: &[u8]) -> Vec<usize> {
+    let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, trigram) in letters.windows(3).enumerate() {
+        positions.entry(trigram).or_default().push(i);
+    }
+    let distances = positions
+        .values()
+        .flat_map(|p| p.windows(2).map(|w| w[1] - w[0]))
+        .collect::<Vec<_>>();
+    let max_length = MAX_VIGENERE_KEY_LENGTH.min(letters.len() / 2).max(1);
+    let mut scored = (1..=max_length)
+        .map(|length| {
+            let ic = (0..length)
+                .map(|start| {
+                    let column = letters
+                        .iter()
+                        .skip(start)
+                        .step_by(length)
+                        .copied()
+                        .collect::<Vec<_>>();
+                    index_of_coincidence(&column)
+                })
+                .sum::<f64>()
+                / length as f64;
+            let kasiski = if distances.is_empty() || length == 1 {
+                0.0
+            } else {
+                distances.iter().filter(|d| *d % length == 0).count() as f64
+                    / distances.len() as f64
+            };
+            (length, ic * (1.0 + kasiski))
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().map(|(length, _)| length).collect()
+}
+
+/// The shift that makes `column` look most like English, by chi-squared
+/// against English letter frequencies.
+fn best_shift(column: &[u8]) -> u8 {
+    let mut counts = [0usize; 26];
+    for letter in column {
+        counts[*letter as usize] += 1;
+    }
+    let chi_squared = |shift: u8| {
+        (0..26)
+            .map(|plain| {
+                let observed = counts[(plain + shift as usize) % 26] as f64;
+                let expected = ENGLISH_FREQUENCIES[plain] * column.len() as f64;
+                (observed - expected).powi(2) / expected
+            })
+            .sum::<f64>()
+    };
+    (0..26)
+        .min_by(|a, b| chi_squared(*a).total_cmp(&chi_squared(*b)))
+        .unwrap()
+}
+
+/// Scores plaintext by how much of it is dictionary words.
+struct Scorer<'f> {
+    map: &'f fst::Map<Mmap>,
+}
+
+impl<'f> Scorer<'f> {
+    /// The fraction of letters in `plaintext` that are in dictionary words.
+    /// A run of letters that isn't a word (because there were no spaces, or
+    /// it's a typo) still counts for the longest words it starts with.
+    fn score(&self, plaintext: &str) -> f64 {
+        let mut letters = 0;
+        let mut covered = 0;
+        for run in plaintext
+            .split(|c: char| !c.is_ascii_lowercase())
+            .filter(|run| !run.is_empty())
+        {
+            letters += run.len();
+            if self.map.contains_key(run) {
+                covered += run.len();
+                continue;
+            }
+            let bytes = run.as_bytes();
+            let mut start = 0;
+            while start < bytes.len() {
+                let mut cursor = Some(FstCursor::new(self.map.as_fst()));
+                let mut longest = 0;
+                for (i, b) in bytes[start..].iter().enumerate() {
+                    cursor = cursor.and_then(|cursor| cursor.step(&[*b]));
+                    match cursor {
+                        Some(cursor) if cursor.frequency().is_some() => longest = i + 1,
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                if longest >= MIN_EMBEDDED_WORD_LENGTH {
+                    covered += longest;
+                    start += longest;
+                } else {
+                    start += 1;
+                }
+            }
+        }
+        if letters == 0 {
+            0.0
+        } else {
+            covered as f64 / letters as f64
+        }
+    }
+}
+
+/// Tries every key for `cipher` (or for Vigenère, the best keys for the
+/// likeliest key lengths) and returns the decryptions, best first. Each
+/// decryption is charged to `limits` by the number of letters.
+pub fn crack(
+    dictionary: &Dictionary,
+    ciphertext: &str,
+    cipher: Cipher,
+    limits: &QueryLimits,
+) -> Result<CrackResults, String> {
+    crack_with_budget(dictionary, ciphertext, cipher, &mut WorkBudget::new(limits))
+}
+
+fn crack_with_budget(
+    dictionary: &Dictionary,
+    ciphertext: &str,
+    cipher: Cipher,
+    budget: &mut WorkBudget,
+) -> Result<CrackResults, String> {
+    let map = dictionary.fst_map()?;
+    let scorer = Scorer { map };
+    let letters = ciphertext
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase() as u8 - b'a')
+        .collect::<Vec<_>>();
+    if letters.is_empty() {
+        return Err("Ciphertext has no letters".to_string());
+    }
+    if letters.len() > MAX_CIPHERTEXT_LENGTH {
+        return Err(format!(
+            "Ciphertext can be at most {} letters",
+            MAX_CIPHERTEXT_LENGTH
+        ));
+    }
+    let mut candidates = vec![];
+    match cipher {
+        Cipher::Caesar => {
+            for shift in 1..26 {
+                candidates.push((shift.to_string(), CandidateKey::Affine(1, shift)));
+            }
+        }
+        Cipher::Affine => {
+            for a in (1..26).filter(|a| modular_inverse(*a).is_some()) {
+                for b in 0..26 {
+                    // that's just the ciphertext
+                    if (a, b) != (1, 0) {
+                        candidates.push((format!("{},{}", a, b), CandidateKey::Affine(a, b)));
+                    }
+                }
+            }
+        }
+        Cipher::Atbash => candidates.push((String::new(), CandidateKey::Affine(25, 25))),
+        Cipher::Vigenere => {
+            let mut keys = HashSet::new();
+            for length in estimate_key_lengths(&letters)
+                .into_iter()
+                .take(KEY_LENGTHS_TRIED)
+            {
+                let key = (0..length)
+                    .map(|start| {
+                        let column = letters
+                            .iter()
+                            .skip(start)
+                            .step_by(length)
+                            .copied()
+                            .collect::<Vec<_>>();
+                        best_shift(&column)
+                    })
+                    .collect::<Vec<_>>();
+                if keys.insert(key.clone()) {
+                    let keyword = key.iter().map(|k| (k + b'a') as char).collect();
+                    candidates.push((keyword, CandidateKey::Vigenere(key)));
+                }
+            }
+        }
+    }
+    let mut results = CrackResults::default();
+    for (key, candidate) in candidates {
+        // decrypting and scoring both go over every letter
+        if !(0..letters.len()).all(|_| budget.charge()) {
+            results.truncated = true;
+            break;
+        }
+        let plaintext = match candidate {
+            CandidateKey::Affine(a, b) => decrypt_affine(ciphertext, a, b)?,
+            CandidateKey::Vigenere(key) => decrypt_vigenere(ciphertext, &key)?,
+        };
+        results.decryptions.push(Decryption {
+            cipher,
+            key,
+            score: scorer.score(&plaintext),
+            plaintext,
+        });
+    }
+    results
+        .decryptions
+        .sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(results)
+}
+
+/// Cracks `ciphertext` with every kind of cipher, to find which one it is.
+/// All of them share one budget.
+pub fn crack_any(
+    dictionary: &Dictionary,
+    ciphertext: &str,
+    limits: &QueryLimits,
+) -> Result<CrackResults, String> {
+    let mut budget = WorkBudget::new(limits);
+    let mut results = CrackResults::default();
+    for cipher in ALL_CIPHERS {
+        let cracked = crack_with_budget(dictionary, ciphertext, cipher, &mut budget)?;
+        results.decryptions.extend(cracked.decryptions);
+        if cracked.truncated {
+            results.truncated = true;
+            break;
+        }
+    }
+    // stable, so for a tie the simpler cipher wins
+    results
+        .decryptions
+        .sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(results)
+}
+
+/// Handles a query string with mode `Caesar`, `Affine`, `Atbash`,
+/// `Vigenere`, or `Cipher` (to try them all), where `pattern` is the
+/// ciphertext.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let ciphertext = query_parts
+        .get("pattern")
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?;
+    let mode = query_parts
+        .get("mode")
+        .ok_or_else(|| String::from("Internal error - no mode specified!"))?;
+    let dictionary = Dictionary::open()?;
+    let limits = QueryLimits::default();
+    let results = match mode.as_str() {
+        "Cipher" => crack_any(&dictionary, ciphertext, &limits)?,
+        mode => crack(&dictionary, ciphertext, Cipher::try_from(mode)?, &limits)?,
+    };
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .decryptions
+            .into_iter()
+            .take(MAX_RESULTS_RETURNED)
+            .map(|d| {
+                json::object! {
+                    "cipher" => d.cipher.name(),
+                    "key" => d.key,
+                    "plaintext" => d.plaintext,
+                    "score" => d.score
+                }
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAINTEXT: &str = "there is this tree and that is the house where the other one said that these were not there when i was there with the other";
+
+    fn encrypt_vigenere(plaintext: &str, key: &str) -> String {
+        let key = key.bytes().map(|b| b - b'a').collect::<Vec<_>>();
+        let mut position = 0;
+        map_letters(plaintext, |p| {
+            let shift = key[position % key.len()];
+            position += 1;
+            (p + shift) % 26
+        })
+    }
+
+    #[test]
+    fn test_decrypt_affine() {
+        assert_eq!("the end.", decrypt_affine("wkh hqg.", 1, 3).unwrap());
+        assert_eq!("the end", decrypt_affine("GSV VMW", 25, 25).unwrap());
+        // 5x + 8
+        assert_eq!("the", decrypt_affine("zrc", 5, 8).unwrap());
+        assert!(decrypt_affine("zrc", 2, 8).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_vigenere() {
+        let ciphertext = encrypt_vigenere("the end", "key");
+        assert_eq!("dlc orb", ciphertext);
+        assert_eq!(
+            Ok("the end".to_string()),
+            decrypt_vigenere(&ciphertext, &[10, 4, 24])
+        );
+        assert!(decrypt_vigenere(&ciphertext, &[]).is_err());
+    }
+
+    #[test]
+    fn test_estimate_key_lengths() {
+        let ciphertext = encrypt_vigenere(&PLAINTEXT.replace(' ', ""), "tea");
+        let letters = ciphertext.bytes().map(|b| b - b'a').collect::<Vec<_>>();
+        let lengths = estimate_key_lengths(&letters);
+        assert!(lengths[..KEY_LENGTHS_TRIED].contains(&3));
+    }
+
+    #[test]
+    fn test_crack() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        let best = |ciphertext: &str, cipher| {
+            let results = crack(&dictionary, ciphertext, cipher, &limits).unwrap();
+            assert!(!results.truncated);
+            results.decryptions[0].clone()
+        };
+        let caesar = best("WKH HQG", Cipher::Caesar);
+        assert_eq!(
+            ("3", "the end"),
+            (caesar.key.as_str(), caesar.plaintext.as_str())
+        );
+        assert_eq!(1.0, caesar.score);
+        assert_eq!("the end", best("zrc cvx", Cipher::Affine).plaintext);
+        assert_eq!("the end", best("GSV VMW", Cipher::Atbash).plaintext);
+        let vigenere = best(&encrypt_vigenere(PLAINTEXT, "tea"), Cipher::Vigenere);
+        assert_eq!("tea", vigenere.key);
+        assert_eq!(PLAINTEXT, vigenere.plaintext);
+        assert!(crack(&dictionary, "123", Cipher::Caesar, &limits).is_err());
+    }
+
+    #[test]
+    fn test_crack_any() {
+        let dictionary = Dictionary::open().unwrap();
+        let results = crack_any(&dictionary, "GSV VMW", &QueryLimits::default()).unwrap();
+        assert!(!results.truncated);
+        assert_eq!("the end", results.decryptions[0].plaintext);
+        // Atbash is also an affine cipher, but simpler
+        assert_eq!(Cipher::Atbash, results.decryptions[0].cipher);
+    }
+
+    #[test]
+    fn test_crack_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        // "WKH HQG" has six letters, so this is enough for ten keys
+        let limits = QueryLimits {
+            max_scanned_entries: 60,
+            ..QueryLimits::default()
+        };
+        let results = crack(&dictionary, "WKH HQG", Cipher::Caesar, &limits).unwrap();
+        assert!(results.truncated);
+        assert_eq!(10, results.decryptions.len());
+        // the ciphers share the budget, so Atbash is never tried
+        let results = crack_any(&dictionary, "WKH HQG", &limits).unwrap();
+        assert!(results.truncated);
+        assert!(results
+            .decryptions
+            .iter()
+            .all(|d| d.cipher == Cipher::Caesar));
+    }
+}
@@ -0,0 +1,438 @@
+use crate::{
+    extend_cipher_key, parse_min_frequency, CipherKey, Dictionary, PatternMode, QueryLimits,
+    WorkBudget,
+};
+use std::collections::{BTreeMap, HashMap};
+
+// One number for each letter of the alphabet.
+const MAX_NUMBER: u8 = 26;
+
+/// A codeword grid: each cell is either a number from 1 to 26 standing for
+/// a letter, or a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodewordGrid {
+    rows: usize,
+    columns: usize,
+    cells: Vec<Option<u8>>,
+}
+
+/// A run of two or more cells across or down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The cells, as `row * columns + column`.
+    pub cells: Vec<usize>,
+    pub numbers: Vec<u8>,
+}
+
+impl Entry {
+    /// The entry as a cryptogram pattern, with each number written as an
+    /// uppercase letter (1 is A, 2 is B and so on). These are just names
+    /// for the numbers - A doesn't mean the number stands for a.
+    fn pattern(&self) -> String {
+        self.numbers
+            .iter()
+            .map(|n| (b'A' + n - 1) as char)
+            .collect()
+    }
+}
+
+impl CodewordGrid {
+    /// Parses a grid written as rows separated by newlines or `/`, with
+    /// cells separated by spaces or commas. Blocks are `#` or `.`.
+    pub fn parse(grid: &str) -> Result<CodewordGrid, String> {
+        let rows = grid
+            .split(['\n', '/'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                row.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|cell| !cell.is_empty())
+                    .map(|cell| match cell {
+                        "#" | "." => Ok(None),
+                        _ => match cell.parse::<u8>() {
+                            Ok(n) if (1..=MAX_NUMBER).contains(&n) => Ok(Some(n)),
+                            _ => Err(format!("Invalid cell {}", cell)),
+                        },
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if rows.is_empty() || rows[0].is_empty() {
+            return Err("Grid is empty".to_string());
+        }
+        let columns = rows[0].len();
+        if rows.iter().any(|row| row.len() != columns) {
+            return Err("All rows must be the same length".to_string());
+        }
+        Ok(CodewordGrid {
+            rows: rows.len(),
+            columns,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    /// All the entries across, then all the entries down.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = vec![];
+        let across = (0..self.rows).map(|r| {
+            (0..self.columns)
+                .map(|c| r * self.columns + c)
+                .collect::<Vec<_>>()
+        });
+        let down = (0..self.columns).map(|c| {
+            (0..self.rows)
+                .map(|r| r * self.columns + c)
+                .collect::<Vec<_>>()
+        });
+        for line in across.chain(down) {
+            for run in line.split(|cell| self.cells[*cell].is_none()) {
+                if run.len() >= 2 {
+                    entries.push(Entry {
+                        cells: run.to_vec(),
+                        numbers: run.iter().map(|cell| self.cells[*cell].unwrap()).collect(),
+                    });
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// Parses given letters written like `3=e,17=q`.
+pub fn parse_given(given: &str) -> Result<Vec<(u8, char)>, String> {
+    given
+        .split(',')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (number, letter) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid given letter {}", pair))?;
+            let number = number
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (1..=MAX_NUMBER).contains(n))
+                .ok_or_else(|| format!("Invalid number {}", number))?;
+            let mut letters = letter.trim().chars();
+            match (letters.next(), letters.next()) {
+                (Some(letter), None) if letter.is_ascii_alphabetic() => {
+                    Ok((number, letter.to_ascii_lowercase()))
+                }
+                _ => Err(format!("Invalid letter {}", letter)),
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodewordSolution {
+    /// The letter each number stands for. Numbers that aren't in any
+    /// entry are left out.
+    pub key: BTreeMap<u8, char>,
+    /// The word for each entry, in the order of `CodewordGrid::entries()`.
+    pub words: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodewordResult {
+    /// The solution with the most common words, or `None` if there isn't one.
+    pub solution: Option<CodewordSolution>,
+    pub truncated: bool,
+}
+
+struct Solver<'a> {
+    patterns: Vec<String>,
+    candidates: Vec<Vec<(String, u64)>>,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+}
+
+impl<'a> Solver<'a> {
+    /// Keeps the candidates for each entry that are still consistent with
+    /// `key`, or returns `None` if some entry has none left.
+    fn narrow(&mut self, key: &CipherKey, live: &[Vec<usize>]) -> Option<Vec<Vec<usize>>> {
+        let mut narrowed = vec![];
+        for (entry, candidates) in live.iter().enumerate() {
+            let mut remaining = vec![];
+            for candidate in candidates {
+                if !self.budget.charge() {
+                    self.truncated = true;
+                    return None;
+                }
+                let word = &self.candidates[entry][*candidate].0;
+                if extend_cipher_key(word, &self.patterns[entry], &mut key.clone()) {
+                    remaining.push(*candidate);
+                }
+            }
+            if remaining.is_empty() {
+                return None;
+            }
+            narrowed.push(remaining);
+        }
+        Some(narrowed)
+    }
+
+    fn solve(&mut self, key: &CipherKey, live: &[Vec<usize>]) -> Option<CipherKey> {
+        // fill in the entry with the fewest options first
+        let next = (0..self.patterns.len())
+            .filter(|entry| {
+                self.patterns[*entry]
+                    .chars()
+                    .any(|c| !key.iter().any(|(symbol, _)| *symbol == c))
+            })
+            .min_by_key(|entry| live[*entry].len());
+        let entry = match next {
+            Some(entry) => entry,
+            // every number is known and every entry is a word
+            None => return Some(key.clone()),
+        };
+        for candidate in &live[entry] {
+            let mut new_key = key.clone();
+            let word = &self.candidates[entry][*candidate].0;
+            if !extend_cipher_key(word, &self.patterns[entry], &mut new_key) {
+                continue;
+            }
+            if let Some(new_live) = self.narrow(&new_key, live) {
+                if let Some(solution) = self.solve(&new_key, &new_live) {
+                    return Some(solution);
+                }
+            }
+            if self.truncated {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Solves a codeword puzzle, using only words with at least
+/// `min_frequency`. Entries are tried most common word first, so the
+/// solution found favors common words.
+pub fn solve_codeword(
+    dictionary: &Dictionary,
+    grid: &CodewordGrid,
+    given: &[(u8, char)],
+    min_frequency: u64,
+    limits: &QueryLimits,
+) -> Result<CodewordResult, String> {
+    let entries = grid.entries();
+    if entries.is_empty() {
+        return Err("Grid has no entries".to_string());
+    }
+    let mut key = CipherKey::new();
+    for (number, letter) in given {
+        let symbol = (b'A' + number - 1) as char;
+        if !extend_cipher_key(&letter.to_string(), &symbol.to_string(), &mut key) {
+            return Err(format!("Conflicting given letter for {}", number));
+        }
+    }
+    let patterns = entries.iter().map(|e| e.pattern()).collect::<Vec<_>>();
+    // one budget for finding the candidates and solving
+    let mut budget = WorkBudget::new(limits);
+    let mut candidates = vec![];
+    for pattern in &patterns {
+        // Crossword mode, since in a codeword a number can stand for the
+        // letter its symbol happens to be
+        let search_pattern = pattern
+            .chars()
+            .map(|c| key.iter().find(|(s, _)| *s == c).map_or('?', |(_, l)| *l))
+            .collect::<String>();
+        let results = dictionary.search_with_budget(
+            PatternMode::Crossword,
+            &search_pattern,
+            "",
+            &mut budget,
+        )?;
+        if results.truncated {
+            return Ok(CodewordResult {
+                solution: None,
+                truncated: true,
+            });
+        }
+        candidates.push(
+            results
+                .words
+                .into_iter()
+                .filter(|(_, frequency)| *frequency >= min_frequency)
+                .collect::<Vec<_>>(),
+        );
+    }
+    let mut solver = Solver {
+        patterns,
+        candidates,
+        budget,
+        truncated: false,
+    };
+    let all = solver
+        .candidates
+        .iter()
+        .map(|c| (0..c.len()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let solution = solver
+        .narrow(&key, &all)
+        .and_then(|live| solver.solve(&key, &live));
+    let solution = solution.map(|key| CodewordSolution {
+        key: key
+            .iter()
+            .map(|(symbol, letter)| (*symbol as u8 - b'A' + 1, *letter))
+            .collect(),
+        words: solver
+            .patterns
+            .iter()
+            .map(|pattern| {
+                pattern
+                    .chars()
+                    .map(|c| key.iter().find(|(s, _)| *s == c).unwrap().1)
+                    .collect()
+            })
+            .collect(),
+    });
+    Ok(CodewordResult {
+        solution,
+        truncated: solver.truncated,
+    })
+}
+
+/// Handles a query string with mode `Codeword`, where `pattern` is the grid
+/// (see `CodewordGrid::parse()`), `given` is the given letters (see
+/// `parse_given()`) and `min_frequency` is optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let grid = CodewordGrid::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    )?;
+    let given = parse_given(query_parts.get("given").map_or("", |g| g.as_str()))?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let result = solve_codeword(
+        &Dictionary::open()?,
+        &grid,
+        &given,
+        min_frequency,
+        &QueryLimits::default(),
+    )?;
+    if result.truncated {
+        return Err("Query took too long - try giving more letters".to_string());
+    }
+    let solution = result
+        .solution
+        .ok_or_else(|| "No solution found".to_string())?;
+    let mut key = json::JsonValue::new_object();
+    for (number, letter) in &solution.key {
+        key[number.to_string()] = letter.to_string().into();
+    }
+    Ok(json::object! { "key" => key, "words" => solution.words })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 2 3    t h e
+    // 4 # 5    i # n
+    // 3 # 6    e # d
+    const GRID: &str = "1 2 3\n4 # 5\n3 # 6";
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = CodewordGrid::parse(GRID).unwrap();
+        assert_eq!(grid, CodewordGrid::parse("1,2,3/4,.,5/3,.,6").unwrap());
+        assert!(CodewordGrid::parse("1 2\n3").is_err());
+        assert!(CodewordGrid::parse("1 27").is_err());
+        assert!(CodewordGrid::parse("1 x").is_err());
+        assert!(CodewordGrid::parse("").is_err());
+    }
+
+    #[test]
+    fn test_entries() {
+        let entries = CodewordGrid::parse(GRID).unwrap().entries();
+        assert_eq!(
+            vec![vec![1, 2, 3], vec![1, 4, 3], vec![3, 5, 6]],
+            entries
+                .iter()
+                .map(|e| e.numbers.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![2, 5, 8], entries[2].cells);
+        assert_eq!("CEF", entries[2].pattern());
+    }
+
+    #[test]
+    fn test_parse_given() {
+        assert_eq!(Ok(vec![(3, 'e'), (17, 'q')]), parse_given("3=e, 17=Q"));
+        assert_eq!(Ok(vec![]), parse_given(""));
+        assert!(parse_given("3").is_err());
+        assert!(parse_given("0=a").is_err());
+        assert!(parse_given("3=ab").is_err());
+    }
+
+    #[test]
+    fn test_solve_codeword() {
+        let dictionary = Dictionary::open().unwrap();
+        let grid = CodewordGrid::parse(GRID).unwrap();
+        let result = solve_codeword(
+            &dictionary,
+            &grid,
+            &[(1, 't'), (2, 'h'), (4, 'i')],
+            0,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!result.truncated);
+        let solution = result.solution.unwrap();
+        assert_eq!(vec!["the", "tie", "end"], solution.words);
+        assert_eq!(Some(&'e'), solution.key.get(&3));
+        assert_eq!(Some(&'d'), solution.key.get(&6));
+        assert_eq!(6, solution.key.len());
+    }
+
+    #[test]
+    fn test_solve_codeword_keeps_letters_distinct() {
+        let dictionary = Dictionary::open().unwrap();
+        // 1 2 2 would be "tee", but then 3 can't be e as well
+        let grid = CodewordGrid::parse("1 2 2\n3 # #").unwrap();
+        let result =
+            solve_codeword(&dictionary, &grid, &[(1, 't')], 0, &QueryLimits::default()).unwrap();
+        let solution = result.solution.unwrap();
+        assert_eq!("tee", solution.words[0]);
+        assert_ne!(Some(&'e'), solution.key.get(&3));
+    }
+
+    #[test]
+    fn test_solve_codeword_no_solution() {
+        let dictionary = Dictionary::open().unwrap();
+        let grid = CodewordGrid::parse(GRID).unwrap();
+        let result =
+            solve_codeword(&dictionary, &grid, &[(3, 'x')], 0, &QueryLimits::default()).unwrap();
+        assert_eq!(None, result.solution);
+        assert!(solve_codeword(
+            &dictionary,
+            &grid,
+            &[(1, 't'), (2, 't')],
+            0,
+            &QueryLimits::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_candidates_and_solving_share_a_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        let grid = CodewordGrid::parse(GRID).unwrap();
+        // enough for finding the candidates or for solving, but not both
+        let limits = QueryLimits {
+            max_scanned_entries: 150,
+            ..QueryLimits::default()
+        };
+        let result = solve_codeword(
+            &dictionary,
+            &grid,
+            &[(1, 't'), (2, 'h'), (4, 'i')],
+            0,
+            &limits,
+        )
+        .unwrap();
+        assert!(result.truncated);
+    }
+}
@@ -0,0 +1,51 @@
+use fst::raw::{Fst, Node, Output};
+
+/// A position partway through walking the dictionary FST one letter at a
+/// time, for searches that build words up as they go (grids, boards and so
+/// on) and want to stop as soon as no word starts with what they have.
+pub(crate) struct FstCursor<'f, D> {
+    fst: &'f Fst<D>,
+    node: Node<'f>,
+    output: Output,
+}
+
+// derive would require D: Copy, but only a reference to it is stored
+impl<'f, D> Clone for FstCursor<'f, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'f, D> Copy for FstCursor<'f, D> {}
+
+impl<'f, D: AsRef<[u8]>> FstCursor<'f, D> {
+    /// A cursor at the empty prefix.
+    pub(crate) fn new(fst: &'f Fst<D>) -> FstCursor<'f, D> {
+        FstCursor {
+            fst,
+            node: fst.root(),
+            output: Output::zero(),
+        }
+    }
+
+    /// Moves past `bytes`, or returns `None` if no word starts with the
+    /// prefix so far followed by `bytes`.
+    pub(crate) fn step(&self, bytes: &[u8]) -> Option<FstCursor<'f, D>> {
+        let mut cursor = *self;
+        for b in bytes {
+            let transition = cursor.node.transition(cursor.node.find_input(*b)?);
+            cursor.output = cursor.output.cat(transition.out);
+            cursor.node = cursor.fst.node(transition.addr);
+        }
+        Some(cursor)
+    }
+
+    /// The frequency of the prefix so far, if it's a word.
+    pub(crate) fn frequency(&self) -> Option<u64> {
+        if self.node.is_final() {
+            Some(self.output.cat(self.node.final_output()).value())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::{build_regex, letter_stats::recommend_letters, Dictionary, PatternMode, QueryLimits};
+
+/// How many wrong guesses are allowed by default (head, body, two arms and
+/// two legs).
+pub const DEFAULT_MAX_WRONG_GUESSES: usize = 6;
+// Most to least common in English words, for when there's nothing better to go on.
+const LETTERS_BY_FREQUENCY: &str = "etaoinshrdlcumwfgypbvkjxqz";
+
+/// A game of hangman, as the guesser sees it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HangmanGame {
+    pattern: String,
+    wrong_guesses: String,
+    max_wrong_guesses: usize,
+}
+
+impl HangmanGame {
+    /// Starts a game on `host`'s word. Only letters have to be guessed, so
+    /// anything else in the word (like an apostrophe or a hyphen) is shown
+    /// from the start.
+    pub fn new(host: &dyn Host, max_wrong_guesses: usize) -> HangmanGame {
+        HangmanGame {
+            pattern: host
+                .answer()
+                .chars()
+                .map(|c| if c.is_ascii_lowercase() { '?' } else { c })
+                .collect(),
+            wrong_guesses: String::new(),
+            max_wrong_guesses,
+        }
+    }
+
+    /// The word with unrevealed letters replaced by `?`, suitable for
+    /// Hangman searches.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The letters that have been guessed and aren't in the word, in the
+    /// order they were guessed.
+    pub fn wrong_guesses(&self) -> &str {
+        &self.wrong_guesses
+    }
+
+    pub fn guesses_left(&self) -> usize {
+        self.max_wrong_guesses
+            .saturating_sub(self.wrong_guesses.len())
+    }
+
+    pub fn is_guessed(&self, letter: char) -> bool {
+        self.pattern.contains(letter) || self.wrong_guesses.contains(letter)
+    }
+
+    pub fn is_won(&self) -> bool {
+        !self.pattern.contains('?')
+    }
+
+    pub fn is_lost(&self) -> bool {
+        !self.is_won() && self.guesses_left() == 0
+    }
+
+    /// Asks `host` where `letter` is, returning how many times it appears.
+    pub fn guess(&mut self, letter: char, host: &mut dyn Host) -> Result<usize, String> {
+        let letter = letter.to_ascii_lowercase();
+        if !letter.is_ascii_lowercase() {
+            return Err(format!("{} isn't a letter", letter));
+        }
+        if self.is_won() || self.is_lost() {
+            return Err("The game is over".to_string());
+        }
+        if self.is_guessed(letter) {
+            return Err(format!("{} has already been guessed", letter));
+        }
+        let positions = host.reveal(self, letter);
+        if positions.is_empty() {
+            self.wrong_guesses.push(letter);
+        } else {
+            self.pattern = self
+                .pattern
+                .chars()
+                .enumerate()
+                .map(|(i, c)| if positions.contains(&i) { letter } else { c })
+                .collect();
+        }
+        Ok(positions.len())
+    }
+}
+
+/// The player who knows (or claims to know) the word.
+pub trait Host {
+    /// Where `letter` is in the word, given the game so far.
+    fn reveal(&mut self, game: &HangmanGame, letter: char) -> Vec<usize>;
+    /// The word, as far as the host is concerned.
+    fn answer(&self) -> String;
+}
+
+/// A host who picks a word at the start and sticks with it.
+pub struct FixedWordHost {
+    word: String,
+}
+
+impl FixedWordHost {
+    pub fn new(word: &str) -> FixedWordHost {
+        FixedWordHost {
+            word: word.to_ascii_lowercase(),
+        }
+    }
+}
+
+impl Host for FixedWordHost {
+    fn reveal(&mut self, _game: &HangmanGame, letter: char) -> Vec<usize> {
+        self.word
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == letter)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn answer(&self) -> String {
+        self.word.clone()
+    }
+}
+
+/// A host who never actually picks a word. Each guess splits the words that
+/// are still possible up by where the letter would be revealed, and the
+/// host goes with whichever group is biggest.
+pub struct EvilHost {
+    candidates: Vec<(String, u64)>,
+}
+
+impl EvilHost {
+    /// Starts with every word of `length` letters in the dictionary.
+    pub fn new(dictionary: &Dictionary, length: usize) -> Result<EvilHost, String> {
+        let results = dictionary.search(
+            PatternMode::Hangman,
+            &"?".repeat(length),
+            "",
+            &QueryLimits::default(),
+        )?;
+        if results.truncated {
+            return Err("Query took too long - try a shorter word".to_string());
+        }
+        if results.words.is_empty() {
+            return Err(format!("No words of length {}", length));
+        }
+        Ok(EvilHost::from_candidates(results.words))
+    }
+
+    /// Starts with `candidates`, sorted by descending frequency.
+    pub fn from_candidates(candidates: Vec<(String, u64)>) -> EvilHost {
+        EvilHost { candidates }
+    }
+
+    /// The words that are still consistent with everything revealed so far.
+    pub fn candidates(&self) -> &[(String, u64)] {
+        &self.candidates
+    }
+}
+
+impl Host for EvilHost {
+    fn reveal(&mut self, game: &HangmanGame, letter: char) -> Vec<usize> {
+        // Each possible set of positions gives a new pattern, and the words
+        // matching that pattern are the ones that would have revealed the
+        // letter there.
+        let mut best: Option<(Vec<usize>, Vec<_>)> = None;
+        let mut options = vec![vec![]];
+        for (word, _) in &self.candidates {
+            let positions = game
+                .pattern()
+                .chars()
+                .zip(word.chars())
+                .enumerate()
+                .filter(|(_, (p, w))| *p == '?' && *w == letter)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            if !options.contains(&positions) {
+                options.push(positions);
+            }
+        }
+        for positions in options {
+            let mut wrong_guesses = game.wrong_guesses().to_string();
+            if positions.is_empty() {
+                wrong_guesses.push(letter);
+            }
+            let pattern = game
+                .pattern()
+                .chars()
+                .enumerate()
+                .map(|(i, c)| if positions.contains(&i) { letter } else { c })
+                .collect::<String>();
+            let regex = match build_regex(&pattern, &wrong_guesses, &PatternMode::Hangman) {
+                Ok(regex) => regex,
+                Err(_) => continue,
+            };
+            let matching = self
+                .candidates
+                .iter()
+                .filter(|(word, _)| regex.is_match(word))
+                .cloned()
+                .collect::<Vec<_>>();
+            // ties go to revealing fewer letters
+            let better = match &best {
+                None => true,
+                Some((best_positions, best_matching)) => {
+                    matching.len() > best_matching.len()
+                        || (matching.len() == best_matching.len()
+                            && positions.len() < best_positions.len())
+                }
+            };
+            if better {
+                best = Some((positions, matching));
+            }
+        }
+        let (positions, matching) = best.unwrap();
+        if !matching.is_empty() {
+            self.candidates = matching;
+        }
+        positions
+    }
+
+    fn answer(&self) -> String {
+        self.candidates
+            .first()
+            .map_or_else(String::new, |(word, _)| word.clone())
+    }
+}
+
+/// The player trying to guess the word.
+pub trait Guesser {
+    fn name(&self) -> String;
+    /// The next letter to guess, or `None` if there's nothing left to try.
+    fn choose_letter(&mut self, game: &HangmanGame) -> Option<char>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LetterChoice {
+    /// The letter most likely to be in the word.
+    MostLikely,
+    /// The letter expected to narrow down the possible words the most.
+    MostInformative,
+}
+
+/// Searches the dictionary for the pattern and guesses using the letter
+/// recommendations.
+pub struct RecommenderGuesser<'a> {
+    dictionary: &'a Dictionary,
+    choice: LetterChoice,
+}
+
+impl<'a> RecommenderGuesser<'a> {
+    pub fn new(dictionary: &'a Dictionary, choice: LetterChoice) -> RecommenderGuesser<'a> {
+        RecommenderGuesser { dictionary, choice }
+    }
+}
+
+impl<'a> Guesser for RecommenderGuesser<'a> {
+    fn name(&self) -> String {
+        match self.choice {
+            LetterChoice::MostLikely => "most likely letter".to_string(),
+            LetterChoice::MostInformative => "most informative letter".to_string(),
+        }
+    }
+
+    fn choose_letter(&mut self, game: &HangmanGame) -> Option<char> {
+        let words = self
+            .dictionary
+            .search(
+                PatternMode::Hangman,
+                game.pattern(),
+                game.wrong_guesses(),
+                &QueryLimits::default(),
+            )
+            .map(|results| results.words)
+            .unwrap_or_default();
+        let mut recommendations = recommend_letters(game.pattern(), &words);
+        if self.choice == LetterChoice::MostLikely {
+            // the sort is stable, so ties still go to the more informative letter
+            recommendations.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap());
+        }
+        recommendations
+            .iter()
+            .map(|r| r.letter)
+            .chain(LETTERS_BY_FREQUENCY.chars())
+            .find(|c| !game.is_guessed(*c))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub won: bool,
+    /// Every letter guessed, in order.
+    pub guesses: String,
+    pub wrong_guesses: usize,
+    pub answer: String,
+}
+
+/// Plays a game of hangman on `host`'s word until the guesser gets it or
+/// runs out of wrong guesses.
+pub fn play_game(
+    host: &mut dyn Host,
+    guesser: &mut dyn Guesser,
+    max_wrong_guesses: usize,
+) -> Result<GameResult, String> {
+    let mut game = HangmanGame::new(host, max_wrong_guesses);
+    let mut guesses = String::new();
+    while !game.is_won() && !game.is_lost() {
+        let letter = guesser
+            .choose_letter(&game)
+            .ok_or_else(|| format!("{} has no letters left to guess", guesser.name()))?;
+        game.guess(letter, host)?;
+        guesses.push(letter);
+    }
+    Ok(GameResult {
+        won: game.is_won(),
+        guesses,
+        wrong_guesses: game.wrong_guesses().len(),
+        answer: host.answer(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ScriptedGuesser {
+        letters: Vec<char>,
+    }
+
+    impl Guesser for ScriptedGuesser {
+        fn name(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn choose_letter(&mut self, _game: &HangmanGame) -> Option<char> {
+            if self.letters.is_empty() {
+                None
+            } else {
+                Some(self.letters.remove(0))
+            }
+        }
+    }
+
+    fn candidates(words: &[&str]) -> Vec<(String, u64)> {
+        words.iter().map(|w| (w.to_string(), 10)).collect()
+    }
+
+    #[test]
+    fn test_guess() {
+        let mut host = FixedWordHost::new("tee");
+        let mut game = HangmanGame::new(&host, 2);
+        assert_eq!(Ok(2), game.guess('e', &mut host));
+        assert_eq!("?ee", game.pattern());
+        assert_eq!(Ok(0), game.guess('x', &mut host));
+        assert_eq!("x", game.wrong_guesses());
+        assert_eq!(1, game.guesses_left());
+        assert!(game.guess('e', &mut host).is_err());
+        assert!(game.guess('1', &mut host).is_err());
+        assert_eq!(Ok(1), game.guess('T', &mut host));
+        assert!(game.is_won());
+        assert!(game.guess('z', &mut host).is_err());
+    }
+
+    #[test]
+    fn test_play_game_lost() {
+        let mut host = FixedWordHost::new("tee");
+        let mut guesser = ScriptedGuesser {
+            letters: "abce".chars().collect(),
+        };
+        let result = play_game(&mut host, &mut guesser, 3).unwrap();
+        assert_eq!(
+            GameResult {
+                won: false,
+                guesses: "abc".to_string(),
+                wrong_guesses: 3,
+                answer: "tee".to_string()
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_play_game_out_of_letters() {
+        let mut host = FixedWordHost::new("tee");
+        let mut guesser = ScriptedGuesser { letters: vec!['t'] };
+        assert!(play_game(&mut host, &mut guesser, 3).is_err());
+    }
+
+    #[test]
+    fn test_punctuation_is_shown() {
+        let host = FixedWordHost::new("Don't");
+        let game = HangmanGame::new(&host, DEFAULT_MAX_WRONG_GUESSES);
+        assert_eq!("???'?", game.pattern());
+        // every letter can be guessed without running out
+        let dictionary = Dictionary::open().unwrap();
+        let mut host = FixedWordHost::new("x-ray");
+        let mut guesser = RecommenderGuesser::new(&dictionary, LetterChoice::MostLikely);
+        let result = play_game(&mut host, &mut guesser, 26).unwrap();
+        assert!(result.won);
+    }
+
+    #[test]
+    fn test_evil_host_keeps_largest_class() {
+        let mut host = EvilHost::from_candidates(candidates(&["tee", "toe", "tie", "eat", "ear"]));
+        let mut game = HangmanGame::new(&host, DEFAULT_MAX_WRONG_GUESSES);
+        // "??e" and "e??" have two words each, "?ee" only has one
+        assert_eq!(Ok(1), game.guess('e', &mut host));
+        assert_eq!("??e", game.pattern());
+        assert_eq!(candidates(&["toe", "tie"]), host.candidates());
+        // "toe" and "tie" are one word each, so say there's no o
+        assert_eq!(Ok(0), game.guess('o', &mut host));
+        assert_eq!("tie", host.answer());
+    }
+
+    #[test]
+    fn test_evil_host_prefers_revealing_less() {
+        let mut host = EvilHost::from_candidates(candidates(&["tot", "too"]));
+        let mut game = HangmanGame::new(&host, DEFAULT_MAX_WRONG_GUESSES);
+        assert_eq!(Ok(1), game.guess('t', &mut host));
+        assert_eq!("t??", game.pattern());
+        assert_eq!("too", host.answer());
+    }
+
+    #[test]
+    fn test_recommender_beats_fixed_word() {
+        let dictionary = Dictionary::open().unwrap();
+        for choice in &[LetterChoice::MostLikely, LetterChoice::MostInformative] {
+            let mut host = FixedWordHost::new("there");
+            let mut guesser = RecommenderGuesser::new(&dictionary, *choice);
+            let result = play_game(&mut host, &mut guesser, DEFAULT_MAX_WRONG_GUESSES).unwrap();
+            assert!(result.won, "{}: {:?}", guesser.name(), result);
+        }
+    }
+
+    #[test]
+    fn test_evil_host_from_dictionary() {
+        let dictionary = Dictionary::open().unwrap();
+        let mut host = EvilHost::new(&dictionary, 4).unwrap();
+        let mut guesser = RecommenderGuesser::new(&dictionary, LetterChoice::MostLikely);
+        let result = play_game(&mut host, &mut guesser, 26).unwrap();
+        assert!(result.won);
+        assert_eq!(4, result.answer.len());
+        assert_eq!(result.answer, host.candidates()[0].0);
+    }
+}
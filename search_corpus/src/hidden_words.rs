@@ -0,0 +1,334 @@
+use crate::{fst_walk::FstCursor, parse_usize_param, Dictionary, QueryLimits, WorkBudget};
+use memmap::Mmap;
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_MIN_WORD_LENGTH: usize = 3;
+pub const DEFAULT_MAX_STEP: usize = 3;
+// Every letter starts an FST walk in the run of consecutive letters and
+// again for each step, so a long phrase would mostly use up the work budget.
+const MAX_PHRASE_LENGTH: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HidingMethod {
+    /// Consecutive letters, maybe running across the spaces between words.
+    Contiguous,
+    /// Every `step`th letter.
+    EveryNth(usize),
+    /// The first letters of consecutive words.
+    FirstLetters,
+}
+
+impl HidingMethod {
+    fn name(&self) -> &'static str {
+        match self {
+            HidingMethod::Contiguous => "contiguous",
+            HidingMethod::EveryNth(_) => "every_nth",
+            HidingMethod::FirstLetters => "first_letters",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HiddenWord {
+    pub word: String,
+    pub frequency: u64,
+    pub method: HidingMethod,
+    /// The index (among the phrase's letters, or words for `FirstLetters`)
+    /// the word starts at.
+    pub start: usize,
+    /// Whether the word runs across a break between words in the phrase.
+    pub across_words: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HiddenResults {
+    /// Sorted by descending frequency.
+    pub words: Vec<HiddenWord>,
+    pub truncated: bool,
+}
+
+struct SequenceSearch<'a, 'f> {
+    root: FstCursor<'f, Mmap>,
+    min_length: usize,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+}
+
+impl<'a, 'f> SequenceSearch<'a, 'f> {
+    /// Every run of at least `min_length` consecutive letters in `sequence`
+    /// that's a word, as (start, length, frequency).
+    fn find(&mut self, sequence: &[u8]) -> Vec<(usize, usize, u64)> {
+        let mut found = vec![];
+        for start in 0..sequence.len() {
+            let mut cursor = self.root;
+            for (i, letter) in sequence[start..].iter().enumerate() {
+                if !self.budget.charge() {
+                    self.truncated = true;
+                    return found;
+                }
+                cursor = match cursor.step(&[*letter]) {
+                    Some(cursor) => cursor,
+                    None => break,
+                };
+                if i + 1 >= self.min_length {
+                    if let Some(frequency) = cursor.frequency() {
+                        found.push((start, i + 1, frequency));
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Finds the dictionary words hidden in `phrase`: as consecutive letters
+/// (across spaces too), as every `n`th letter for `n` from 2 to `max_step`,
+/// and as the first letters of its words. Anything that isn't a letter
+/// separates words.
+pub fn find_hidden_words(
+    dictionary: &Dictionary,
+    phrase: &str,
+    min_length: usize,
+    max_step: usize,
+    limits: &QueryLimits,
+) -> Result<HiddenResults, String> {
+    let map = dictionary.fst_map()?;
+    let phrase_words = phrase
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    // which phrase word each letter is in
+    let mut word_of_letter = vec![];
+    let mut letters = vec![];
+    for (i, word) in phrase_words.iter().enumerate() {
+        letters.extend(word.bytes());
+        word_of_letter.extend(std::iter::repeat_n(i, word.len()));
+    }
+    if letters.is_empty() {
+        return Err("Phrase has no letters".to_string());
+    }
+    if letters.len() > MAX_PHRASE_LENGTH {
+        return Err(format!(
+            "Phrase can be at most {} letters",
+            MAX_PHRASE_LENGTH
+        ));
+    }
+    if max_step > MAX_PHRASE_LENGTH {
+        return Err(format!("Step can be at most {}", MAX_PHRASE_LENGTH));
+    }
+    // a longer step can only pick out one letter
+    let max_step = max_step.min(letters.len());
+    let mut search = SequenceSearch {
+        root: FstCursor::new(map.as_fst()),
+        min_length: min_length.max(1),
+        budget: WorkBudget::new(limits),
+        truncated: false,
+    };
+    let mut words = vec![];
+    let mut record = |method, start, letter_indices: &[usize], frequency, sequence: &[u8]| {
+        words.push(HiddenWord {
+            word: String::from_utf8(sequence.to_vec()).unwrap(),
+            frequency,
+            method,
+            start,
+            across_words: word_of_letter[letter_indices[0]]
+                != word_of_letter[*letter_indices.last().unwrap()],
+        })
+    };
+
+    for (start, length, frequency) in search.find(&letters) {
+        let indices = (start..start + length).collect::<Vec<_>>();
+        record(
+            HidingMethod::Contiguous,
+            start,
+            &indices,
+            frequency,
+            &letters[start..start + length],
+        );
+    }
+    'steps: for step in 2..=max_step {
+        for offset in 0..step {
+            if search.truncated {
+                break 'steps;
+            }
+            let indices = (offset..letters.len()).step_by(step).collect::<Vec<_>>();
+            let sequence = indices.iter().map(|i| letters[*i]).collect::<Vec<_>>();
+            for (start, length, frequency) in search.find(&sequence) {
+                record(
+                    HidingMethod::EveryNth(step),
+                    indices[start],
+                    &indices[start..start + length],
+                    frequency,
+                    &sequence[start..start + length],
+                );
+            }
+        }
+    }
+    let first_letters = phrase_words
+        .iter()
+        .map(|w| w.as_bytes()[0])
+        .collect::<Vec<_>>();
+    let first_indices = phrase_words
+        .iter()
+        .scan(0, |position, word| {
+            let index = *position;
+            *position += word.len();
+            Some(index)
+        })
+        .collect::<Vec<_>>();
+    let found = if search.truncated {
+        vec![]
+    } else {
+        search.find(&first_letters)
+    };
+    for (start, length, frequency) in found {
+        record(
+            HidingMethod::FirstLetters,
+            start,
+            &first_indices[start..start + length],
+            frequency,
+            &first_letters[start..start + length],
+        );
+    }
+
+    // the same word found the same way twice isn't interesting
+    let mut seen = HashSet::new();
+    words.retain(|w| seen.insert((w.word.clone(), w.method)));
+    words.sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.word.cmp(&b.word)));
+    Ok(HiddenResults {
+        words,
+        truncated: search.truncated,
+    })
+}
+
+/// Handles a query string with mode `Hidden`, where `pattern` is the phrase
+/// and `min_length` and `max_step` are optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let phrase = query_parts
+        .get("pattern")
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?;
+    let min_length = parse_usize_param(query_parts, "min_length", DEFAULT_MIN_WORD_LENGTH)?;
+    let max_step = parse_usize_param(query_parts, "max_step", DEFAULT_MAX_STEP)?;
+    let results = find_hidden_words(
+        &Dictionary::open()?,
+        phrase,
+        min_length,
+        max_step,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .words
+            .into_iter()
+            .map(|w| {
+                let mut entry = json::object! {
+                    "word" => w.word,
+                    "frequency" => w.frequency,
+                    "method" => w.method.name(),
+                    "start" => w.start,
+                    "across_words" => w.across_words
+                };
+                if let HidingMethod::EveryNth(step) = w.method {
+                    entry["step"] = step.into();
+                }
+                entry
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(phrase: &str, min_length: usize) -> Vec<HiddenWord> {
+        let results = find_hidden_words(
+            &Dictionary::open().unwrap(),
+            phrase,
+            min_length,
+            DEFAULT_MAX_STEP,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!results.truncated);
+        results.words
+    }
+
+    #[test]
+    fn test_contiguous() {
+        // "bathe reef" hides "there" across the space
+        let words = find("Bathe, reef!", 3);
+        let there = words
+            .iter()
+            .find(|w| w.word == "there" && w.method == HidingMethod::Contiguous)
+            .unwrap();
+        assert_eq!(2, there.start);
+        assert!(there.across_words);
+        let the = words.iter().find(|w| w.word == "the").unwrap();
+        assert!(!the.across_words);
+        assert!(words.iter().all(|w| w.word.len() >= 3));
+        assert!(words.windows(2).all(|w| w[0].frequency >= w[1].frequency));
+    }
+
+    #[test]
+    fn test_every_nth() {
+        // t.h.e
+        let words = find("tahxe", 3);
+        let the = words
+            .iter()
+            .find(|w| w.word == "the" && w.method == HidingMethod::EveryNth(2))
+            .unwrap();
+        assert_eq!(0, the.start);
+        assert!(!words
+            .iter()
+            .any(|w| w.word == "the" && w.method == HidingMethod::Contiguous));
+    }
+
+    #[test]
+    fn test_first_letters() {
+        let words = find("Tall houses eat", 3);
+        let the = words
+            .iter()
+            .find(|w| w.method == HidingMethod::FirstLetters)
+            .unwrap();
+        assert_eq!(("the", 0), (the.word.as_str(), the.start));
+        assert!(the.across_words);
+    }
+
+    #[test]
+    fn test_invalid_phrase() {
+        assert!(find_hidden_words(
+            &Dictionary::open().unwrap(),
+            "123 ...",
+            3,
+            DEFAULT_MAX_STEP,
+            &QueryLimits::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_max_step() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        // anything past the length of the phrase is the same as the length
+        let results = find_hidden_words(&dictionary, "bathe reef", 3, 500, &limits).unwrap();
+        assert!(!results.truncated);
+        assert!(find_hidden_words(&dictionary, "bathe reef", 3, usize::MAX, &limits).is_err());
+    }
+
+    #[test]
+    fn test_stops_when_truncated() {
+        let limits = QueryLimits {
+            max_scanned_entries: 5,
+            ..QueryLimits::default()
+        };
+        let results =
+            find_hidden_words(&Dictionary::open().unwrap(), "bathe reef", 3, 3, &limits).unwrap();
+        assert!(results.truncated);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    fst_walk::FstCursor, parse_min_frequency, parse_usize_param, Dictionary, QueryLimits,
+    WorkBudget,
+};
+use memmap::Mmap;
+use std::collections::{HashMap, HashSet};
+
+pub const SIDES: usize = 4;
+pub const LETTERS_PER_SIDE: usize = 3;
+pub const MIN_WORD_LENGTH: usize = 3;
+pub const DEFAULT_MAX_WORDS: usize = 4;
+// Only this many chains are returned from a query.
+const MAX_SOLUTIONS_RETURNED: usize = 20;
+const ALL_LETTERS: u16 = (1 << (SIDES * LETTERS_PER_SIDE)) - 1;
+
+/// The twelve letters around the sides of the box.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetterBox {
+    sides: Vec<Vec<u8>>,
+}
+
+impl LetterBox {
+    /// Parses the sides, separated by commas, `/` or whitespace, like
+    /// `hge,oir,nst,myv`.
+    pub fn parse(sides: &str) -> Result<LetterBox, String> {
+        let sides = sides
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|side| !side.is_empty())
+            .map(|side| side.to_ascii_lowercase().into_bytes())
+            .collect::<Vec<_>>();
+        if sides.len() != SIDES || sides.iter().any(|side| side.len() != LETTERS_PER_SIDE) {
+            return Err(format!(
+                "Box must have {} sides of {} letters",
+                SIDES, LETTERS_PER_SIDE
+            ));
+        }
+        let mut seen = HashSet::new();
+        for letter in sides.iter().flatten() {
+            if !letter.is_ascii_lowercase() {
+                return Err("Disallowed characters in box".to_string());
+            }
+            if !seen.insert(*letter) {
+                return Err(format!("{} is on the box twice", *letter as char));
+            }
+        }
+        Ok(LetterBox { sides })
+    }
+
+    /// The side `letter` is on and its bit in a mask of the box's letters,
+    /// or `None` if it isn't on the box.
+    fn position(&self, letter: u8) -> Option<(usize, u16)> {
+        self.sides.iter().enumerate().find_map(|(side, letters)| {
+            letters
+                .iter()
+                .position(|l| *l == letter)
+                .map(|i| (side, 1 << (side * LETTERS_PER_SIDE + i)))
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoxWord {
+    pub word: String,
+    pub frequency: u64,
+    /// Which of the box's letters the word uses.
+    mask: u16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LetterBoxedResults {
+    /// Every playable word, sorted by descending frequency.
+    pub words: Vec<BoxWord>,
+    /// The shortest chains that use every letter, most common words first.
+    pub solutions: Vec<Vec<String>>,
+    pub truncated: bool,
+}
+
+struct WordSearch<'a> {
+    letter_box: &'a LetterBox,
+    min_frequency: u64,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+    word: Vec<u8>,
+    found: Vec<BoxWord>,
+}
+
+impl<'a> WordSearch<'a> {
+    /// Walks the FST and the box together - the state is where we are in
+    /// the FST and which side the last letter was on, and the next letter
+    /// can be anything on a different side.
+    fn visit(&mut self, cursor: &FstCursor<Mmap>, last_side: Option<usize>, mask: u16) {
+        if self.word.len() >= MIN_WORD_LENGTH {
+            if let Some(frequency) = cursor.frequency() {
+                if frequency >= self.min_frequency {
+                    self.found.push(BoxWord {
+                        word: String::from_utf8(self.word.clone()).unwrap(),
+                        frequency,
+                        mask,
+                    });
+                }
+            }
+        }
+        for (side, letters) in self.letter_box.sides.iter().enumerate() {
+            if Some(side) == last_side {
+                continue;
+            }
+            for letter in letters {
+                if !self.budget.charge() {
+                    self.truncated = true;
+                    return;
+                }
+                if let Some(next) = cursor.step(&[*letter]) {
+                    let (_, bit) = self.letter_box.position(*letter).unwrap();
+                    self.word.push(*letter);
+                    self.visit(&next, Some(side), mask | bit);
+                    self.word.pop();
+                }
+            }
+        }
+    }
+}
+
+struct ChainSearch<'a> {
+    words: &'a [BoxWord],
+    by_first_letter: HashMap<u8, Vec<usize>>,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+    // (letters used, last letter, words left) that can't be finished
+    dead_ends: HashSet<(u16, u8, usize)>,
+    chain: Vec<usize>,
+    solutions: Vec<Vec<usize>>,
+}
+
+impl<'a> ChainSearch<'a> {
+    /// Finds every chain of exactly `words_left` more words, starting with
+    /// `last`, that uses the rest of the letters. Returns whether there
+    /// were any.
+    fn extend(&mut self, mask: u16, last: u8, words_left: usize) -> bool {
+        if mask == ALL_LETTERS {
+            if words_left == 0 {
+                self.solutions.push(self.chain.clone());
+                return true;
+            }
+            // a shorter chain already works
+            return false;
+        }
+        if words_left == 0 || self.dead_ends.contains(&(mask, last, words_left)) {
+            return false;
+        }
+        let mut any = false;
+        let next_words = self.by_first_letter.get(&last).cloned().unwrap_or_default();
+        for next in next_words {
+            if !self.budget.charge() {
+                self.truncated = true;
+                return any;
+            }
+            let word = &self.words[next];
+            if word.mask | mask == mask {
+                // no new letters
+                continue;
+            }
+            self.chain.push(next);
+            any |= self.extend(
+                mask | word.mask,
+                *word.word.as_bytes().last().unwrap(),
+                words_left - 1,
+            );
+            self.chain.pop();
+            if self.truncated {
+                return any;
+            }
+        }
+        if !any {
+            self.dead_ends.insert((mask, last, words_left));
+        }
+        any
+    }
+}
+
+/// Finds the words that can be made on `letter_box` (with at least
+/// `min_frequency`), and the chains of the fewest words, up to `max_words`,
+/// where each word starts with the last letter of the one before and every
+/// letter gets used.
+pub fn solve_letter_boxed(
+    dictionary: &Dictionary,
+    letter_box: &LetterBox,
+    min_frequency: u64,
+    max_words: usize,
+    limits: &QueryLimits,
+) -> Result<LetterBoxedResults, String> {
+    let map = dictionary.fst_map()?;
+    // one budget for finding the words and chaining them
+    let mut word_search = WordSearch {
+        letter_box,
+        min_frequency,
+        budget: WorkBudget::new(limits),
+        truncated: false,
+        word: vec![],
+        found: vec![],
+    };
+    word_search.visit(&FstCursor::new(map.as_fst()), None, 0);
+    let mut words = word_search.found;
+    words.sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.word.cmp(&b.word)));
+    if word_search.truncated || words.is_empty() {
+        return Ok(LetterBoxedResults {
+            words,
+            solutions: vec![],
+            truncated: word_search.truncated,
+        });
+    }
+
+    let mut by_first_letter: HashMap<u8, Vec<usize>> = HashMap::new();
+    for (i, word) in words.iter().enumerate() {
+        by_first_letter
+            .entry(word.word.as_bytes()[0])
+            .or_default()
+            .push(i);
+    }
+    let mut chain_search = ChainSearch {
+        words: &words,
+        by_first_letter,
+        budget: word_search.budget,
+        truncated: false,
+        dead_ends: HashSet::new(),
+        chain: vec![],
+        solutions: vec![],
+    };
+    // each word uses at least one new letter
+    let max_words = max_words.min(SIDES * LETTERS_PER_SIDE);
+    for length in 1..=max_words {
+        if !chain_search.budget.charge() {
+            chain_search.truncated = true;
+            break;
+        }
+        for (first, word) in words.iter().enumerate() {
+            chain_search.chain.push(first);
+            chain_search.extend(word.mask, *word.word.as_bytes().last().unwrap(), length - 1);
+            chain_search.chain.pop();
+            if chain_search.truncated {
+                break;
+            }
+        }
+        if !chain_search.solutions.is_empty() || chain_search.truncated {
+            break;
+        }
+    }
+    let mut solutions = chain_search.solutions;
+    let truncated = chain_search.truncated;
+    // the chains whose rarest word is most common first
+    let rarest = |chain: &[usize]| chain.iter().map(|i| words[*i].frequency).min();
+    solutions.sort_by(|a, b| rarest(b).cmp(&rarest(a)).then(a.cmp(b)));
+    solutions.truncate(MAX_SOLUTIONS_RETURNED);
+    let solutions = solutions
+        .into_iter()
+        .map(|chain| chain.into_iter().map(|i| words[i].word.clone()).collect())
+        .collect();
+    Ok(LetterBoxedResults {
+        words,
+        solutions,
+        truncated,
+    })
+}
+
+/// Handles a query string with mode `LetterBoxed`, where `pattern` is the
+/// sides of the box (see `LetterBox::parse()`), and `min_frequency` and
+/// `max_words` are optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let letter_box = LetterBox::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    )?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let max_words = parse_usize_param(query_parts, "max_words", DEFAULT_MAX_WORDS)?;
+    let results = solve_letter_boxed(
+        &Dictionary::open()?,
+        &letter_box,
+        min_frequency,
+        max_words,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long - try a higher frequency threshold".to_string());
+    }
+    Ok(json::object! {
+        "words" => json::JsonValue::Array(results
+            .words
+            .into_iter()
+            .map(|w| json::object! { "word" => w.word, "frequency" => w.frequency })
+            .collect()),
+        "solutions" => results.solutions
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: &str = "hge,oir,nst,myv";
+
+    fn solve(min_frequency: u64, max_words: usize) -> LetterBoxedResults {
+        let results = solve_letter_boxed(
+            &Dictionary::open().unwrap(),
+            &LetterBox::parse(BOX).unwrap(),
+            min_frequency,
+            max_words,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!results.truncated);
+        results
+    }
+
+    #[test]
+    fn test_parse_box() {
+        let letter_box = LetterBox::parse("HGE OIR/nst, myv").unwrap();
+        assert_eq!(letter_box, LetterBox::parse(BOX).unwrap());
+        assert_eq!(Some((1, 1 << 4)), letter_box.position(b'i'));
+        assert_eq!(None, letter_box.position(b'a'));
+        assert!(LetterBox::parse("hge,oir,nst").is_err());
+        assert!(LetterBox::parse("hge,oir,nst,myvw").is_err());
+        assert!(LetterBox::parse("hge,oir,nst,myh").is_err());
+        assert!(LetterBox::parse("hge,oir,nst,my1").is_err());
+    }
+
+    #[test]
+    fn test_words() {
+        let results = solve(0, DEFAULT_MAX_WORDS);
+        let words = results
+            .words
+            .iter()
+            .map(|w| w.word.as_str())
+            .collect::<Vec<_>>();
+        assert!(words.contains(&"some"));
+        assert!(words.contains(&"everything"));
+        // t and h are on different sides, but h and e aren't
+        assert!(!words.contains(&"the"));
+        // too short
+        assert!(!words.contains(&"so"));
+    }
+
+    #[test]
+    fn test_solutions() {
+        let results = solve(0, DEFAULT_MAX_WORDS);
+        assert!(results
+            .solutions
+            .contains(&vec!["some".to_string(), "everything".to_string()]));
+        let first = results.solutions[0].len();
+        assert!(results.solutions.iter().all(|s| s.len() == first));
+        for solution in &results.solutions {
+            assert!(solution
+                .windows(2)
+                .all(|w| w[0].as_bytes().last() == w[1].as_bytes().first()));
+        }
+        assert!(solve(0, 1).solutions.is_empty());
+    }
+
+    #[test]
+    fn test_words_and_chains_share_a_budget() {
+        // enough for finding the words or for chaining them, but not both
+        let limits = QueryLimits {
+            max_scanned_entries: 1450,
+            ..QueryLimits::default()
+        };
+        let results = solve_letter_boxed(
+            &Dictionary::open().unwrap(),
+            &LetterBox::parse(BOX).unwrap(),
+            0,
+            DEFAULT_MAX_WORDS,
+            &limits,
+        )
+        .unwrap();
+        assert!(results.truncated);
+        assert!(!results.words.is_empty());
+    }
+
+    #[test]
+    fn test_max_words_is_clamped() {
+        // a chain never needs more words than there are letters
+        assert_eq!(
+            solve(0, SIDES * LETTERS_PER_SIDE).solutions,
+            solve(0, usize::MAX).solutions
+        );
+    }
+
+    #[test]
+    fn test_no_words() {
+        let results = solve(u64::MAX, usize::MAX);
+        assert!(results.words.is_empty());
+        assert!(results.solutions.is_empty());
+    }
+}
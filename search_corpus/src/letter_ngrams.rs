@@ -0,0 +1,347 @@
+use crate::{try_find_processed_file, QueryLimits, WorkBudget};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{collections::BTreeMap, fs};
+
+// Letters from most to least common in English, for the first guess at a key.
+const ENGLISH_LETTER_ORDER: &[u8; 26] = b"etaoinshrdlcumwfgypbvkjxqz";
+// Hill climbing gets stuck on keys that are nearly right, so start again
+// from this many random keys.
+const RESTARTS: usize = 100;
+// Only trigram and quadgram tables are supported, which keeps the table of
+// every possible n-gram to 26^4 entries.
+const MIN_N: usize = 3;
+const MAX_N: usize = 4;
+// In the words being scored, letters that are already known are stored as
+// this plus the letter, and cipher letters as just the letter.
+const KNOWN: u8 = 26;
+
+/// Log probabilities of runs of `n` letters, from `letter_quadgrams.txt` or
+/// `letter_trigrams.txt` (see process_corpus).
+pub struct LetterNgrams {
+    n: usize,
+    log_probabilities: Vec<f32>,
+}
+
+impl LetterNgrams {
+    /// Opens the quadgram file, or the trigram file if there isn't one.
+    pub fn open() -> Result<LetterNgrams, String> {
+        let path = try_find_processed_file("letter_quadgrams.txt")
+            .or_else(|| try_find_processed_file("letter_trigrams.txt"))
+            .ok_or_else(|| "Couldn't find letter n-gram files!".to_string())?;
+        LetterNgrams::parse(&fs::read_to_string(path).map_err(|e| e.to_string())?)
+    }
+
+    /// Parses lines of "<n-gram> <count>". All the n-grams must be the same
+    /// length, either 3 or 4.
+    pub fn parse(text: &str) -> Result<LetterNgrams, String> {
+        let mut counts = vec![];
+        let mut n = None;
+        for line in text.lines() {
+            let mut parts = line.split_ascii_whitespace();
+            let (ngram, count) = match (parts.next(), parts.next()) {
+                (Some(ngram), Some(count)) => (ngram, count),
+                _ => return Err(format!("Invalid n-gram line {}", line)),
+            };
+            if !ngram.bytes().all(|b| b.is_ascii_lowercase())
+                || *n.get_or_insert(ngram.len()) != ngram.len()
+            {
+                return Err(format!("Invalid n-gram {}", ngram));
+            }
+            if !(MIN_N..=MAX_N).contains(&ngram.len()) {
+                return Err(format!(
+                    "N-grams must be {} to {} letters long",
+                    MIN_N, MAX_N
+                ));
+            }
+            counts.push((
+                index(ngram.bytes().map(|b| b - b'a')),
+                count.parse::<u64>().map_err(|e| e.to_string())?,
+            ));
+        }
+        let n = n.ok_or_else(|| "No n-grams found".to_string())?;
+        let total = counts.iter().map(|c| c.1 as f64).sum::<f64>();
+        // n-grams that never appear are rare rather than impossible
+        let floor = (0.01 / total).log10() as f32;
+        let mut log_probabilities = vec![floor; 26usize.pow(n as u32)];
+        for (index, count) in counts {
+            log_probabilities[index] = (count as f64 / total).log10() as f32;
+        }
+        Ok(LetterNgrams {
+            n,
+            log_probabilities,
+        })
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// How English-like `letters` (0 for a, 1 for b and so on) is - the sum
+    /// of the log probabilities of its n-grams.
+    pub fn score(&self, letters: &[u8]) -> f64 {
+        if letters.len() < self.n {
+            return 0.0;
+        }
+        letters
+            .windows(self.n)
+            .map(|w| self.log_probabilities[index(w.iter().copied())] as f64)
+            .sum()
+    }
+}
+
+fn index(letters: impl Iterator<Item = u8>) -> usize {
+    letters.fold(0, |acc, l| acc * 26 + l as usize)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NgramDecoding {
+    /// Cipher letter to the letter it stands for, for the letters in the
+    /// ciphertext.
+    pub key: BTreeMap<char, char>,
+    /// The ciphertext with each letter decoded, and everything else left as
+    /// it was.
+    pub plaintext: String,
+    pub score: f64,
+    pub truncated: bool,
+}
+
+/// Scores decoding `words` (cipher letters, 0 for A and so on, and known
+/// letters) with `key`.
+fn score_key(model: &LetterNgrams, words: &[Vec<u8>], key: &[u8; 26], buffer: &mut Vec<u8>) -> f64 {
+    words
+        .iter()
+        .map(|word| {
+            buffer.clear();
+            buffer.extend(word.iter().map(|c| {
+                if *c < KNOWN {
+                    key[*c as usize]
+                } else {
+                    c - KNOWN
+                }
+            }));
+            model.score(buffer)
+        })
+        .sum()
+}
+
+/// Whether cipher letter `c` may stand for the letter `key` gives it, where
+/// `forbidden` has a bitmask of the letters each cipher letter can't be.
+fn is_allowed(key: &[u8; 26], forbidden: &[u32; 26], c: usize) -> bool {
+    forbidden[c] & (1 << key[c]) == 0
+}
+
+/// Swaps letters in `key` until every cipher letter is allowed (see
+/// `is_allowed()`), as far as that's possible.
+fn make_allowed(key: &mut [u8; 26], forbidden: &[u32; 26]) {
+    for c in 0..26 {
+        if is_allowed(key, forbidden, c) {
+            continue;
+        }
+        let swap = (0..26)
+            .find(|d| forbidden[c] & (1 << key[*d]) == 0 && forbidden[*d] & (1 << key[c]) == 0);
+        if let Some(d) = swap {
+            key.swap(c, d);
+        }
+    }
+}
+
+/// Decodes a substitution cipher without a dictionary, by hill climbing on
+/// the key scored by `model`. This works on names and rare
+/// words that aren't in the word list, but needs a fair amount of
+/// ciphertext. Uppercase letters in `ciphertext` are cipher letters,
+/// lowercase letters are already known, and anything else separates words.
+/// As in a cryptogram, no cipher letter stands for itself, for a known
+/// letter, or for one of `absent_letters`.
+pub fn hill_climb(
+    model: &LetterNgrams,
+    ciphertext: &str,
+    absent_letters: &str,
+    seed: u64,
+    limits: &QueryLimits,
+) -> NgramDecoding {
+    let words = ciphertext
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            word.bytes()
+                .map(|b| {
+                    if b.is_ascii_uppercase() {
+                        b - b'A'
+                    } else {
+                        KNOWN + b - b'a'
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let taken = ciphertext
+        .bytes()
+        .chain(absent_letters.bytes())
+        .filter(|b| b.is_ascii_lowercase())
+        .fold(0u32, |taken, b| taken | 1 << (b - b'a'));
+    let mut forbidden = [0u32; 26];
+    for c in words.iter().flatten().filter(|c| **c < KNOWN) {
+        forbidden[*c as usize] = taken | 1 << c;
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut budget = WorkBudget::new(limits);
+    let mut truncated = false;
+    let mut buffer = vec![];
+
+    // first guess: the most common cipher letter is e, and so on
+    let mut frequencies = [0usize; 26];
+    for c in words.iter().flatten().filter(|c| **c < KNOWN) {
+        frequencies[*c as usize] += 1;
+    }
+    let mut order = (0..26u8).collect::<Vec<_>>();
+    order.sort_by_key(|c| std::cmp::Reverse(frequencies[*c as usize]));
+    let mut first_key = [0u8; 26];
+    for (c, plain) in order.iter().zip(ENGLISH_LETTER_ORDER.iter()) {
+        first_key[*c as usize] = plain - b'a';
+    }
+    make_allowed(&mut first_key, &forbidden);
+
+    let mut best_key = first_key;
+    let mut best_score = score_key(model, &words, &best_key, &mut buffer);
+    'restarts: for restart in 0..RESTARTS {
+        let mut key = first_key;
+        if restart > 0 {
+            key.shuffle(&mut rng);
+            make_allowed(&mut key, &forbidden);
+        }
+        let mut score = score_key(model, &words, &key, &mut buffer);
+        // swap pairs of letters in the key until no swap makes it better
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for a in 0..26 {
+                for b in a + 1..26 {
+                    if !budget.charge() {
+                        truncated = true;
+                        break 'restarts;
+                    }
+                    key.swap(a, b);
+                    if !is_allowed(&key, &forbidden, a) || !is_allowed(&key, &forbidden, b) {
+                        key.swap(a, b);
+                        continue;
+                    }
+                    let new_score = score_key(model, &words, &key, &mut buffer);
+                    if new_score > score {
+                        score = new_score;
+                        improved = true;
+                    } else {
+                        key.swap(a, b);
+                    }
+                }
+            }
+        }
+        if score > best_score {
+            best_score = score;
+            best_key = key;
+        }
+    }
+
+    let decode = |c: char| (best_key[(c as u8 - b'A') as usize] + b'a') as char;
+    NgramDecoding {
+        key: ciphertext
+            .chars()
+            .filter(|c| c.is_ascii_uppercase())
+            .map(|c| (c, decode(c)))
+            .collect(),
+        plaintext: ciphertext
+            .chars()
+            .map(|c| if c.is_ascii_uppercase() { decode(c) } else { c })
+            .collect(),
+        score: best_score,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(plaintext: &str, shift: u8) -> String {
+        plaintext
+            .chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() {
+                    ((c as u8 - b'a' + shift) % 26 + b'A') as char
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let model = LetterNgrams::parse("the 30\nand 10").unwrap();
+        assert_eq!(3, model.n());
+        let (the, and, xyz) = ([19, 7, 4], [0, 13, 3], [23, 24, 25]);
+        assert!(model.score(&the) > model.score(&and));
+        assert!(model.score(&and) > model.score(&xyz));
+        assert_eq!(0.0, model.score(&the[..2]));
+        assert!(LetterNgrams::parse("the 30\nthat 10").is_err());
+        assert!(LetterNgrams::parse("the").is_err());
+        assert!(LetterNgrams::parse("").is_err());
+        // the table would have 26^n entries
+        assert!(LetterNgrams::parse("abcdefghijkl 1").is_err());
+        assert!(LetterNgrams::parse("th 1").is_err());
+    }
+
+    #[test]
+    fn test_hill_climb() {
+        let model = LetterNgrams::open().unwrap();
+        let plaintext = "there is this tree and that is the house where the other one said that these were not there when i was there with the other";
+        let decoding = hill_climb(
+            &model,
+            &encode(plaintext, 3),
+            "",
+            0,
+            &QueryLimits::default(),
+        );
+        assert!(!decoding.truncated);
+        assert_eq!(plaintext, decoding.plaintext);
+        assert_eq!(Some(&'t'), decoding.key.get(&'W'));
+    }
+
+    #[test]
+    fn test_hill_climb_never_maps_a_letter_to_itself() {
+        let model = LetterNgrams::open().unwrap();
+        // the identity key, which a cryptogram never uses
+        let decoding = hill_climb(
+            &model,
+            "THERE IS THIS TREE AND THAT IS THE HOUSE",
+            "",
+            0,
+            &QueryLimits::default(),
+        );
+        assert!(decoding
+            .key
+            .iter()
+            .all(|(c, plain)| c.to_ascii_lowercase() != *plain));
+    }
+
+    #[test]
+    fn test_hill_climb_known_and_absent_letters() {
+        let model = LetterNgrams::open().unwrap();
+        let decoding = hill_climb(
+            &model,
+            "WKHUH LV WKLV tUHH",
+            "a",
+            0,
+            &QueryLimits::default(),
+        );
+        assert!(decoding
+            .plaintext
+            .split(' ')
+            .nth(3)
+            .unwrap()
+            .starts_with('t'));
+        assert!(decoding
+            .key
+            .values()
+            .all(|plain| *plain != 't' && *plain != 'a'));
+    }
+}
@@ -0,0 +1,213 @@
+/// For each letter that could still be hiding behind a `?` in `pattern`, the
+/// fraction of the total candidate frequency made up of candidates that have
+/// that letter in one of the unknown positions. This is what the frontend
+/// shows as "best letters to guess". Sorted by descending weight.
+pub fn letter_weights(pattern: &str, candidates: &[(String, u64)]) -> Vec<(char, f64)> {
+    let total: u64 = candidates.iter().map(|c| c.1).sum();
+    if total == 0 {
+        return vec![];
+    }
+    let mut weights = [0u64; 26];
+    for (word, frequency) in candidates {
+        let mut seen = [false; 26];
+        for (pattern_char, word_char) in pattern.chars().zip(word.chars()) {
+            if pattern_char == '?' && word_char.is_ascii_lowercase() {
+                seen[(word_char as u8 - b'a') as usize] = true;
+            }
+        }
+        for (i, letter_seen) in seen.iter().enumerate() {
+            if *letter_seen {
+                weights[i] += frequency;
+            }
+        }
+    }
+    let mut result = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .map(|(i, weight)| ((b'a' + i as u8) as char, *weight as f64 / total as f64))
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    result
+}
+
+/// The candidates that would reveal `letter` in the same positions of the
+/// unknown part of the pattern, and their total frequency.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetterOutcome {
+    /// Where the letter would be revealed - empty if it's not in the word.
+    pub positions: Vec<usize>,
+    pub total_frequency: u64,
+    /// The most common candidate in this outcome.
+    pub best_candidate: usize,
+}
+
+/// Splits `candidates` up by where `letter` would be revealed in the `?`
+/// positions of `pattern` if it were called.
+pub fn letter_outcomes(
+    pattern: &str,
+    candidates: &[(String, u64)],
+    letter: char,
+) -> Vec<LetterOutcome> {
+    let mut outcomes: Vec<LetterOutcome> = vec![];
+    for (i, (word, frequency)) in candidates.iter().enumerate() {
+        let positions = pattern
+            .chars()
+            .zip(word.chars())
+            .enumerate()
+            .filter(|(_, (pattern_char, word_char))| *pattern_char == '?' && *word_char == letter)
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+        match outcomes.iter_mut().find(|o| o.positions == positions) {
+            Some(outcome) => {
+                outcome.total_frequency += frequency;
+                if *frequency > candidates[outcome.best_candidate].1 {
+                    outcome.best_candidate = i;
+                }
+            }
+            None => outcomes.push(LetterOutcome {
+                positions,
+                total_frequency: *frequency,
+                best_candidate: i,
+            }),
+        }
+    }
+    outcomes
+}
+
+/// How good a letter would be to call next.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LetterRecommendation {
+    pub letter: char,
+    /// See `letter_weights()`.
+    pub weight: f64,
+    /// How many bits of information calling the letter is expected to give,
+    /// counting where it shows up and not just whether it does.
+    pub expected_information: f64,
+}
+
+/// For each letter that could be behind a `?` in `pattern`, both how likely
+/// it is to be there and how much calling it is expected to narrow down
+/// the candidates. Sorted by descending expected information.
+pub fn recommend_letters(pattern: &str, candidates: &[(String, u64)]) -> Vec<LetterRecommendation> {
+    let total = candidates.iter().map(|c| c.1).sum::<u64>() as f64;
+    let mut recommendations = letter_weights(pattern, candidates)
+        .into_iter()
+        .map(|(letter, weight)| {
+            // Which candidate it is determines the outcome, so the expected
+            // drop in entropy over the candidates is just the entropy of the
+            // outcome.
+            let expected_information = letter_outcomes(pattern, candidates, letter)
+                .iter()
+                .map(|outcome| {
+                    let p = outcome.total_frequency as f64 / total;
+                    -p * p.log2()
+                })
+                .sum::<f64>();
+            LetterRecommendation {
+                letter,
+                weight,
+                expected_information,
+            }
+        })
+        .collect::<Vec<_>>();
+    recommendations.sort_by(|a, b| {
+        b.expected_information
+            .partial_cmp(&a.expected_information)
+            .unwrap()
+            .then(b.weight.partial_cmp(&a.weight).unwrap())
+            .then(a.letter.cmp(&b.letter))
+    });
+    recommendations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letter_weights() {
+        let candidates = vec![
+            ("tie".to_string(), 30),
+            ("toe".to_string(), 10),
+            ("tee".to_string(), 10),
+        ];
+        let weights = letter_weights("t?e", &candidates);
+        assert_eq!(vec![('i', 0.6), ('e', 0.2), ('o', 0.2)], weights);
+    }
+
+    #[test]
+    fn test_letter_weights_only_counts_unknown_positions() {
+        let candidates = vec![("tee".to_string(), 10)];
+        assert_eq!(vec![('e', 1.0)], letter_weights("t?e", &candidates));
+        assert_eq!(
+            Vec::<(char, f64)>::new(),
+            letter_weights("tee", &candidates)
+        );
+    }
+
+    #[test]
+    fn test_letter_outcomes() {
+        let candidates = vec![
+            ("tee".to_string(), 10),
+            ("tie".to_string(), 30),
+            ("eve".to_string(), 5),
+            ("toe".to_string(), 10),
+        ];
+        let outcomes = letter_outcomes("??e", &candidates, 'e');
+        assert_eq!(
+            vec![
+                LetterOutcome {
+                    positions: vec![1],
+                    total_frequency: 10,
+                    best_candidate: 0
+                },
+                LetterOutcome {
+                    positions: vec![],
+                    total_frequency: 40,
+                    best_candidate: 1
+                },
+                LetterOutcome {
+                    positions: vec![0],
+                    total_frequency: 5,
+                    best_candidate: 2
+                },
+            ],
+            outcomes
+        );
+    }
+
+    #[test]
+    fn test_recommend_letters_prefers_splitting_letter() {
+        // s is in every candidate so it's the most likely letter, but it
+        // shows up in the same place every time so it tells us nothing.
+        let candidates = vec![
+            ("tas".to_string(), 30),
+            ("ats".to_string(), 30),
+            ("bas".to_string(), 20),
+            ("abs".to_string(), 20),
+        ];
+        let recommendations = recommend_letters("???", &candidates);
+        let letters = recommendations.iter().map(|r| r.letter).collect::<String>();
+        assert_eq!("tbas", letters);
+        assert_eq!(0.6, recommendations[0].weight);
+        assert!(recommendations[0].expected_information > 1.5);
+        assert_eq!(1.0, recommendations[3].weight);
+        assert_eq!(0.0, recommendations[3].expected_information);
+    }
+
+    #[test]
+    fn test_recommend_letters_no_information_from_certain_letter() {
+        let candidates = vec![("tie".to_string(), 30), ("toe".to_string(), 10)];
+        let recommendations = recommend_letters("t?e", &candidates);
+        assert_eq!(2, recommendations.len());
+        assert_eq!('i', recommendations[0].letter);
+        let certain = recommend_letters("t?e", &candidates[..1]);
+        assert_eq!(0.0, certain[0].expected_information);
+    }
+
+    #[test]
+    fn test_letter_weights_no_candidates() {
+        assert!(letter_weights("t?e", &[]).is_empty());
+    }
+}
@@ -1,29 +1,66 @@
 extern crate json;
 extern crate url;
 
-use fst::IntoStreamer;
+use fst::{IntoStreamer, Streamer};
 use memmap::Mmap;
 use regex::Regex;
 use regex_automata::dense;
-use smallvec::{smallvec, SmallVec};
+use smallvec::SmallVec;
 use std::{
-    collections::{HashMap, HashSet},
+    cell::OnceCell,
+    collections::HashMap,
     convert::TryFrom,
     fs::File,
     io::{self, BufRead},
     path::Path,
+    time::{Duration, Instant},
 };
 
-// Use FST if number of questions marks is below this
-// threshold. (set to 0 to never use FST, set to
-// 1000 to always use FST)
-const FST_QUESTION_MARK_THRESHOLD: usize = 6;
+pub mod acrostic;
+pub mod advisor;
+pub mod batch;
+pub mod boggle;
+pub mod classic_ciphers;
+pub mod codeword;
+mod fst_walk;
+pub mod hangman;
+pub mod hidden_words;
+pub mod letter_boxed;
+pub mod letter_ngrams;
+pub mod letter_stats;
+pub mod patristocrat;
+pub mod puzzle_generator;
+pub mod query_cache;
+pub mod scrabble;
+pub mod search_strategy;
+pub mod simulator;
+pub mod spelling_bee;
+pub mod transforms;
+pub mod word_index;
+pub mod word_ladder;
+pub mod word_search;
+pub mod wordle;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+use letter_ngrams::{hill_climb, LetterNgrams};
+use letter_stats::recommend_letters;
+use search_strategy::{choose_strategy, DictionaryStats, SearchStrategy};
+use word_index::WordIndex;
+use wordle::{best_guesses, WordleConstraints};
+
+/// The characters that can appear in dictionary words, in the order the
+/// word index (and the masks from `position_masks()`) use.
+pub const WORD_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz'-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PatternMode {
     WheelOfFortune,
     Crossword,
     Cryptogram,
+    /// The pattern is a list of past guesses and their feedback - see
+    /// `WordleConstraints::parse()`.
+    Wordle,
+    /// Like WheelOfFortune, but for a single word.
+    Hangman,
 }
 
 impl TryFrom<&str> for PatternMode {
@@ -34,20 +71,26 @@ impl TryFrom<&str> for PatternMode {
             "WheelOfFortune" => Ok(PatternMode::WheelOfFortune),
             "Crossword" => Ok(PatternMode::Crossword),
             "Cryptogram" => Ok(PatternMode::Cryptogram),
+            "Wordle" => Ok(PatternMode::Wordle),
+            "Hangman" => Ok(PatternMode::Hangman),
             _ => Err(()),
         }
     }
 }
 
 pub fn find_processed_file(filename: &str) -> String {
+    try_find_processed_file(filename).expect("Couldn't find file!")
+}
+
+fn try_find_processed_file(filename: &str) -> Option<String> {
     let mut path: String = format!("data/processed/{}", filename);
     for _i in 0..5 {
         if Path::new(&path).exists() {
-            return path;
+            return Some(path);
         }
         path = format!("../{}", path);
     }
-    panic!("Couldn't find file!");
+    None
 }
 
 ///
@@ -70,13 +113,7 @@ pub fn search_combinations(parts: &[Vec<char>]) -> Result<Vec<(String, u64)>, St
         regex_str.push(']');
     }
     dbg!(&regex_str);
-    let mmap = unsafe {
-        Mmap::map(
-            &File::open(find_processed_file("word_frequency.fst")).map_err(|e| e.to_string())?,
-        )
-        .map_err(|e| e.to_string())?
-    };
-    let map = fst::Map::new(mmap).map_err(|e| e.to_string())?;
+    let map = open_fst_map()?;
     let dfa = dense::Builder::new()
         .anchored(true)
         .build(&regex_str)
@@ -90,189 +127,748 @@ pub fn search_combinations(parts: &[Vec<char>]) -> Result<Vec<(String, u64)>, St
     Ok(results)
 }
 
-fn is_valid_string(s: &str, pattern: &str, mode: &PatternMode) -> bool {
-    if mode == &PatternMode::Cryptogram {
-        // the regex crate doesn't support backreferences, so make sure
-        // that capital letters in the pattern match in the result string
-        let mut mappings: SmallVec<[(char, char); 20]> = smallvec![];
-        let mut s_chars = s.chars();
-        for (_i, pattern_char) in pattern.char_indices() {
-            let s_char = s_chars.next().unwrap();
-            if pattern_char.is_ascii_uppercase() {
-                let mut found = false;
-                for entry in &mappings {
-                    if entry.0 == pattern_char {
-                        if entry.1 != s_char {
-                            return false;
-                        }
-                        found = true;
-                        break;
+/// What a word has to satisfy beyond the position masks, worked out once
+/// per query.
+enum WordCheck<'a> {
+    None,
+    // the masks can't say how many times each letter appears
+    Wordle(WordleConstraints),
+    // the regex crate doesn't support backreferences, so make sure that
+    // capital letters in the pattern match in the result string
+    Cryptogram(&'a str),
+}
+
+impl<'a> WordCheck<'a> {
+    fn new(pattern: &'a str, mode: &PatternMode) -> Result<WordCheck<'a>, String> {
+        Ok(match mode {
+            PatternMode::Wordle => WordCheck::Wordle(WordleConstraints::parse(pattern)?),
+            PatternMode::Cryptogram => WordCheck::Cryptogram(pattern),
+            _ => WordCheck::None,
+        })
+    }
+
+    fn allows(&self, s: &str) -> bool {
+        match self {
+            WordCheck::None => true,
+            WordCheck::Wordle(constraints) => constraints.allows(s),
+            WordCheck::Cryptogram(pattern) => extend_cipher_key(s, pattern, &mut CipherKey::new()),
+        }
+    }
+}
+
+/// Pairs of (uppercase letter in a cryptogram pattern, letter it stands for).
+type CipherKey = SmallVec<[(char, char); 26]>;
+
+/// Checks that `s` could be `pattern` under the cryptogram rules - each
+/// uppercase letter always stands for the same letter, and no two stand for
+/// the same letter - given the pairs already in `key`. Any new pairs are
+/// added to `key`.
+fn extend_cipher_key(s: &str, pattern: &str, key: &mut CipherKey) -> bool {
+    let mut s_chars = s.chars();
+    for pattern_char in pattern.chars() {
+        let s_char = s_chars.next().unwrap();
+        if pattern_char.is_ascii_uppercase() {
+            let mut found = false;
+            for entry in key.iter() {
+                if entry.0 == pattern_char {
+                    if entry.1 != s_char {
+                        return false;
                     }
+                    found = true;
+                    break;
                 }
-                if !found {
-                    for entry in &mappings {
-                        if entry.1 == s_char {
-                            return false;
-                        }
+            }
+            if !found {
+                for entry in key.iter() {
+                    if entry.1 == s_char {
+                        return false;
                     }
-                    mappings.push((pattern_char, s_char));
                 }
+                key.push((pattern_char, s_char));
             }
         }
     }
-    return true;
+    true
 }
 
-pub fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
-    let query_parts: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+/// Bounds on how much work a single query is allowed to do, so that
+/// pathological patterns can't tie up a shared server.
+#[derive(Clone, Debug)]
+pub struct QueryLimits {
+    /// Maximum memory (in bytes) the DFA built from the pattern may use.
+    /// Building stops early if the DFA gets too many states for 16-bit state
+    /// IDs, and the memory it uses is checked before searching with it.
+    pub max_dfa_bytes: usize,
+    /// Maximum number of dictionary entries to examine.
+    pub max_scanned_entries: usize,
+    /// Maximum wall-clock time to spend on the query.
+    pub max_duration: Duration,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_dfa_bytes: 10 * 1024 * 1024,
+            max_scanned_entries: 5_000_000,
+            max_duration: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The words matching a query, sorted by descending frequency.
+///
+/// If `truncated` is set, the query ran out of its budget and `words` only
+/// holds the matches found before that happened. `strategy` is how the words
+/// were found, or `None` if the pattern couldn't match anything, and
+/// `estimated_costs` is what each available strategy was expected to cost.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchResults {
+    pub words: Vec<(String, u64)>,
+    pub truncated: bool,
+    pub strategy: Option<SearchStrategy>,
+    pub estimated_costs: Vec<(SearchStrategy, f64)>,
+}
+
+// How often (in entries) to check the clock - Instant::now() isn't free.
+const TIME_CHECK_INTERVAL: usize = 1024;
+
+struct WorkBudget<'a> {
+    limits: &'a QueryLimits,
+    start: Instant,
+    scanned: usize,
+}
+
+impl<'a> WorkBudget<'a> {
+    fn new(limits: &'a QueryLimits) -> Self {
+        WorkBudget {
+            limits,
+            start: Instant::now(),
+            scanned: 0,
+        }
+    }
+
+    /// Records that one more entry has been examined. Returns false if the
+    /// budget is exhausted and the search should stop.
+    fn charge(&mut self) -> bool {
+        self.scanned += 1;
+        if self.scanned > self.limits.max_scanned_entries {
+            return false;
+        }
+        !self.scanned.is_multiple_of(TIME_CHECK_INTERVAL)
+            || self.start.elapsed() <= self.limits.max_duration
+    }
+
+    /// Whether the budget has run out, for work that shares one budget
+    /// across several searches.
+    fn is_exhausted(&self) -> bool {
+        self.scanned >= self.limits.max_scanned_entries
+            || self.start.elapsed() > self.limits.max_duration
+    }
+}
+
+fn open_fst_map() -> Result<fst::Map<Mmap>, String> {
+    let mmap = unsafe {
+        Mmap::map(
+            &File::open(find_processed_file("word_frequency.fst")).map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| e.to_string())?
+    };
+    fst::Map::new(mmap).map_err(|e| e.to_string())
+}
+
+// Building the DFA with 16-bit state IDs makes determinization give up once
+// it has more states than fit in one, instead of running until it's done.
+type DfaStateId = u16;
+
+fn build_dfa(
+    regex_pattern: &str,
+    limits: &QueryLimits,
+) -> Result<dense::DenseDFA<Vec<DfaStateId>, DfaStateId>, String> {
+    let dfa = dense::Builder::new()
+        .anchored(true)
+        .build_with_size::<DfaStateId>(regex_pattern)
+        .map_err(|e| match e.kind() {
+            regex_automata::ErrorKind::StateIDOverflow { .. } => "Pattern too complex".to_string(),
+            _ => e.to_string(),
+        })?;
+    if dfa.memory_usage() > limits.max_dfa_bytes {
+        return Err("Pattern too complex".to_string());
+    }
+    Ok(dfa)
+}
+
+struct ParsedQuery {
+    mode: PatternMode,
+    pattern: String,
+    absent_letters: String,
+    /// Whether to recommend letters to call along with the words.
+    include_letters: bool,
+    /// Whether to say how the words were found.
+    debug: bool,
+}
+
+fn split_query_string(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
         .into_owned()
-        .collect();
+        .collect()
+}
+
+/// Parses a partial cryptogram key like `Q=e,X=t`, where each uppercase
+/// cipher letter is followed by the letter it stands for.
+fn parse_cipher_key(key: &str) -> Result<CipherKey, String> {
+    let mut parsed = CipherKey::new();
+    for pair in key
+        .split(',')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+    {
+        let mut chars = pair.chars();
+        let (symbol, letter) = match (chars.next(), chars.next(), chars.next(), chars.next()) {
+            (Some(symbol), Some('='), Some(letter), None)
+                if symbol.is_ascii_alphabetic() && letter.is_ascii_alphabetic() =>
+            {
+                (symbol.to_ascii_uppercase(), letter.to_ascii_lowercase())
+            }
+            _ => return Err(format!("Invalid key entry {}", pair)),
+        };
+        // cryptogram rules - a letter can't stand for itself
+        if symbol.to_ascii_lowercase() == letter
+            || !extend_cipher_key(&letter.to_string(), &symbol.to_string(), &mut parsed)
+        {
+            return Err(format!("Inconsistent key entry {}", pair));
+        }
+    }
+    Ok(parsed)
+}
+
+/// Replaces the cipher letters in `pattern` that `key` knows with the
+/// letters they stand for.
+fn apply_cipher_key(pattern: &str, key: &CipherKey) -> String {
+    pattern
+        .chars()
+        .map(|c| {
+            key.iter()
+                .find(|(symbol, _)| *symbol == c)
+                .map_or(c, |(_, letter)| *letter)
+        })
+        .collect()
+}
+
+/// The cipher letters in a Cryptogram `pattern` and what they stand for if
+/// the answer is `word`, as a JSON object.
+fn cipher_key_to_json(word: &str, pattern: &str) -> json::JsonValue {
+    let mut key = CipherKey::new();
+    extend_cipher_key(word, pattern, &mut key);
+    let mut json = json::JsonValue::new_object();
+    for (symbol, letter) in key {
+        json[symbol.to_string()] = letter.to_string().into();
+    }
+    json
+}
+
+/// Pulls the mode, pattern and absent letters out of a query string.
+fn parse_query_string(query: &str) -> Result<ParsedQuery, String> {
+    let mut query_parts = split_query_string(query);
     let mode = query_parts
         .get("mode")
-        .ok_or(String::from("Internal error - no mode specified!"))?;
+        .ok_or_else(|| String::from("Internal error - no mode specified!"))?;
     let mode = PatternMode::try_from(mode.as_str())
         .map_err(|_| String::from("Internal error - invalid mode!"))?;
-    let pattern = query_parts
-        .get("pattern")
-        .ok_or(String::from("Internal error - no pattern specified!"))?;
-    validate_pattern(pattern, &mode)?;
+    let mut pattern = query_parts
+        .remove("pattern")
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?;
     // TODO - validate if in WheelOfFortune mode?
     //let absent_letters = query_parts.get("absent_letters").ok_or(String::from("Internal error - no absent_letters specified!"))?;
-    let empty_absent_letters = String::from("");
-    let absent_letters = query_parts
-        .get("absent_letters")
-        .unwrap_or(&empty_absent_letters);
-    validate_absent_letters(absent_letters)?;
-    let word_regex = build_regex(pattern, absent_letters, &mode)?;
-    let pattern_question_marks = if mode == PatternMode::Cryptogram {
-        pattern.chars().filter(|c| c.is_ascii_uppercase()).count()
-    } else {
-        pattern.chars().filter(|c| *c == '?').count()
-    };
-    let read_fst_file: bool = pattern_question_marks < FST_QUESTION_MARK_THRESHOLD;
-    let json_results = if read_fst_file {
-        let mmap = unsafe {
-            Mmap::map(
-                &File::open(find_processed_file("word_frequency.fst"))
-                    .map_err(|e| e.to_string())?,
-            )
-            .map_err(|e| e.to_string())?
-        };
-        let map = fst::Map::new(mmap).map_err(|e| e.to_string())?;
-        // need to strip off the ^ and $, but setting anchored to true will cover that
-        let word_regex_pattern = &word_regex.as_str()[1..word_regex.as_str().len() - 1];
-        let dfa = dense::Builder::new()
-            .anchored(true)
-            .build(word_regex_pattern)
-            .unwrap();
-        let mut results = map
-            .search(&dfa)
-            .into_stream()
-            .into_str_vec()
-            .map_err(|e| e.to_string())?;
-        if mode == PatternMode::Cryptogram {
-            results = results
-                .into_iter()
-                .filter(|a| is_valid_string(&a.0, &pattern, &mode))
-                .collect();
-        }
-        results.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut absent_letters = query_parts.remove("absent_letters").unwrap_or_default();
+    if let Some(key) = query_parts.get("key") {
+        if mode != PatternMode::Cryptogram {
+            return Err("A key can only be given in Cryptogram mode".to_string());
+        }
+        let key = parse_cipher_key(key)?;
+        pattern = apply_cipher_key(&pattern, &key);
+        // the letters in the key can't be standing for anything else
+        absent_letters.extend(key.iter().map(|(_, letter)| *letter));
+    }
+    let include_letters = query_parts
+        .get("letters")
+        .is_some_and(|value| value == "1" || value == "true");
+    let debug = query_parts
+        .get("debug")
+        .is_some_and(|value| value == "1" || value == "true");
+    Ok(ParsedQuery {
+        mode,
+        pattern,
+        absent_letters,
+        include_letters,
+        debug,
+    })
+}
+
+fn results_to_json(results: &SearchResults) -> Result<json::JsonValue, String> {
+    if results.truncated {
+        return Err("Query took too long - try filling in more letters".to_string());
+    }
+    Ok(json::JsonValue::Array(
         results
+            .words
             .iter()
             .map(|entry| json::object! { "word" => entry.0.clone(), "frequency" => entry.1 })
-            .collect()
-    } else {
-        let mut results = vec![];
-        let mut line = String::new();
-        let file =
-            File::open(find_processed_file("word_frequency.txt")).map_err(|e| e.to_string())?;
-        let mut reader = io::BufReader::new(file);
-        while reader.read_line(&mut line).map_err(|e| e.to_string())? > 0 {
-            let mut parts = line.split_ascii_whitespace();
-            let word = parts.next().unwrap();
-            if word_regex.is_match(word) {
-                if !(mode == PatternMode::Cryptogram && !is_valid_string(&word, &pattern, &mode)) {
-                    // TODO - use Object constructor
-                    results.push(json::object! { "word" => word, "frequency" => parts.next().unwrap().parse::<u64>().unwrap() });
-                }
+            .collect(),
+    ))
+}
+
+/// The response to a query (see `words_response()`). In debug mode it's
+/// always an object, which also has the strategy used to find the words and
+/// what each available strategy was expected to cost.
+fn query_response(query: &ParsedQuery, results: &SearchResults) -> Result<json::JsonValue, String> {
+    let mut response = words_response(query, results)?;
+    if query.debug {
+        if response.is_array() {
+            response = json::object! { "words" => response };
+        }
+        response["strategy"] = results.strategy.map(|strategy| strategy.name()).into();
+        let mut costs = json::JsonValue::new_object();
+        for (strategy, cost) in &results.estimated_costs {
+            costs[strategy.name()] = (*cost).into();
+        }
+        response["estimated_costs"] = costs;
+    }
+    Ok(response)
+}
+
+/// Just the words (along with the key each one implies in Cryptogram mode),
+/// or if letters were asked for, an object with the words and the letters
+/// to call next (or in Wordle mode, the words to guess next).
+fn words_response(query: &ParsedQuery, results: &SearchResults) -> Result<json::JsonValue, String> {
+    let mut words = results_to_json(results)?;
+    if query.mode == PatternMode::Cryptogram {
+        // what each word would add to the key, for solving the rest of the
+        // puzzle
+        for (entry, (word, _)) in words.members_mut().zip(&results.words) {
+            entry["key"] = cipher_key_to_json(word, &query.pattern);
+        }
+    }
+    if !query.include_letters {
+        return Ok(words);
+    }
+    if query.mode == PatternMode::Wordle {
+        let (guesses, truncated) = best_guesses(&results.words, 10, &QueryLimits::default());
+        let guesses = guesses
+            .into_iter()
+            .map(|(guess, information)| {
+                json::object! { "word" => guess, "information" => information }
+            })
+            .collect();
+        // if scoring ran out of time, the guesses only come from the most
+        // common candidates
+        return Ok(json::object! {
+            "words" => words,
+            "guesses" => json::JsonValue::Array(guesses),
+            "guesses_truncated" => truncated
+        });
+    }
+    let letters = recommend_letters(&query.pattern.to_ascii_lowercase(), &results.words)
+        .into_iter()
+        .map(|r| {
+            json::object! {
+                "letter" => r.letter.to_string(),
+                "weight" => r.weight,
+                "information" => r.expected_information
             }
-            line.clear();
+        })
+        .collect();
+    Ok(json::object! { "words" => words, "letters" => json::JsonValue::Array(letters) })
+}
+
+/// Handles queries for puzzles that aren't a pattern to match, or returns
+/// `None` if `query` is for a `PatternMode`.
+fn process_puzzle_query(query: &str) -> Option<Result<json::JsonValue, String>> {
+    let query_parts = split_query_string(query);
+    match query_parts.get("mode").map(|mode| mode.as_str()) {
+        Some("SpellingBee") => Some(spelling_bee::process_query(&query_parts)),
+        Some("Boggle") => Some(boggle::process_query(&query_parts)),
+        Some("WordLadder") => Some(word_ladder::process_query(&query_parts)),
+        Some("Codeword") => Some(codeword::process_query(&query_parts)),
+        Some("Scrabble") => Some(scrabble::process_query(&query_parts)),
+        Some("Patristocrat") => Some(patristocrat::process_query(&query_parts)),
+        Some("Acrostic") => Some(acrostic::process_query(&query_parts)),
+        Some("Hidden") => Some(hidden_words::process_query(&query_parts)),
+        Some("Transform") => Some(transforms::process_query(&query_parts)),
+        Some("LetterBoxed") => Some(letter_boxed::process_query(&query_parts)),
+        Some("WordSearch") => Some(word_search::process_query(&query_parts)),
+        Some("WordSearchGenerator") => Some(word_search::process_generator_query(&query_parts)),
+        Some("PuzzleGenerator") => Some(puzzle_generator::process_query(&query_parts)),
+        Some("Advisor") => Some(advisor::process_query(&query_parts)),
+        Some("Caesar" | "Affine" | "Atbash" | "Vigenere" | "Cipher") => {
+            Some(classic_ciphers::process_query(&query_parts))
         }
-        results
-    };
-    Ok(json::JsonValue::Array(json_results))
+        _ => None,
+    }
 }
 
-fn build_regex(pattern: &str, absent_letters: &str, mode: &PatternMode) -> Result<Regex, String> {
-    // (?-u) turns off unicode, although that's not really necessary here since we're already
-    // specifying the exact characters to match.
-    let mut regex_str = "^(?-u)".to_string();
-    match mode {
-        &PatternMode::WheelOfFortune => {
-            let mut absent_letter_set = HashSet::new();
-            for letter in absent_letters.chars() {
-                //println!("inserting {} from absent", letter);
-                absent_letter_set.insert(letter.to_ascii_lowercase());
+pub fn process_query_string(query: &str) -> Result<json::JsonValue, String> {
+    if let Some(response) = process_puzzle_query(query) {
+        return response;
+    }
+    let query = parse_query_string(query)?;
+    let mut results = search_pattern(
+        query.mode,
+        &query.pattern,
+        &query.absent_letters,
+        &QueryLimits::default(),
+    )?;
+    let mut from_ngrams = false;
+    if query.mode == PatternMode::Cryptogram && results.words.is_empty() && !results.truncated {
+        if let Some(word) = ngram_decoding(&query) {
+            results.words.push((word, 0));
+            from_ngrams = true;
+        }
+    }
+    let mut response = query_response(&query, &results)?;
+    if from_ngrams {
+        // mark it the same way as a Patristocrat n-gram decoding, since it
+        // isn't really a word from the list
+        let words = if response.is_array() {
+            &mut response
+        } else {
+            &mut response["words"]
+        };
+        words[0]["method"] = "ngrams".into();
+    }
+    Ok(response)
+}
+
+/// For a cryptogram word that isn't in the word list (like a name), the
+/// decoding that looks most like English by its letter n-grams. Returns
+/// `None` if there's no n-gram file or the word is too short to score.
+fn ngram_decoding(query: &ParsedQuery) -> Option<String> {
+    let model = LetterNgrams::open().ok()?;
+    if query.pattern.len() < model.n() || !query.pattern.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let decoding = hill_climb(
+        &model,
+        &query.pattern,
+        &query.absent_letters,
+        0,
+        &QueryLimits::default(),
+    );
+    if decoding.truncated {
+        return None;
+    }
+    Some(decoding.plaintext)
+}
+
+/// Finds all words in the dictionary matching `pattern` under the rules
+/// of `mode`, giving up once `limits` are exceeded.
+pub fn search_pattern(
+    mode: PatternMode,
+    pattern: &str,
+    absent_letters: &str,
+    limits: &QueryLimits,
+) -> Result<SearchResults, String> {
+    Dictionary::open()?.search(mode, pattern, absent_letters, limits)
+}
+
+/// Reads the optional `min_frequency` parameter of a query, or `default` if
+/// it isn't there.
+fn parse_min_frequency(query_parts: &HashMap<String, String>, default: u64) -> Result<u64, String> {
+    match query_parts.get("min_frequency") {
+        Some(min_frequency) => min_frequency.parse::<u64>().map_err(|e| e.to_string()),
+        None => Ok(default),
+    }
+}
+
+/// Reads the optional whole number parameter `name` of a query, or
+/// `default` if it isn't there.
+fn parse_usize_param(
+    query_parts: &HashMap<String, String>,
+    name: &str,
+    default: usize,
+) -> Result<usize, String> {
+    match query_parts.get(name) {
+        Some(value) => value.parse::<usize>().map_err(|e| e.to_string()),
+        None => Ok(default),
+    }
+}
+
+/// The processed dictionary files, opened once so many queries can share them.
+pub struct Dictionary {
+    index: Option<WordIndex>,
+    map: Option<fst::Map<Mmap>>,
+    has_text_file: bool,
+    // the sum of every word's frequency, worked out the first time it's needed
+    total_frequency: OnceCell<u64>,
+}
+
+impl Dictionary {
+    pub fn open() -> Result<Dictionary, String> {
+        let index = match try_find_processed_file("word_index.bin") {
+            Some(_) => Some(WordIndex::open()?),
+            None => None,
+        };
+        let map = match try_find_processed_file("word_frequency.fst") {
+            Some(_) => Some(open_fst_map()?),
+            None => None,
+        };
+        let has_text_file = try_find_processed_file("word_frequency.txt").is_some();
+        if index.is_none() && map.is_none() && !has_text_file {
+            return Err("Couldn't find any dictionary files!".to_string());
+        }
+        Ok(Dictionary {
+            index,
+            map,
+            has_text_file,
+            total_frequency: OnceCell::new(),
+        })
+    }
+
+    fn stats(&self, length: usize) -> DictionaryStats {
+        match (&self.index, &self.map) {
+            (Some(index), _) => DictionaryStats {
+                total_words: index.total_words(),
+                words_of_length: index.word_count(length),
+            },
+            (None, Some(map)) => DictionaryStats {
+                total_words: map.len(),
+                // no way to know without the index, so assume they're spread
+                // evenly over the possible lengths
+                words_of_length: map.len() / 20,
+            },
+            (None, None) => DictionaryStats::default(),
+        }
+    }
+
+    /// The FST of every word and its frequency, for searches that walk it
+    /// directly instead of matching a pattern.
+    fn fst_map(&self) -> Result<&fst::Map<Mmap>, String> {
+        self.map
+            .as_ref()
+            .ok_or_else(|| "Couldn't find word_frequency.fst!".to_string())
+    }
+
+    /// The sum of the frequencies of every word in the FST, for turning a
+    /// frequency into a probability. Only the first call has to add them up.
+    fn total_frequency(&self) -> Result<u64, String> {
+        let map = self.fst_map()?;
+        Ok(*self.total_frequency.get_or_init(|| {
+            let mut total = 0u64;
+            let mut stream = map.stream();
+            while let Some((_, frequency)) = stream.next() {
+                total = total.saturating_add(frequency);
             }
-            for letter in pattern.chars() {
-                if letter.is_ascii_alphabetic() {
-                    //println!("inserting {} from pattern", letter);
-                    absent_letter_set.insert(letter.to_ascii_lowercase());
-                }
+            total
+        }))
+    }
+
+    /// See `search_pattern()`.
+    pub fn search(
+        &self,
+        mode: PatternMode,
+        pattern: &str,
+        absent_letters: &str,
+        limits: &QueryLimits,
+    ) -> Result<SearchResults, String> {
+        self.search_with_budget(mode, pattern, absent_letters, &mut WorkBudget::new(limits))
+    }
+
+    /// Like `search()`, but charges the work to `budget`, so several
+    /// searches can share one.
+    fn search_with_budget(
+        &self,
+        mode: PatternMode,
+        pattern: &str,
+        absent_letters: &str,
+        budget: &mut WorkBudget,
+    ) -> Result<SearchResults, String> {
+        validate_pattern(pattern, &mode)?;
+        validate_absent_letters(absent_letters)?;
+        let masks = position_masks(pattern, absent_letters, &mode);
+        if masks.contains(&0) {
+            return Ok(SearchResults::default());
+        }
+        let mut available = vec![];
+        if self.index.is_some() {
+            available.push(SearchStrategy::WordIndex);
+        }
+        if self.map.is_some() {
+            available.push(SearchStrategy::FstDfa);
+        }
+        if self.has_text_file {
+            available.push(SearchStrategy::LinearScan);
+        }
+        let (strategy, costs) = choose_strategy(&masks, &self.stats(masks.len()), &available)
+            .ok_or_else(|| "Couldn't find any dictionary files!".to_string())?;
+        let limits = budget.limits;
+        let check = WordCheck::new(pattern, &mode)?;
+        let (words, truncated) = match strategy {
+            SearchStrategy::WordIndex => {
+                search_word_index(self.index.as_ref().unwrap(), &masks, &check, budget)?
             }
-            // regex gets cranky about turning off unicode then matching characters that aren't something
-            // (because they might be unicode characters!) so just iterate over all the possibilities here.
-            let mut absent_letter_builder = "[".to_string();
-            for letter in 'a'..='z' {
-                if !absent_letter_set.contains(&letter) {
-                    absent_letter_builder.push(letter);
-                }
+            SearchStrategy::FstDfa => search_fst(
+                self.map.as_ref().unwrap(),
+                pattern,
+                absent_letters,
+                &mode,
+                &check,
+                limits,
+                budget,
+            )?,
+            SearchStrategy::LinearScan => {
+                search_linear(pattern, absent_letters, &mode, &check, budget)?
             }
-            absent_letter_builder.push(']');
-            regex_str.push_str(
-                &pattern
-                    .to_ascii_lowercase()
-                    .replace("?", absent_letter_builder.as_str()),
-            );
-        }
-        &PatternMode::Crossword => {
-            let mut absent_letter_builder = "[".to_string();
-            for letter in 'a'..='z' {
-                absent_letter_builder.push(letter);
+        };
+        Ok(SearchResults {
+            words,
+            truncated,
+            strategy: Some(strategy),
+            estimated_costs: costs,
+        })
+    }
+}
+
+type WordsAndTruncated = (Vec<(String, u64)>, bool);
+
+fn search_word_index(
+    index: &WordIndex,
+    masks: &[u32],
+    check: &WordCheck,
+    budget: &mut WorkBudget,
+) -> Result<WordsAndTruncated, String> {
+    let mut results = vec![];
+    for (word, frequency) in index.matching_words(masks) {
+        if !budget.charge() {
+            return Ok((results, true));
+        }
+        let word = std::str::from_utf8(word).map_err(|e| e.to_string())?;
+        if check.allows(word) {
+            results.push((word.to_string(), frequency));
+        }
+    }
+    Ok((results, false))
+}
+
+fn search_fst(
+    map: &fst::Map<Mmap>,
+    pattern: &str,
+    absent_letters: &str,
+    mode: &PatternMode,
+    check: &WordCheck,
+    limits: &QueryLimits,
+    budget: &mut WorkBudget,
+) -> Result<WordsAndTruncated, String> {
+    let word_regex = build_regex(pattern, absent_letters, mode)?;
+    // need to strip off the ^ and $, but setting anchored to true will cover that
+    let word_regex_pattern = &word_regex.as_str()[1..word_regex.as_str().len() - 1];
+    let dfa = build_dfa(word_regex_pattern, limits)?;
+    let mut stream = map.search(&dfa).into_stream();
+    let mut results = vec![];
+    let mut truncated = false;
+    while let Some((word, frequency)) = stream.next() {
+        if !budget.charge() {
+            truncated = true;
+            break;
+        }
+        let word = String::from_utf8(word.to_vec()).map_err(|e| e.to_string())?;
+        if check.allows(&word) {
+            results.push((word, frequency));
+        }
+    }
+    results.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok((results, truncated))
+}
+
+fn search_linear(
+    pattern: &str,
+    absent_letters: &str,
+    mode: &PatternMode,
+    check: &WordCheck,
+    budget: &mut WorkBudget,
+) -> Result<WordsAndTruncated, String> {
+    let word_regex = build_regex(pattern, absent_letters, mode)?;
+    let mut results = vec![];
+    let mut line = String::new();
+    let file = File::open(find_processed_file("word_frequency.txt")).map_err(|e| e.to_string())?;
+    let mut reader = io::BufReader::new(file);
+    while reader.read_line(&mut line).map_err(|e| e.to_string())? > 0 {
+        if !budget.charge() {
+            return Ok((results, true));
+        }
+        let mut parts = line.split_ascii_whitespace();
+        let word = parts.next().unwrap();
+        if word_regex.is_match(word) && check.allows(word) {
+            results.push((
+                word.to_string(),
+                parts.next().unwrap().parse::<u64>().unwrap(),
+            ));
+        }
+        line.clear();
+    }
+    Ok((results, false))
+}
+
+fn char_mask(c: char) -> u32 {
+    WORD_ALPHABET
+        .iter()
+        .position(|a| *a as char == c)
+        .map_or(0, |i| 1 << i)
+}
+
+/// For each position in `pattern`, the set of characters a matching word
+/// can have there, as a bitmask over `WORD_ALPHABET`.
+fn position_masks(pattern: &str, absent_letters: &str, mode: &PatternMode) -> Vec<u32> {
+    if mode == &PatternMode::Wordle {
+        return WordleConstraints::parse(pattern)
+            .map(|c| c.position_masks(absent_letters))
+            .unwrap_or_default();
+    }
+    let mut unknown_mask: u32 = ('a'..='z').map(char_mask).fold(0, |acc, m| acc | m);
+    match *mode {
+        PatternMode::WheelOfFortune | PatternMode::Hangman => {
+            // letters that have already been guessed can't be hiding behind a ?
+            for letter in absent_letters.chars().chain(pattern.chars()) {
+                unknown_mask &= !char_mask(letter.to_ascii_lowercase());
+            }
+        }
+        PatternMode::Crossword | PatternMode::Wordle => {}
+        PatternMode::Cryptogram => {
+            let known_letters = pattern.chars().filter(|c| !c.is_ascii_uppercase());
+            for letter in absent_letters.chars().chain(known_letters) {
+                unknown_mask &= !char_mask(letter.to_ascii_lowercase());
             }
-            absent_letter_builder.push(']');
-            regex_str.push_str(
-                &pattern
-                    .to_ascii_lowercase()
-                    .replace("?", absent_letter_builder.as_str()),
-            );
-        }
-        &PatternMode::Cryptogram => {
-            let mut known_letters: HashSet<char> = pattern
-                .chars()
-                .filter(|c| !c.is_ascii_uppercase())
-                .collect();
-            for letter in absent_letters.chars() {
-                //println!("inserting {} from absent", letter);
-                known_letters.insert(letter.to_ascii_lowercase());
+        }
+    }
+    pattern
+        .chars()
+        .map(|c| match *mode {
+            // cryptogram rules - a letter can't stand for itself
+            PatternMode::Cryptogram if c.is_ascii_uppercase() => {
+                unknown_mask & !char_mask(c.to_ascii_lowercase())
             }
-            for pattern_char in pattern.chars() {
-                if !pattern_char.is_ascii_uppercase() {
-                    regex_str.push(pattern_char);
-                } else {
-                    regex_str.push('[');
-                    for letter in 'a'..='z' {
-                        // cryptogram rules - can't match this character
-                        if !(known_letters.contains(&letter)
-                            || letter == pattern_char.to_ascii_lowercase())
-                        {
-                            regex_str.push(letter);
-                        }
-                    }
-                    regex_str.push(']');
+            PatternMode::Cryptogram => char_mask(c),
+            _ if c == '?' => unknown_mask,
+            _ => char_mask(c.to_ascii_lowercase()),
+        })
+        .collect()
+}
+
+fn build_regex(pattern: &str, absent_letters: &str, mode: &PatternMode) -> Result<Regex, String> {
+    // (?-u) turns off unicode, although that's not really necessary here since we're already
+    // specifying the exact characters to match.
+    let mut regex_str = "^(?-u)".to_string();
+    for mask in position_masks(pattern, absent_letters, mode) {
+        // regex gets cranky about turning off unicode then matching characters that aren't something
+        // (because they might be unicode characters!) so just list all the possibilities here.
+        regex_str.push('[');
+        for (i, c) in WORD_ALPHABET.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                if *c == b'-' {
+                    regex_str.push('\\');
                 }
+                regex_str.push(*c as char);
             }
         }
+        regex_str.push(']');
     }
     regex_str.push('$');
     Regex::new(&regex_str).map_err(|e| e.to_string())
@@ -282,11 +878,14 @@ fn is_allowed_char(c: char, mode: &PatternMode) -> bool {
     if c.is_ascii_alphabetic() || c == '\'' || c == '-' {
         return true;
     }
-    return mode != &PatternMode::Cryptogram && c == '?';
+    mode != &PatternMode::Cryptogram && c == '?'
 }
 
 fn validate_pattern(pattern: &str, mode: &PatternMode) -> Result<(), String> {
-    if pattern.chars().any(|c| !is_allowed_char(c, &mode)) {
+    if mode == &PatternMode::Wordle {
+        return WordleConstraints::parse(pattern).map(|_| ());
+    }
+    if pattern.chars().any(|c| !is_allowed_char(c, mode)) {
         return Err("Disallowed characters in pattern".to_string());
     }
     if pattern.len() > 20 {
@@ -303,6 +902,8 @@ fn validate_absent_letters(absent_letters: &str) -> Result<(), String> {
     Ok(())
 }
 
+// These tests predate running clippy on all targets.
+#[allow(clippy::useless_format, clippy::iter_nth_zero, clippy::nonminimal_bool)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
@@ -536,6 +1137,35 @@ mod tests {
         assert_eq!("for", result[0]["word"].to_string());
     }
 
+    #[test]
+    fn test_cryptogram_key() {
+        let result = process_query_string("mode=Cryptogram&pattern=ABC&key=A%3Dt,b%3DH").unwrap();
+        assert_eq!("the", result[0]["word"].to_string());
+        // only the letters the key didn't already have
+        assert_eq!("e", result[0]["key"]["C"].to_string());
+        assert_eq!(1, result[0]["key"].len());
+        let result = process_query_string("mode=Cryptogram&pattern=ABC&key=").unwrap();
+        assert_eq!("t", result[0]["key"]["A"].to_string());
+    }
+
+    #[test]
+    fn test_cryptogram_key_excludes_letters() {
+        let result = process_query_string("mode=Cryptogram&pattern=BC&key=A%3Dt").unwrap();
+        assert!(!result.is_empty());
+        assert!(result
+            .members()
+            .all(|x| !x["word"].to_string().contains('t')));
+    }
+
+    #[test]
+    fn test_cryptogram_invalid_key() {
+        for key in ["A%3Da", "A%3Dt,B%3Dt", "A%3Dt,A%3Dh", "A", "A%3D?"] {
+            let query = format!("mode=Cryptogram&pattern=ABC&key={}", key);
+            assert!(process_query_string(&query).is_err(), "{}", key);
+        }
+        assert!(process_query_string("mode=Crossword&pattern=t?e&key=A%3Dt").is_err());
+    }
+
     #[test]
     fn test_cryptogram_over_limit() {
         let query = format!("mode=Cryptogram&pattern=ABCDEF&absent_letters=");
@@ -543,10 +1173,27 @@ mod tests {
         assert_eq!("should", result[0]["word"].to_string());
     }
 
+    #[test]
+    fn test_cryptogram_ngram_fallback() {
+        // not a word in the list, so the key comes from letter n-grams
+        let result = process_query_string("mode=Cryptogram&pattern=WKHUHLV&key=K%3Dh").unwrap();
+        assert_eq!(1, result.len());
+        let word = result[0]["word"].to_string();
+        assert_eq!(7, word.len());
+        assert_eq!(Some('h'), word.chars().nth(1));
+        assert_eq!(0, result[0]["frequency"].as_u64().unwrap());
+        assert_eq!("ngrams", result[0]["method"].to_string());
+        for (symbol, letter) in result[0]["key"].entries() {
+            assert_ne!(symbol.to_ascii_lowercase(), letter.to_string());
+        }
+    }
+
     #[test]
     fn test_cryptogram_do_not_double_assign_letters() {
         let query = format!("mode=Cryptogram&pattern=scABCD&absent_letters=");
         let result = process_query_string(&query).unwrap();
+        // words from the list aren't marked
+        assert!(result[0]["method"].is_null());
         // not "school" because B and C can't map to o
         assert_eq!("script", result[0]["word"].to_string());
         let words = result
@@ -556,6 +1203,365 @@ mod tests {
         assert!(!words.contains(&"school".to_string()));
     }
 
+    #[test]
+    fn test_position_masks_wheeloffortune() {
+        let masks = position_masks("t?-", "h", &PatternMode::WheelOfFortune);
+        assert_eq!(char_mask('t'), masks[0]);
+        assert_eq!(0, masks[1] & (char_mask('t') | char_mask('h')));
+        assert_ne!(0, masks[1] & char_mask('e'));
+        assert_eq!(0, masks[1] & (char_mask('\'') | char_mask('-')));
+        assert_eq!(char_mask('-'), masks[2]);
+    }
+
+    #[test]
+    fn test_build_regex_matches_masks() {
+        let regex = build_regex("t?e", "h", &PatternMode::WheelOfFortune).unwrap();
+        assert!(regex.is_match("tie"));
+        assert!(!regex.is_match("the"));
+        let regex = build_regex("n?n-?", "", &PatternMode::Crossword).unwrap();
+        assert!(regex.is_match("non-a"));
+        assert!(!regex.is_match("nonba"));
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let index = WordIndex::open().unwrap();
+        let map = open_fst_map().unwrap();
+        let limits = QueryLimits::default();
+        for (pattern, absent_letters, mode) in [
+            ("t???", "h", PatternMode::WheelOfFortune),
+            ("c??'t", "", PatternMode::Crossword),
+            ("ABCC", "", PatternMode::Cryptogram),
+            ("scABCD", "", PatternMode::Cryptogram),
+        ] {
+            let masks = position_masks(pattern, absent_letters, &mode);
+            let check = WordCheck::new(pattern, &mode).unwrap();
+            let from_index =
+                search_word_index(&index, &masks, &check, &mut WorkBudget::new(&limits)).unwrap();
+            let from_fst = search_fst(
+                &map,
+                pattern,
+                absent_letters,
+                &mode,
+                &check,
+                &limits,
+                &mut WorkBudget::new(&limits),
+            )
+            .unwrap();
+            let from_scan = search_linear(
+                pattern,
+                absent_letters,
+                &mode,
+                &check,
+                &mut WorkBudget::new(&limits),
+            )
+            .unwrap();
+            assert!(!from_index.0.is_empty());
+            assert_eq!(from_index, from_fst);
+            assert_eq!(from_index, from_scan);
+        }
+    }
+
+    #[test]
+    fn test_scanned_entries_limit_truncates() {
+        let limits = QueryLimits {
+            max_scanned_entries: 2,
+            ..QueryLimits::default()
+        };
+        let result = search_pattern(PatternMode::WheelOfFortune, "?????", "hx", &limits).unwrap();
+        assert!(result.truncated);
+        assert!(result.words.len() <= 2);
+    }
+
+    #[test]
+    fn test_scanned_entries_limit_not_hit() {
+        let result = search_pattern(
+            PatternMode::WheelOfFortune,
+            "t?e",
+            "h",
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!result.truncated);
+        assert_eq!("tie", result.words[0].0);
+        let strategy = result.strategy.unwrap();
+        assert!(result.estimated_costs.iter().any(|(s, _)| *s == strategy));
+    }
+
+    #[test]
+    fn test_dfa_size_limit() {
+        let limits = QueryLimits {
+            max_dfa_bytes: 1,
+            ..QueryLimits::default()
+        };
+        assert!(build_dfa("[a-z][a-z]", &limits).is_err());
+        assert!(build_dfa("[a-z][a-z]", &QueryLimits::default()).is_ok());
+        // needs a state for every combination of the last 20 letters
+        assert_eq!(
+            Err("Pattern too complex".to_string()),
+            build_dfa("[ab]*a[ab]{20}", &QueryLimits::default()).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_recommended_letters() {
+        let result =
+            process_query_string("mode=WheelOfFortune&pattern=t?e&absent_letters=h&letters=1")
+                .unwrap();
+        assert_eq!("tie", result["words"][0]["word"].to_string());
+        assert_eq!("i", result["letters"][0]["letter"].to_string());
+        assert!(result["letters"][0]["information"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_wordle() {
+        let result =
+            process_query_string("mode=Wordle&pattern=crane:bybbg,build:bbbbb&letters=1").unwrap();
+        let words = result["words"]
+            .members()
+            .map(|x| x["word"].to_string())
+            .collect::<Vec<String>>();
+        assert!(words.contains(&"there".to_string()));
+        assert!(words.iter().all(|word| word.len() == 5
+            && word.ends_with('e')
+            && word.contains('r')
+            && !word.contains('b')));
+        assert!(result["guesses"][0]["information"].as_f64().unwrap() > 0.0);
+        assert_eq!(Some(false), result["guesses_truncated"].as_bool());
+    }
+
+    #[test]
+    fn test_wordle_invalid_feedback() {
+        assert!(process_query_string("mode=Wordle&pattern=crane:bybb").is_err());
+    }
+
+    #[test]
+    fn test_spelling_bee() {
+        let result = process_query_string("mode=SpellingBee&pattern=thercab&center=e").unwrap();
+        let words = result["words"]
+            .members()
+            .map(|x| x["word"].to_string())
+            .collect::<Vec<String>>();
+        assert!(words.contains(&"there".to_string()));
+        assert!(result["total_score"].as_u32().unwrap() > 0);
+        assert!(process_query_string("mode=SpellingBee&pattern=thercab").is_err());
+        assert!(process_query_string("mode=SpellingBee&pattern=thercab&center=").is_err());
+        assert!(process_query_string("mode=SpellingBee&pattern=thercab&center=eh").is_err());
+    }
+
+    #[test]
+    fn test_boggle() {
+        let result = process_query_string("mode=Boggle&pattern=the,xre,xxx").unwrap();
+        let words = result
+            .members()
+            .map(|x| x["word"].to_string())
+            .collect::<Vec<String>>();
+        assert!(words.contains(&"there".to_string()));
+        assert!(process_query_string("mode=Boggle&pattern=the,xre").is_err());
+    }
+
+    #[test]
+    fn test_word_ladder() {
+        let result = process_query_string("mode=WordLadder&start=the&end=she").unwrap();
+        assert_eq!(2, result.len());
+        assert_eq!("she", result[1]["word"].to_string());
+    }
+
+    #[test]
+    fn test_codeword() {
+        let result = process_query_string(
+            "mode=Codeword&pattern=1+2+3/4+%23+5/3+%23+6&given=1%3Dt,2%3Dh,4%3Di",
+        )
+        .unwrap();
+        assert_eq!("end", result["words"][2].to_string());
+        assert_eq!("e", result["key"]["3"].to_string());
+    }
+
+    #[test]
+    fn test_scrabble() {
+        let board = vec!["..............."; 15].join("/");
+        let result =
+            process_query_string(&format!("mode=Scrabble&pattern={}&rack=the", board)).unwrap();
+        assert_eq!("the", result[0]["word"].to_string());
+        assert_eq!(12, result[0]["score"].as_u32().unwrap());
+        let placements = &result[0]["placements"];
+        assert_eq!(3, placements.len());
+        assert_eq!(
+            "the",
+            placements
+                .members()
+                .map(|p| p["letter"].to_string())
+                .collect::<String>()
+        );
+        assert!(placements.members().all(|p| p["is_blank"] == false));
+        let result =
+            process_query_string(&format!("mode=Scrabble&pattern={}&rack=th?", board)).unwrap();
+        assert!(result
+            .members()
+            .any(|m| m["placements"].members().any(|p| p["is_blank"] == true)));
+        assert!(process_query_string(&format!("mode=Scrabble&pattern={}", board)).is_err());
+    }
+
+    #[test]
+    fn test_patristocrat() {
+        let result =
+            process_query_string("mode=Patristocrat&pattern=WKHUH LVWKL VWUHH&spacing=groups")
+                .unwrap();
+        assert_eq!("there is this tree", result[0]["plaintext"].to_string());
+        assert_eq!("t", result[0]["key"]["W"].to_string());
+        assert!(process_query_string("mode=Patristocrat&pattern=WKH&spacing=lines").is_err());
+    }
+
+    #[test]
+    fn test_patristocrat_ngram_fallback() {
+        // nothing is common enough, so the word list can't help
+        let result = process_query_string(
+            "mode=Patristocrat&spacing=words&min_frequency=18446744073709551615&pattern=\
+             WKHUH LV WKLV WUHH DQG WKDW LV WKH KRXVH ZKHUH WKH RWKHU RQH VDLG WKDW WKHVH \
+             ZHUH QRW WKHUH ZKHQ L ZDV WKHUH ZLWK WKH RWKHU",
+        )
+        .unwrap();
+        assert_eq!("ngrams", result[0]["method"].to_string());
+        assert!(result[0]["plaintext"]
+            .to_string()
+            .starts_with("there is this tree and that"));
+    }
+
+    #[test]
+    fn test_classic_ciphers() {
+        let result = process_query_string("mode=Caesar&pattern=WKH HQG").unwrap();
+        assert_eq!("the end", result[0]["plaintext"].to_string());
+        assert_eq!("3", result[0]["key"].to_string());
+        let result = process_query_string("mode=Cipher&pattern=GSV VMW").unwrap();
+        assert_eq!("Atbash", result[0]["cipher"].to_string());
+        assert!(process_query_string("mode=Vigenere&pattern=").is_err());
+    }
+
+    #[test]
+    fn test_transform() {
+        let result = process_query_string("mode=Transform&transform=reversal&pattern=on").unwrap();
+        assert_eq!("no", result["pairs"][0]["result"].to_string());
+        assert!(result["pairs"][0]["result_frequency"].as_u64().unwrap() > 0);
+        assert_eq!(false, result["truncated"]);
+        assert!(process_query_string("mode=Transform&transform=anagram").is_err());
+    }
+
+    #[test]
+    fn test_hidden() {
+        let result = process_query_string("mode=Hidden&pattern=bathe reef&min_length=5").unwrap();
+        assert_eq!("there", result[0]["word"].to_string());
+        assert_eq!("contiguous", result[0]["method"].to_string());
+        assert!(result[0]["across_words"].as_bool().unwrap());
+        let result = process_query_string("mode=Hidden&pattern=tahxe&max_step=2").unwrap();
+        assert!(result
+            .members()
+            .any(|w| w["word"] == "the" && w["method"] == "every_nth" && w["step"] == 2));
+    }
+
+    #[test]
+    fn test_acrostic() {
+        let result = process_query_string(
+            "mode=Acrostic&pattern=1 2 3 4 5/6 7/8 9 10/11 12 13 14\
+             &clues=1 6 3/7 2 5/8 4 10 13/9 14 12&known=1%3Dt,7%3Ds,11%3Dt",
+        )
+        .unwrap();
+        assert!(result["solved"].as_bool().unwrap());
+        assert_eq!("there is the tree", result["quote"].to_string());
+        assert_eq!("tsth", result["source"].to_string());
+        assert_eq!("i", result["letters"]["6"].to_string());
+        assert!(process_query_string("mode=Acrostic&pattern=1 2&clues=3").is_err());
+    }
+
+    #[test]
+    fn test_letter_boxed() {
+        let result = process_query_string("mode=LetterBoxed&pattern=hge,oir,nst,myv").unwrap();
+        assert!(result["words"].members().any(|w| w["word"] == "some"));
+        assert_eq!(2, result["solutions"][0].len());
+        assert!(process_query_string("mode=LetterBoxed&pattern=abc,def").is_err());
+    }
+
+    #[test]
+    fn test_word_search() {
+        let result =
+            process_query_string("mode=WordSearch&pattern=tree,hxxx,exxx&min_length=3").unwrap();
+        assert!(result
+            .members()
+            .any(|w| w["word"] == "the" && w["direction"] == "down" && w["row"] == 0));
+        let result = process_query_string(
+            "mode=WordSearchGenerator&pattern=there,house&rows=6&columns=6&seed=3",
+        )
+        .unwrap();
+        assert_eq!(6, result["grid"].len());
+        assert_eq!("house", result["words"][0]["word"].to_string());
+        assert_eq!("3", result["seed"].to_string());
+        assert!(
+            process_query_string("mode=WordSearchGenerator&pattern=there&rows=2&columns=2")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_puzzle_generator() {
+        let result = process_query_string(
+            "mode=PuzzleGenerator&pattern=Thing%09the tree%0Aabcdefghijklmnopq&seed=1",
+        )
+        .unwrap();
+        assert_eq!(Some(false), result["truncated"].as_bool());
+        assert_eq!(1, result["puzzles"].len());
+        assert_eq!("Thing", result["puzzles"][0]["category"].to_string());
+        assert_eq!(
+            "   THE TREE   ",
+            result["puzzles"][0]["board"][1].to_string()
+        );
+        assert_eq!("easy", result["puzzles"][0]["difficulty"].to_string());
+        assert!(
+            process_query_string("mode=PuzzleGenerator&pattern=was&difficulty=tricky").is_err()
+        );
+    }
+
+    #[test]
+    fn test_total_frequency() {
+        let dictionary = Dictionary::open().unwrap();
+        let text = std::fs::read_to_string(find_processed_file("word_frequency.txt")).unwrap();
+        let expected = text
+            .lines()
+            .map(|line| line.split(' ').nth(1).unwrap().parse::<u64>().unwrap())
+            .sum::<u64>();
+        assert_eq!(expected, dictionary.total_frequency().unwrap());
+        // the second call uses the stored total
+        assert_eq!(expected, dictionary.total_frequency().unwrap());
+    }
+
+    #[test]
+    fn test_debug() {
+        let result = process_query_string("mode=Crossword&pattern=t?e&debug=1").unwrap();
+        assert_eq!("the", result["words"][0]["word"].to_string());
+        let strategy = result["strategy"].to_string();
+        assert!(result["estimated_costs"][strategy.as_str()]
+            .as_f64()
+            .is_some());
+        let result = process_query_string("mode=Crossword&pattern=t?e&letters=1&debug=1").unwrap();
+        assert!(!result["letters"].is_empty());
+        assert_eq!(strategy, result["strategy"].to_string());
+        // not without the flag
+        let result = process_query_string("mode=Crossword&pattern=t?e").unwrap();
+        assert!(result.is_array());
+    }
+
+    #[test]
+    fn test_advisor() {
+        let result = process_query_string("mode=Advisor&pattern=t?e&guessed=te&bank=2000").unwrap();
+        // "the" is nearly certain, so solving is worth nearly the whole bank
+        let solve = result["solve"].as_f64().unwrap();
+        assert!(solve > 1900.0 && solve <= 2000.0);
+        assert_eq!("h", result["spin"]["letter"].to_string());
+        assert_eq!("spin", result["recommended"]["action"].to_string());
+        let result = process_query_string("mode=Advisor&pattern=th?&guessed=th&bank=200").unwrap();
+        // can't afford a vowel
+        assert!(result["buy_vowel"].is_null());
+        assert!(process_query_string("mode=Advisor&pattern=th?&bank=lots").is_err());
+    }
+
     #[test]
     fn test_invalid_mode() {
         let result = process_query_string("mode=NotARealMode&pattern=t??&absent_letters=h");
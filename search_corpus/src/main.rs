@@ -1,4 +1,4 @@
-use search_corpus::process_query_string;
+use search_corpus::{batch::process_batch_json, process_query_string};
 
 extern crate cgi;
 extern crate json;
@@ -28,6 +28,10 @@ fn error(s: &str) -> cgi::Response {
 }
 
 fn process_request(request: &cgi::Request) -> Result<json::JsonValue, String> {
+    if request.method() == cgi::http::Method::POST {
+        let body = std::str::from_utf8(request.body()).map_err(|e| e.to_string())?;
+        return process_batch_json(body);
+    }
     let query = request
         .uri()
         .query()
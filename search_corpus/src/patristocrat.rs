@@ -0,0 +1,439 @@
+use crate::{
+    extend_cipher_key,
+    fst_walk::FstCursor,
+    letter_ngrams::{hill_climb, LetterNgrams},
+    parse_min_frequency, CipherKey, Dictionary, QueryLimits, WorkBudget,
+};
+use memmap::Mmap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+};
+
+// Long enough for any real puzzle, short enough that a search can't run away.
+const MAX_CIPHERTEXT_LENGTH: usize = 500;
+// Longest word tried at each point in a patristocrat.
+const MAX_WORD_LENGTH: usize = 20;
+// How many partial decodings are kept for each point in the ciphertext.
+const BEAM_WIDTH: usize = 100;
+// How many words (the most common ones) are tried from each partial decoding.
+const MAX_CANDIDATES: usize = 50;
+// Only this many decodings are returned from a query.
+const MAX_DECODINGS_RETURNED: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spacing {
+    /// An aristocrat - the spaces are the word breaks.
+    Words,
+    /// A patristocrat - spaces (usually groups of five, if there are any)
+    /// don't mean anything.
+    Ignored,
+}
+
+impl TryFrom<&str> for Spacing {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "words" => Ok(Spacing::Words),
+            "none" | "groups" => Ok(Spacing::Ignored),
+            _ => Err(format!("Unrecognized spacing {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ciphertext {
+    /// The cipher letters, all uppercase.
+    letters: Vec<char>,
+    /// For each point between letters, whether a word can end there, or
+    /// `None` if a word can end anywhere.
+    word_ends: Option<Vec<bool>>,
+}
+
+impl Ciphertext {
+    /// Parses ciphertext, which is letters and whitespace. Both uppercase and
+    /// lowercase letters are cipher letters.
+    pub fn parse(ciphertext: &str, spacing: Spacing) -> Result<Ciphertext, String> {
+        let mut letters = vec![];
+        let mut word_ends = vec![false];
+        for c in ciphertext.chars() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if !c.is_ascii_alphabetic() {
+                return Err("Disallowed characters in ciphertext".to_string());
+            }
+            if letters.len() >= MAX_CIPHERTEXT_LENGTH {
+                return Err(format!(
+                    "Ciphertext can be at most {} letters",
+                    MAX_CIPHERTEXT_LENGTH
+                ));
+            }
+            letters.push(c.to_ascii_uppercase());
+            word_ends.push(false);
+        }
+        if letters.is_empty() {
+            return Err("Ciphertext is empty".to_string());
+        }
+        let word_ends = match spacing {
+            Spacing::Words => {
+                let mut position = 0;
+                for c in ciphertext.chars() {
+                    if c.is_whitespace() {
+                        word_ends[position] = true;
+                    } else {
+                        position += 1;
+                    }
+                }
+                word_ends[0] = false;
+                word_ends[position] = true;
+                Some(word_ends)
+            }
+            Spacing::Ignored => None,
+        };
+        Ok(Ciphertext { letters, word_ends })
+    }
+
+    pub fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.letters.is_empty()
+    }
+
+    /// The cipher letters, with spaces between the words if they're known.
+    fn spaced(&self) -> String {
+        let mut spaced = String::new();
+        for (i, letter) in self.letters.iter().enumerate() {
+            if i > 0 && self.word_ends.as_ref().is_some_and(|ends| ends[i]) {
+                spaced.push(' ');
+            }
+            spaced.push(*letter);
+        }
+        spaced
+    }
+
+    fn can_end_word(&self, position: usize) -> bool {
+        self.word_ends.as_ref().is_none_or(|ends| ends[position])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoding {
+    pub words: Vec<(String, u64)>,
+    /// Cipher letter to the letter it stands for.
+    pub key: BTreeMap<char, char>,
+    /// The log probability of the words, treating each one as independent.
+    pub score: f64,
+}
+
+impl Decoding {
+    pub fn plaintext(&self) -> String {
+        self.words
+            .iter()
+            .map(|w| w.0.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodingResults {
+    /// Sorted by descending score.
+    pub decodings: Vec<Decoding>,
+    pub truncated: bool,
+}
+
+/// A decoding of the start of the ciphertext.
+#[derive(Clone)]
+struct Partial {
+    key: CipherKey,
+    words: Vec<(String, u64)>,
+    score: f64,
+}
+
+struct WordFinder<'a> {
+    ciphertext: &'a Ciphertext,
+    min_frequency: u64,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+}
+
+impl<'a> WordFinder<'a> {
+    /// The words that could start at `start` given `key`, with the key each
+    /// one extends it to.
+    fn find_words(
+        &mut self,
+        start: usize,
+        key: &CipherKey,
+        root: &FstCursor<Mmap>,
+    ) -> Vec<(String, u64, CipherKey)> {
+        let mut found = vec![];
+        self.extend(start, start, root, &mut String::new(), key, &mut found);
+        found.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        found.truncate(MAX_CANDIDATES);
+        found
+    }
+
+    fn extend(
+        &mut self,
+        start: usize,
+        position: usize,
+        cursor: &FstCursor<Mmap>,
+        word: &mut String,
+        key: &CipherKey,
+        found: &mut Vec<(String, u64, CipherKey)>,
+    ) {
+        if position > start && self.ciphertext.can_end_word(position) {
+            if let Some(frequency) = cursor.frequency() {
+                if frequency >= self.min_frequency {
+                    found.push((word.clone(), frequency, key.clone()));
+                }
+            }
+            // aristocrat words can't run past a space
+            if self.ciphertext.word_ends.is_some() {
+                return;
+            }
+        }
+        if position == self.ciphertext.len() || word.len() == MAX_WORD_LENGTH {
+            return;
+        }
+        let symbol = self.ciphertext.letters[position];
+        for letter in 'a'..='z' {
+            // cryptogram rules - a letter can't stand for itself
+            if letter == symbol.to_ascii_lowercase() {
+                continue;
+            }
+            let mut extended = key.clone();
+            if !extend_cipher_key(&letter.to_string(), &symbol.to_string(), &mut extended) {
+                continue;
+            }
+            if !self.budget.charge() {
+                self.truncated = true;
+                return;
+            }
+            if let Some(next) = cursor.step(&[letter as u8]) {
+                word.push(letter);
+                self.extend(start, position + 1, &next, word, &extended, found);
+                word.pop();
+            }
+        }
+    }
+}
+
+/// Decodes a patristocrat (or an aristocrat, depending on `spacing`) by
+/// splitting it into words and working out the key at the same time. Each
+/// word is scored by how common it is, and only the best `BEAM_WIDTH`
+/// partial decodings are kept at each point in the ciphertext.
+pub fn decode(
+    dictionary: &Dictionary,
+    ciphertext: &Ciphertext,
+    min_frequency: u64,
+    limits: &QueryLimits,
+) -> Result<DecodingResults, String> {
+    let map = dictionary.fst_map()?;
+    let total = dictionary.total_frequency()?.max(1) as f64;
+    let root = FstCursor::new(map.as_fst());
+    let mut finder = WordFinder {
+        ciphertext,
+        min_frequency,
+        budget: WorkBudget::new(limits),
+        truncated: false,
+    };
+    let mut beams = vec![vec![]; ciphertext.len() + 1];
+    beams[0].push(Partial {
+        key: CipherKey::new(),
+        words: vec![],
+        score: 0.0,
+    });
+    for position in 0..ciphertext.len() {
+        let mut beam = std::mem::take(&mut beams[position]);
+        beam.sort_by(|a, b| b.score.total_cmp(&a.score));
+        beam.truncate(BEAM_WIDTH);
+        for partial in beam {
+            for (word, frequency, key) in finder.find_words(position, &partial.key, &root) {
+                let end = position + word.len();
+                let mut words = partial.words.clone();
+                words.push((word, frequency));
+                beams[end].push(Partial {
+                    key,
+                    words,
+                    score: partial.score + (frequency.max(1) as f64 / total).ln(),
+                });
+            }
+            if finder.truncated {
+                break;
+            }
+        }
+    }
+    let mut decodings = beams
+        .pop()
+        .unwrap()
+        .into_iter()
+        .map(|partial| Decoding {
+            words: partial.words,
+            key: partial.key.into_iter().collect(),
+            score: partial.score,
+        })
+        .collect::<Vec<_>>();
+    decodings.sort_by(|a, b| b.score.total_cmp(&a.score));
+    decodings.truncate(MAX_DECODINGS_RETURNED);
+    Ok(DecodingResults {
+        decodings,
+        truncated: finder.truncated,
+    })
+}
+
+fn key_to_json(key: &BTreeMap<char, char>) -> json::JsonValue {
+    let mut json = json::JsonValue::new_object();
+    for (symbol, letter) in key {
+        json[symbol.to_string()] = letter.to_string().into();
+    }
+    json
+}
+
+/// Handles a query string with mode `Patristocrat`, where `pattern` is the
+/// ciphertext, `spacing` is `none`, `groups` (both the default) or `words`
+/// (for an aristocrat) and `min_frequency` is optional. If no decoding fits
+/// the word list, this falls back on scoring keys by letter n-grams.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let spacing = Spacing::try_from(query_parts.get("spacing").map_or("none", |s| s.as_str()))?;
+    let ciphertext = Ciphertext::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+        spacing,
+    )?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let limits = QueryLimits::default();
+    let results = decode(&Dictionary::open()?, &ciphertext, min_frequency, &limits)?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    if results.decodings.is_empty() {
+        // names and rare words aren't in the word list, but can still look
+        // like English
+        if let Ok(model) = LetterNgrams::open() {
+            let decoding = hill_climb(&model, &ciphertext.spaced(), "", 0, &limits);
+            if decoding.truncated {
+                return Err("Query took too long".to_string());
+            }
+            return Ok(json::array![json::object! {
+                "plaintext" => decoding.plaintext.clone(),
+                "words" => json::JsonValue::new_array(),
+                "key" => key_to_json(&decoding.key),
+                "score" => decoding.score,
+                "method" => "ngrams"
+            }]);
+        }
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .decodings
+            .iter()
+            .map(|decoding| {
+                json::object! {
+                    "plaintext" => decoding.plaintext(),
+                    "words" => json::JsonValue::Array(decoding
+                        .words
+                        .iter()
+                        .map(|(word, frequency)| {
+                            json::object! { "word" => word.clone(), "frequency" => *frequency }
+                        })
+                        .collect()),
+                    "key" => key_to_json(&decoding.key),
+                    "score" => decoding.score,
+                    "method" => "words"
+                }
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "there is this tree", shifted by three letters
+    const CIPHERTEXT: &str = "WKHUH LV WKLV WUHH";
+
+    fn best(ciphertext: &str, spacing: Spacing, min_frequency: u64) -> Option<Decoding> {
+        let results = decode(
+            &Dictionary::open().unwrap(),
+            &Ciphertext::parse(ciphertext, spacing).unwrap(),
+            min_frequency,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!results.truncated);
+        results.decodings.into_iter().next()
+    }
+
+    #[test]
+    fn test_parse_ciphertext() {
+        let ciphertext = Ciphertext::parse("ab c", Spacing::Words).unwrap();
+        assert_eq!(vec!['A', 'B', 'C'], ciphertext.letters);
+        assert_eq!(Some(vec![false, false, true, true]), ciphertext.word_ends);
+        let ciphertext = Ciphertext::parse("ab c", Spacing::Ignored).unwrap();
+        assert!(ciphertext.can_end_word(1));
+        assert!(Ciphertext::parse("ab, c", Spacing::Ignored).is_err());
+        assert!(Ciphertext::parse("  ", Spacing::Ignored).is_err());
+        assert_eq!(Ok(Spacing::Ignored), Spacing::try_from("groups"));
+        assert!(Spacing::try_from("lines").is_err());
+    }
+
+    #[test]
+    fn test_spaced() {
+        assert_eq!(
+            "WKHUH LV WKLV WUHH",
+            Ciphertext::parse(" WKHUH LV  WKLV WUHH", Spacing::Words)
+                .unwrap()
+                .spaced()
+        );
+        assert_eq!(
+            "WKHUHLVWKLVWUHH",
+            Ciphertext::parse(CIPHERTEXT, Spacing::Ignored)
+                .unwrap()
+                .spaced()
+        );
+    }
+
+    #[test]
+    fn test_decode_patristocrat() {
+        let decoding = best(&CIPHERTEXT.replace(' ', ""), Spacing::Ignored, 0).unwrap();
+        assert_eq!("there is this tree", decoding.plaintext());
+        assert_eq!(Some(&'t'), decoding.key.get(&'W'));
+        assert_eq!(6, decoding.key.len());
+        let groups = best("WKHUH LVWKL VWUHH", Spacing::Ignored, 0).unwrap();
+        assert_eq!(decoding, groups);
+    }
+
+    #[test]
+    fn test_decode_aristocrat() {
+        let decoding = best(CIPHERTEXT, Spacing::Words, 0).unwrap();
+        assert_eq!(
+            vec!["there", "is", "this", "tree"],
+            decoding
+                .words
+                .iter()
+                .map(|w| w.0.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_decode_follows_cryptogram_rules() {
+        // "tee" would need T to stand for itself
+        assert!(best("TEE", Spacing::Words, 0).is_none_or(|d| d.plaintext() != "tee"));
+        // two different letters can't both be e
+        assert!(best("ABC", Spacing::Words, 0).is_some_and(|d| {
+            let mut letters = d.key.values().collect::<Vec<_>>();
+            letters.sort_unstable();
+            letters.dedup();
+            letters.len() == 3
+        }));
+        assert_eq!(None, best(CIPHERTEXT, Spacing::Words, u64::MAX));
+    }
+}
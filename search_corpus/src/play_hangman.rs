@@ -0,0 +1,48 @@
+use search_corpus::{
+    hangman::{
+        EvilHost, FixedWordHost, Guesser, HangmanGame, Host, LetterChoice, RecommenderGuesser,
+        DEFAULT_MAX_WRONG_GUESSES,
+    },
+    Dictionary,
+};
+use std::env;
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    let usage = format!("usage: {} <word> | --evil <length>", args[0]);
+    let dictionary = Dictionary::open()?;
+    let mut host: Box<dyn Host> = match args.get(1).map(|s| s.as_str()) {
+        Some("--evil") => {
+            let length = args
+                .get(2)
+                .ok_or_else(|| usage.clone())?
+                .parse::<usize>()
+                .map_err(|e| e.to_string())?;
+            Box::new(EvilHost::new(&dictionary, length)?)
+        }
+        Some(word) => Box::new(FixedWordHost::new(word)),
+        None => return Err(usage),
+    };
+    let mut guesser = RecommenderGuesser::new(&dictionary, LetterChoice::MostLikely);
+    let mut game = HangmanGame::new(host.as_ref(), DEFAULT_MAX_WRONG_GUESSES);
+    println!("{}", game.pattern());
+    while !game.is_won() && !game.is_lost() {
+        let letter = guesser
+            .choose_letter(&game)
+            .ok_or_else(|| "Out of letters to guess".to_string())?;
+        let count = game.guess(letter, host.as_mut())?;
+        println!(
+            "{}: {} ({} found, {} wrong guesses left)",
+            letter,
+            game.pattern(),
+            count,
+            game.guesses_left()
+        );
+    }
+    println!(
+        "{} - the word was {}",
+        if game.is_won() { "Won" } else { "Lost" },
+        host.answer()
+    );
+    Ok(())
+}
@@ -0,0 +1,539 @@
+use crate::{
+    parse_min_frequency, parse_usize_param,
+    simulator::{parse_puzzles, Board, Puzzle},
+    try_find_processed_file, Dictionary, PatternMode, QueryLimits, WorkBudget,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{collections::HashMap, convert::TryFrom, fs};
+
+// The rows of the puzzle board from top to bottom - the top and bottom rows
+// are shorter because of the rounded corners.
+pub const BOARD_ROW_WIDTHS: [usize; 4] = [12, 14, 14, 12];
+// The letters given in the bonus round, which is about as much as players
+// ever get for free.
+pub const DEFAULT_OPENING_LETTERS: &str = "rstlne";
+pub const DEFAULT_PUZZLE_COUNT: usize = 5;
+// Which rows a puzzle with a given number of lines can go on, best first.
+// Short puzzles sit in the middle of the board.
+const ROW_CHOICES: [&[&[usize]]; 4] = [
+    &[&[1], &[2]],
+    &[&[1, 2], &[0, 1], &[2, 3]],
+    &[&[1, 2, 3], &[0, 1, 2]],
+    &[&[0, 1, 2, 3]],
+];
+// A word the dictionary doesn't know counts as this many candidates.
+const UNKNOWN_WORD_CANDIDATES: usize = 1000;
+// Difficulty scores (the log10 of the number of ways to fill in the board)
+// below these are easy and medium.
+const EASY_SCORE: f64 = 1.0;
+const MEDIUM_SCORE: f64 = 3.0;
+// Only consider this many of the most common bigrams.
+const MAX_BIGRAM_PHRASES: usize = 10000;
+const MAX_PUZZLE_COUNT: usize = 100;
+// Punctuation that gets its own square on the board, showing from the start.
+const BOARD_PUNCTUATION: &str = "&,.?!:";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rating {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl TryFrom<&str> for Rating {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "easy" => Ok(Rating::Easy),
+            "medium" => Ok(Rating::Medium),
+            "hard" => Ok(Rating::Hard),
+            _ => Err(format!("Unknown difficulty {}", value)),
+        }
+    }
+}
+
+impl Rating {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rating::Easy => "easy",
+            Rating::Medium => "medium",
+            Rating::Hard => "hard",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    /// How many dictionary words fit each word of the puzzle once the
+    /// opening letters are showing (1 for words that are all showing).
+    pub word_candidates: Vec<usize>,
+    /// The log10 of the product of `word_candidates`.
+    pub score: f64,
+    pub rating: Rating,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedPuzzle {
+    pub puzzle: Puzzle,
+    /// The board's rows, with a space for each empty square.
+    pub board: Vec<String>,
+    pub difficulty: Difficulty,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeneratedPuzzles {
+    pub puzzles: Vec<GeneratedPuzzle>,
+    /// Whether rating the puzzles gave up before there were enough.
+    pub truncated: bool,
+}
+
+/// Lays `phrase` out on the board, wrapping between words and centering
+/// each line, using as few lines as possible. Punctuation takes up a square
+/// like a letter. Returns an error if it doesn't fit.
+pub fn layout_board(phrase: &str) -> Result<Vec<String>, String> {
+    if phrase
+        .chars()
+        .any(|c| !c.is_ascii_alphabetic() && !" '-".contains(c) && !BOARD_PUNCTUATION.contains(c))
+    {
+        return Err("Disallowed characters in phrase".to_string());
+    }
+    let words = phrase.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() {
+        return Err("Phrase has no words".to_string());
+    }
+    for row_choices in ROW_CHOICES.iter() {
+        for rows in row_choices.iter() {
+            if let Some(wrapped) = wrap_words(&words, rows) {
+                let mut board = BOARD_ROW_WIDTHS
+                    .iter()
+                    .map(|width| " ".repeat(*width))
+                    .collect::<Vec<_>>();
+                for (row, line) in rows.iter().zip(wrapped) {
+                    let width = BOARD_ROW_WIDTHS[*row];
+                    let padding = (width - line.len()) / 2;
+                    board[*row] = format!(
+                        "{}{}{}",
+                        " ".repeat(padding),
+                        line.to_ascii_uppercase(),
+                        " ".repeat(width - padding - line.len())
+                    );
+                }
+                return Ok(board);
+            }
+        }
+    }
+    Err("Phrase doesn't fit on the board".to_string())
+}
+
+/// Fills the lines for `rows` with as many of `words` as fit on each, or
+/// returns `None` if they don't all fit.
+fn wrap_words(words: &[&str], rows: &[usize]) -> Option<Vec<String>> {
+    let mut lines = vec![String::new(); rows.len()];
+    let mut line = 0;
+    for word in words {
+        loop {
+            let width = BOARD_ROW_WIDTHS[*rows.get(line)?];
+            let current = &mut lines[line];
+            let needed = if current.is_empty() {
+                word.len()
+            } else {
+                current.len() + 1 + word.len()
+            };
+            if needed <= width {
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.push_str(word);
+                break;
+            }
+            if current.is_empty() {
+                // too long for any line
+                return None;
+            }
+            line += 1;
+        }
+    }
+    if lines.iter().any(|line| line.is_empty()) {
+        return None;
+    }
+    Some(lines)
+}
+
+/// Rates how hard `phrase` is by how many words fit each of its words once
+/// `opening_letters` are showing.
+pub fn rate_difficulty(
+    dictionary: &Dictionary,
+    phrase: &str,
+    opening_letters: &str,
+    limits: &QueryLimits,
+) -> Result<Difficulty, String> {
+    rate_difficulty_with_budget(
+        dictionary,
+        phrase,
+        opening_letters,
+        &mut WorkBudget::new(limits),
+    )
+}
+
+/// Like `rate_difficulty()`, but charges the searches to `budget`. A word
+/// whose search runs out counts as one the dictionary doesn't know.
+fn rate_difficulty_with_budget(
+    dictionary: &Dictionary,
+    phrase: &str,
+    opening_letters: &str,
+    budget: &mut WorkBudget,
+) -> Result<Difficulty, String> {
+    // the punctuation is showing, and a literal ? would look like a hidden
+    // letter
+    let letters = phrase
+        .chars()
+        .filter(|c| !BOARD_PUNCTUATION.contains(*c))
+        .collect::<String>();
+    let mut board = Board::new(&letters);
+    for letter in opening_letters.chars() {
+        if !letter.is_ascii_alphabetic() {
+            return Err("Disallowed characters in opening letters".to_string());
+        }
+        board.guess(letter);
+    }
+    let absent_letters = board.guessed_letters();
+    let mut word_candidates = vec![];
+    for pattern in board.word_patterns() {
+        if !pattern.contains('?') {
+            word_candidates.push(1);
+            continue;
+        }
+        let candidates = match dictionary.search_with_budget(
+            PatternMode::WheelOfFortune,
+            &pattern,
+            &absent_letters,
+            budget,
+        ) {
+            Ok(results) if !results.truncated && !results.words.is_empty() => results.words.len(),
+            _ => UNKNOWN_WORD_CANDIDATES,
+        };
+        word_candidates.push(candidates);
+    }
+    let score = word_candidates
+        .iter()
+        .map(|c| (*c as f64).log10())
+        .sum::<f64>();
+    let rating = if score < EASY_SCORE {
+        Rating::Easy
+    } else if score < MEDIUM_SCORE {
+        Rating::Medium
+    } else {
+        Rating::Hard
+    };
+    Ok(Difficulty {
+        word_candidates,
+        score,
+        rating,
+    })
+}
+
+/// Parses lines of "<first word> <second word> <count>", most common first,
+/// into two-word phrases, skipping any seen fewer than `min_frequency` times.
+pub fn parse_bigram_phrases(contents: &str, min_frequency: u64) -> Result<Vec<Puzzle>, String> {
+    let mut phrases = vec![];
+    for line in contents.lines().take(MAX_BIGRAM_PHRASES) {
+        let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
+        let (first, second, count) = match parts[..] {
+            [first, second, count] => (first, second, count),
+            _ => return Err(format!("Invalid bigram line {}", line)),
+        };
+        if count.parse::<u64>().map_err(|e| e.to_string())? >= min_frequency {
+            phrases.push(Puzzle {
+                category: String::new(),
+                phrase: format!("{} {}", first, second),
+            });
+        }
+    }
+    Ok(phrases)
+}
+
+/// Reads the two-word phrases from `word_bigrams.txt`.
+pub fn open_bigram_phrases(min_frequency: u64) -> Result<Vec<Puzzle>, String> {
+    let path = try_find_processed_file("word_bigrams.txt")
+        .ok_or_else(|| "Couldn't find word_bigrams.txt!".to_string())?;
+    parse_bigram_phrases(
+        &fs::read_to_string(path).map_err(|e| e.to_string())?,
+        min_frequency,
+    )
+}
+
+/// Picks up to `count` puzzles at random from `phrases` that fit on the
+/// board (and have the given `rating`, if there is one). Rating them all
+/// shares one budget, and once it runs out no more puzzles are added.
+pub fn generate_puzzles(
+    dictionary: &Dictionary,
+    phrases: &[Puzzle],
+    count: usize,
+    opening_letters: &str,
+    rating: Option<Rating>,
+    seed: u64,
+    limits: &QueryLimits,
+) -> Result<GeneratedPuzzles, String> {
+    let mut order = phrases.iter().collect::<Vec<_>>();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    let mut budget = WorkBudget::new(limits);
+    let mut results = GeneratedPuzzles::default();
+    for puzzle in order {
+        if results.puzzles.len() >= count {
+            break;
+        }
+        let board = match layout_board(&puzzle.phrase) {
+            Ok(board) => board,
+            Err(_) => continue,
+        };
+        let difficulty =
+            rate_difficulty_with_budget(dictionary, &puzzle.phrase, opening_letters, &mut budget)?;
+        // the rating might be off if a search ran out
+        if budget.is_exhausted() {
+            results.truncated = true;
+            break;
+        }
+        if rating.is_some_and(|rating| rating != difficulty.rating) {
+            continue;
+        }
+        results.puzzles.push(GeneratedPuzzle {
+            puzzle: puzzle.clone(),
+            board,
+            difficulty,
+        });
+    }
+    Ok(results)
+}
+
+/// Handles a query string with mode `PuzzleGenerator`. `pattern` is the
+/// phrases to choose from, one per line as in a puzzle file (see
+/// `parse_puzzles()`); without it, the phrases come from the bigram file,
+/// using `min_frequency`. `count`, `opening`, `difficulty` and `seed` are
+/// optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let phrases = match query_parts.get("pattern") {
+        Some(pattern) => parse_puzzles(pattern),
+        None => open_bigram_phrases(parse_min_frequency(query_parts, 0)?)?,
+    };
+    let count = parse_usize_param(query_parts, "count", DEFAULT_PUZZLE_COUNT)?;
+    if count > MAX_PUZZLE_COUNT {
+        return Err(format!("Can make at most {} puzzles", MAX_PUZZLE_COUNT));
+    }
+    let opening_letters = query_parts
+        .get("opening")
+        .map(|opening| opening.as_str())
+        .unwrap_or(DEFAULT_OPENING_LETTERS);
+    let rating = match query_parts.get("difficulty") {
+        Some(difficulty) => Some(Rating::try_from(difficulty.as_str())?),
+        None => None,
+    };
+    // pick a seed if there isn't one, but return it so the puzzles can be
+    // made again
+    let seed = match query_parts.get("seed") {
+        Some(seed) => seed.parse::<u64>().map_err(|e| e.to_string())?,
+        None => rand::random(),
+    };
+    let results = generate_puzzles(
+        &Dictionary::open()?,
+        &phrases,
+        count,
+        opening_letters,
+        rating,
+        seed,
+        &QueryLimits::default(),
+    )?;
+    Ok(json::object! {
+        "seed" => seed.to_string(),
+        "truncated" => results.truncated,
+        "puzzles" => json::JsonValue::Array(results.puzzles
+            .into_iter()
+            .map(|p| json::object! {
+                "category" => p.puzzle.category,
+                "phrase" => p.puzzle.phrase,
+                "board" => p.board,
+                "word_candidates" => p.difficulty.word_candidates,
+                "score" => p.difficulty.score,
+                "difficulty" => p.difficulty.rating.name()
+            })
+            .collect())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_one_line() {
+        let board = layout_board("the tree").unwrap();
+        assert_eq!(
+            vec![
+                " ".repeat(12),
+                "   THE TREE   ".to_string(),
+                " ".repeat(14),
+                " ".repeat(12)
+            ],
+            board
+        );
+    }
+
+    #[test]
+    fn test_layout_wraps_words() {
+        // doesn't fit on three lines
+        let board = layout_board("there is this house where the other tree").unwrap();
+        assert_eq!(
+            vec![
+                "  THERE IS  ",
+                "  THIS HOUSE  ",
+                "  WHERE THE   ",
+                " OTHER TREE ",
+            ],
+            board
+        );
+        let board = layout_board("there is this tree").unwrap();
+        assert_eq!(
+            vec![
+                " ".repeat(12),
+                "THERE IS THIS ".to_string(),
+                "     TREE     ".to_string(),
+                " ".repeat(12)
+            ],
+            board
+        );
+    }
+
+    #[test]
+    fn test_layout_errors() {
+        // too long for any row
+        assert!(layout_board("abcdefghijklmno").is_err());
+        assert!(layout_board(&"word ".repeat(20)).is_err());
+        assert!(layout_board("what;").is_err());
+        assert!(layout_board("  ").is_err());
+        // fits on the 14 letter rows but not the 12
+        assert!(layout_board("abcdefghijklm").is_ok());
+    }
+
+    #[test]
+    fn test_layout_punctuation() {
+        let board = layout_board("there, & the tree?").unwrap();
+        assert_eq!(
+            vec![
+                " ".repeat(12),
+                " THERE, & THE ".to_string(),
+                "    TREE?     ".to_string(),
+                " ".repeat(12)
+            ],
+            board
+        );
+        // the ! needs a square too
+        assert!(layout_board("abcdefghijklmn").is_ok());
+        assert!(layout_board("abcdefghijklmn!").is_err());
+    }
+
+    #[test]
+    fn test_rate_difficulty() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        let difficulty = rate_difficulty(&dictionary, "there tree", "rstlne", &limits).unwrap();
+        assert_eq!(vec![1, 1], difficulty.word_candidates);
+        assert_eq!(Rating::Easy, difficulty.rating);
+        let difficulty = rate_difficulty(&dictionary, "was", "", &limits).unwrap();
+        assert!(difficulty.word_candidates[0] > 10);
+        assert_ne!(Rating::Easy, difficulty.rating);
+        assert!(rate_difficulty(&dictionary, "was", "1", &limits).is_err());
+        // punctuation is already showing
+        let difficulty =
+            rate_difficulty(&dictionary, "there? tree & there!", "rstlne", &limits).unwrap();
+        assert_eq!(vec![1, 1, 1], difficulty.word_candidates);
+    }
+
+    #[test]
+    fn test_parse_bigram_phrases() {
+        let phrases = parse_bigram_phrases("of the 100\nin the 50\nthe tree 5\n", 10).unwrap();
+        assert_eq!(
+            vec!["of the", "in the"],
+            phrases
+                .iter()
+                .map(|p| p.phrase.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(parse_bigram_phrases("of the\n", 0).is_err());
+        assert!(parse_bigram_phrases("of the x\n", 0).is_err());
+    }
+
+    #[test]
+    fn test_generate_puzzles() {
+        let dictionary = Dictionary::open().unwrap();
+        let phrases =
+            parse_puzzles("Thing\tthe tree\nPhrase\tthere is this house\nabcdefghijklmnopq\nwas");
+        let limits = QueryLimits::default();
+        let results =
+            generate_puzzles(&dictionary, &phrases, 10, "rstlne", None, 0, &limits).unwrap();
+        assert!(!results.truncated);
+        let puzzles = results.puzzles;
+        // the long one doesn't fit
+        assert_eq!(3, puzzles.len());
+        assert_eq!(
+            puzzles,
+            generate_puzzles(&dictionary, &phrases, 10, "rstlne", None, 0, &limits)
+                .unwrap()
+                .puzzles
+        );
+        let tree = puzzles
+            .iter()
+            .find(|p| p.puzzle.phrase == "the tree")
+            .unwrap();
+        assert_eq!("Thing", tree.puzzle.category);
+        assert_eq!(Rating::Easy, tree.difficulty.rating);
+        let easy = generate_puzzles(
+            &dictionary,
+            &phrases,
+            10,
+            "rstlne",
+            Some(Rating::Easy),
+            0,
+            &limits,
+        )
+        .unwrap();
+        assert!(easy
+            .puzzles
+            .iter()
+            .all(|p| p.difficulty.rating == Rating::Easy));
+        assert_eq!(
+            1,
+            generate_puzzles(&dictionary, &phrases, 1, "rstlne", None, 0, &limits)
+                .unwrap()
+                .puzzles
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_truncated_search_is_unknown_word() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits {
+            max_scanned_entries: 1,
+            ..QueryLimits::default()
+        };
+        let difficulty = rate_difficulty(&dictionary, "was", "", &limits).unwrap();
+        assert_eq!(vec![UNKNOWN_WORD_CANDIDATES], difficulty.word_candidates);
+    }
+
+    #[test]
+    fn test_generate_puzzles_shares_a_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        let phrases = parse_puzzles(
+            "was
+this
+that",
+        );
+        let limits = QueryLimits {
+            max_scanned_entries: 1,
+            ..QueryLimits::default()
+        };
+        let results = generate_puzzles(&dictionary, &phrases, 10, "", None, 0, &limits).unwrap();
+        assert!(results.truncated);
+        assert!(results.puzzles.is_empty());
+    }
+}
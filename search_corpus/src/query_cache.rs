@@ -0,0 +1,258 @@
+use crate::{
+    validate_absent_letters, validate_pattern, Dictionary, PatternMode, QueryLimits, SearchResults,
+    WorkBudget,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    mode: PatternMode,
+    pattern: String,
+    absent_letters: String,
+}
+
+impl CacheKey {
+    /// Builds a key such that queries that must have the same results get
+    /// the same key.
+    fn new(mode: PatternMode, pattern: &str, absent_letters: &str) -> CacheKey {
+        // In cryptogram mode the case of the pattern matters (uppercase
+        // letters are unknown), otherwise everything gets lowercased.
+        let pattern = if mode == PatternMode::Cryptogram {
+            pattern.to_string()
+        } else {
+            pattern.to_ascii_lowercase()
+        };
+        let mut absent_letters = match mode {
+            // absent letters don't matter in crossword mode
+            PatternMode::Crossword => vec![],
+            // the pattern has feedback letters in it too, so keep them all
+            PatternMode::Wordle => absent_letters
+                .chars()
+                .map(|c| c.to_ascii_lowercase())
+                .collect::<Vec<_>>(),
+            // letters in the pattern are already known not to be behind a ?,
+            // so listing them as absent doesn't change anything
+            PatternMode::WheelOfFortune | PatternMode::Hangman | PatternMode::Cryptogram => {
+                absent_letters
+                    .chars()
+                    .map(|c| c.to_ascii_lowercase())
+                    .filter(|c| !pattern.contains(*c))
+                    .collect::<Vec<_>>()
+            }
+        };
+        absent_letters.sort_unstable();
+        absent_letters.dedup();
+        CacheKey {
+            mode,
+            pattern,
+            absent_letters: absent_letters.into_iter().collect(),
+        }
+    }
+}
+
+/// Hit/miss counts for a `QueryCache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// A least-recently-used cache of query results, along with the dictionary
+/// they come from, for callers that make the same queries over and over -
+/// like a batch request, where a puzzle often has the same pattern twice.
+pub struct QueryCache {
+    dictionary: Dictionary,
+    capacity: usize,
+    // each entry remembers when it was last used, and `by_last_use` maps
+    // back from that to the key so we can find the oldest one quickly
+    entries: HashMap<CacheKey, (Arc<SearchResults>, u64)>,
+    by_last_use: BTreeMap<u64, CacheKey>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    pub fn new(dictionary: Dictionary, capacity: usize) -> QueryCache {
+        QueryCache {
+            dictionary,
+            capacity,
+            entries: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Like `Dictionary::search()`, but returns cached results if an
+    /// equivalent query has been made recently. Truncated results aren't
+    /// cached.
+    pub fn search(
+        &mut self,
+        mode: PatternMode,
+        pattern: &str,
+        absent_letters: &str,
+        limits: &QueryLimits,
+    ) -> Result<Arc<SearchResults>, String> {
+        self.search_with_budget(mode, pattern, absent_letters, &mut WorkBudget::new(limits))
+    }
+
+    /// Like `search()`, but charges any searching to `budget`.
+    pub(crate) fn search_with_budget(
+        &mut self,
+        mode: PatternMode,
+        pattern: &str,
+        absent_letters: &str,
+        budget: &mut WorkBudget,
+    ) -> Result<Arc<SearchResults>, String> {
+        // validate first so an invalid query can't get a valid query's results
+        validate_pattern(pattern, &mode)?;
+        validate_absent_letters(absent_letters)?;
+        let key = CacheKey::new(mode, pattern, absent_letters);
+        self.clock += 1;
+        if let Some((results, last_use)) = self.entries.get_mut(&key) {
+            self.by_last_use.remove(last_use);
+            *last_use = self.clock;
+            self.by_last_use.insert(self.clock, key);
+            self.hits += 1;
+            return Ok(results.clone());
+        }
+        self.misses += 1;
+        let results =
+            Arc::new(
+                self.dictionary
+                    .search_with_budget(mode, pattern, absent_letters, budget)?,
+            );
+        if !results.truncated && self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                if let Some((_, oldest)) = self.by_last_use.pop_first() {
+                    self.entries.remove(&oldest);
+                }
+            }
+            self.by_last_use.insert(self.clock, key.clone());
+            self.entries.insert(key, (results.clone(), self.clock));
+        }
+        Ok(results)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.by_last_use.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_normalizes_absent_letters() {
+        let key = CacheKey::new(PatternMode::WheelOfFortune, "t?e", "htht");
+        assert_eq!(key, CacheKey::new(PatternMode::WheelOfFortune, "t?e", "h"));
+        assert_eq!(key, CacheKey::new(PatternMode::WheelOfFortune, "T?E", "H"));
+        assert_ne!(key, CacheKey::new(PatternMode::WheelOfFortune, "t?e", "hr"));
+    }
+
+    #[test]
+    fn test_key_ignores_absent_letters_for_crossword() {
+        assert_eq!(
+            CacheKey::new(PatternMode::Crossword, "t?e", "h"),
+            CacheKey::new(PatternMode::Crossword, "t?e", "")
+        );
+    }
+
+    #[test]
+    fn test_key_keeps_case_for_cryptogram() {
+        assert_ne!(
+            CacheKey::new(PatternMode::Cryptogram, "ABc", ""),
+            CacheKey::new(PatternMode::Cryptogram, "abc", "")
+        );
+        assert_eq!(
+            CacheKey::new(PatternMode::Cryptogram, "ABc", "cd"),
+            CacheKey::new(PatternMode::Cryptogram, "ABc", "d")
+        );
+    }
+
+    #[test]
+    fn test_key_keeps_feedback_letters_for_wordle() {
+        assert_ne!(
+            CacheKey::new(PatternMode::Wordle, "crane:bybbg", "g"),
+            CacheKey::new(PatternMode::Wordle, "crane:bybbg", "")
+        );
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = QueryCache::new(Dictionary::open().unwrap(), 10);
+        let limits = QueryLimits::default();
+        let first = cache
+            .search(PatternMode::WheelOfFortune, "t?e", "h", &limits)
+            .unwrap();
+        let second = cache
+            .search(PatternMode::WheelOfFortune, "t?e", "hth", &limits)
+            .unwrap();
+        assert_eq!("tie", second.words[0].0);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                entries: 1
+            },
+            cache.stats()
+        );
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = QueryCache::new(Dictionary::open().unwrap(), 2);
+        let limits = QueryLimits::default();
+        cache
+            .search(PatternMode::Crossword, "t?e", "", &limits)
+            .unwrap();
+        cache
+            .search(PatternMode::Crossword, "a?d", "", &limits)
+            .unwrap();
+        // use t?e again so a?d is the oldest
+        cache
+            .search(PatternMode::Crossword, "t?e", "", &limits)
+            .unwrap();
+        cache
+            .search(PatternMode::Crossword, "?he", "", &limits)
+            .unwrap();
+        assert_eq!(2, cache.stats().entries);
+        cache
+            .search(PatternMode::Crossword, "t?e", "", &limits)
+            .unwrap();
+        assert_eq!(2, cache.stats().hits);
+        cache
+            .search(PatternMode::Crossword, "a?d", "", &limits)
+            .unwrap();
+        assert_eq!(2, cache.stats().hits);
+    }
+
+    #[test]
+    fn test_invalid_query_is_not_a_hit() {
+        let mut cache = QueryCache::new(Dictionary::open().unwrap(), 10);
+        let limits = QueryLimits::default();
+        cache
+            .search(PatternMode::Crossword, "t?e", "", &limits)
+            .unwrap();
+        assert!(cache
+            .search(PatternMode::Crossword, "t?e", "1", &limits)
+            .is_err());
+    }
+}
@@ -0,0 +1,760 @@
+use crate::{fst_walk::FstCursor, parse_min_frequency, Dictionary, QueryLimits, WorkBudget};
+use memmap::Mmap;
+use std::collections::{HashMap, HashSet};
+
+pub const BOARD_SIZE: usize = 15;
+pub const RACK_SIZE: usize = 7;
+pub const BINGO_BONUS: u32 = 50;
+// Only this many moves are returned from a query.
+const MAX_MOVES_RETURNED: usize = 200;
+const CENTER: usize = BOARD_SIZE / 2;
+
+// The top-left quarter of the board (the rest is mirrored): T is triple
+// word, D is double word, t is triple letter and d is double letter.
+const PREMIUM_QUARTER: [&str; 8] = [
+    "T..d...T", ".D...t..", "..D...d.", "d..D...d", "....D...", ".t...t..", "..d...d.", "T..d...D",
+];
+
+const LETTER_VALUES: [u32; 26] = [
+    1, 3, 3, 2, 1, 4, 2, 4, 1, 8, 5, 1, 3, 1, 1, 3, 10, 1, 1, 1, 1, 4, 4, 8, 4, 10,
+];
+
+/// The value of `letter`'s tile (not counting blanks, which are worth 0).
+pub fn letter_value(letter: char) -> u32 {
+    LETTER_VALUES[(letter as u8 - b'a') as usize]
+}
+
+/// The (letter multiplier, word multiplier) for a square.
+fn premium(row: usize, column: usize) -> (u32, u32) {
+    let fold = |i: usize| if i > CENTER { BOARD_SIZE - 1 - i } else { i };
+    match PREMIUM_QUARTER[fold(row)].as_bytes()[fold(column)] {
+        b'T' => (1, 3),
+        b'D' => (1, 2),
+        b't' => (3, 1),
+        b'd' => (2, 1),
+        _ => (1, 1),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Tile {
+    pub letter: char,
+    /// Whether this is a blank standing for `letter`.
+    pub blank: bool,
+}
+
+impl Tile {
+    fn value(&self) -> u32 {
+        if self.blank {
+            0
+        } else {
+            letter_value(self.letter)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    cells: [[Option<Tile>; BOARD_SIZE]; BOARD_SIZE],
+}
+
+impl Board {
+    /// Parses a board written as 15 rows of 15 characters, separated by
+    /// newlines or `/`. Empty squares are `.`, tiles are lowercase letters
+    /// and blanks are the uppercase letter they stand for.
+    pub fn parse(board: &str) -> Result<Board, String> {
+        let rows = board
+            .split(['\n', '/'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        if rows.len() != BOARD_SIZE || rows.iter().any(|row| row.len() != BOARD_SIZE) {
+            return Err(format!(
+                "Board must be {} rows of {} squares",
+                BOARD_SIZE, BOARD_SIZE
+            ));
+        }
+        let mut cells = [[None; BOARD_SIZE]; BOARD_SIZE];
+        for (r, row) in rows.iter().enumerate() {
+            for (c, square) in row.chars().enumerate() {
+                cells[r][c] = match square {
+                    '.' => None,
+                    'a'..='z' => Some(Tile {
+                        letter: square,
+                        blank: false,
+                    }),
+                    'A'..='Z' => Some(Tile {
+                        letter: square.to_ascii_lowercase(),
+                        blank: true,
+                    }),
+                    _ => return Err("Disallowed characters in board".to_string()),
+                };
+            }
+        }
+        Ok(Board { cells })
+    }
+
+    pub fn empty() -> Board {
+        Board {
+            cells: [[None; BOARD_SIZE]; BOARD_SIZE],
+        }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Tile> {
+        self.cells[row][column]
+    }
+
+    fn transposed(&self) -> Board {
+        let mut cells = [[None; BOARD_SIZE]; BOARD_SIZE];
+        for (r, row) in cells.iter_mut().enumerate() {
+            for (c, cell) in row.iter_mut().enumerate() {
+                *cell = self.cells[c][r];
+            }
+        }
+        Board { cells }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cells.iter().flatten().all(|cell| cell.is_none())
+    }
+
+    /// Empty squares a new word has to touch: ones next to a tile, or the
+    /// center square if the board is empty.
+    fn is_anchor(&self, row: usize, column: usize) -> bool {
+        if self.cells[row][column].is_some() {
+            return false;
+        }
+        if self.is_empty() {
+            return (row, column) == (CENTER, CENTER);
+        }
+        let neighbors = [
+            (row.wrapping_sub(1), column),
+            (row + 1, column),
+            (row, column.wrapping_sub(1)),
+            (row, column + 1),
+        ];
+        neighbors
+            .iter()
+            .any(|(r, c)| *r < BOARD_SIZE && *c < BOARD_SIZE && self.cells[*r][*c].is_some())
+    }
+}
+
+/// The tiles a player has. Blanks are `?`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rack {
+    counts: [u8; 26],
+    blanks: u8,
+}
+
+impl Rack {
+    pub fn parse(rack: &str) -> Result<Rack, String> {
+        if rack.chars().count() > RACK_SIZE {
+            return Err(format!("A rack has at most {} tiles", RACK_SIZE));
+        }
+        let mut counts = [0; 26];
+        let mut blanks = 0;
+        for c in rack.chars() {
+            match c.to_ascii_lowercase() {
+                '?' => blanks += 1,
+                'a'..='z' => counts[(c.to_ascii_lowercase() as u8 - b'a') as usize] += 1,
+                _ => return Err("Disallowed characters in rack".to_string()),
+            }
+        }
+        Ok(Rack { counts, blanks })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Across,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub row: usize,
+    pub column: usize,
+    pub tile: Tile,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    /// Where the main word starts.
+    pub row: usize,
+    pub column: usize,
+    pub direction: Direction,
+    pub word: String,
+    /// The tiles played from the rack.
+    pub placements: Vec<Placement>,
+    pub score: u32,
+    /// The frequency of the main word.
+    pub frequency: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ranking {
+    /// Highest scoring moves first, ties going to more common words.
+    Score,
+    /// Moves making the most common words first, ties going to higher scores.
+    Frequency,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MoveResults {
+    pub moves: Vec<Move>,
+    pub truncated: bool,
+}
+
+/// What the words crossing an empty square allow there.
+#[derive(Clone, Copy)]
+struct CrossCheck {
+    /// Bitmask of letters that make a valid crossing word.
+    allowed: u32,
+    /// The value of the tiles already in the crossing word, or `None` if
+    /// there's no crossing word.
+    score: Option<u32>,
+}
+
+struct Generator<'a, 'f, 'l> {
+    board: &'a Board,
+    map: &'f fst::Map<Mmap>,
+    min_frequency: u64,
+    cross_checks: Vec<Vec<CrossCheck>>,
+    anchors: Vec<Vec<bool>>,
+    budget: &'a mut WorkBudget<'l>,
+    truncated: bool,
+    moves: Vec<Move>,
+}
+
+/// A word being built along a row.
+#[derive(Clone)]
+struct Partial {
+    start: usize,
+    word: String,
+    placements: Vec<Placement>,
+    main_score: u32,
+    word_multiplier: u32,
+    cross_score: u32,
+    touches_anchor: bool,
+}
+
+impl<'a, 'f, 'l> Generator<'a, 'f, 'l> {
+    fn cross_check(
+        board: &Board,
+        row: usize,
+        column: usize,
+        is_word: impl Fn(&str) -> bool,
+    ) -> CrossCheck {
+        let mut above = String::new();
+        let mut score = 0;
+        let mut r = row;
+        while r > 0 {
+            match board.get(r - 1, column) {
+                Some(tile) => {
+                    above.insert(0, tile.letter);
+                    score += tile.value();
+                    r -= 1;
+                }
+                None => break,
+            }
+        }
+        let mut below = String::new();
+        for r in row + 1..BOARD_SIZE {
+            match board.get(r, column) {
+                Some(tile) => {
+                    below.push(tile.letter);
+                    score += tile.value();
+                }
+                None => break,
+            }
+        }
+        if above.is_empty() && below.is_empty() {
+            return CrossCheck {
+                allowed: (1 << 26) - 1,
+                score: None,
+            };
+        }
+        let allowed = (b'a'..=b'z')
+            .filter(|letter| is_word(&format!("{}{}{}", above, *letter as char, below)))
+            .fold(0, |mask, letter| mask | 1 << (letter - b'a'));
+        CrossCheck {
+            allowed,
+            score: Some(score),
+        }
+    }
+
+    fn record(&mut self, row: usize, partial: &Partial, frequency: u64) {
+        let bingo = if partial.placements.len() == RACK_SIZE {
+            BINGO_BONUS
+        } else {
+            0
+        };
+        self.moves.push(Move {
+            row,
+            column: partial.start,
+            direction: Direction::Across,
+            word: partial.word.clone(),
+            placements: partial.placements.clone(),
+            score: partial.main_score * partial.word_multiplier + partial.cross_score + bingo,
+            frequency,
+        });
+    }
+
+    fn extend(
+        &mut self,
+        row: usize,
+        column: usize,
+        cursor: FstCursor<'f, Mmap>,
+        rack: &mut Rack,
+        partial: &mut Partial,
+    ) {
+        if self.truncated {
+            return;
+        }
+        if !self.budget.charge() {
+            self.truncated = true;
+            return;
+        }
+        if column < BOARD_SIZE {
+            if let Some(tile) = self.board.get(row, column) {
+                // have to use the tile that's already there
+                if let Some(cursor) = cursor.step(&[tile.letter as u8]) {
+                    let mut partial = partial.clone();
+                    partial.word.push(tile.letter);
+                    partial.main_score += tile.value();
+                    self.extend(row, column + 1, cursor, rack, &mut partial);
+                }
+                return;
+            }
+        }
+        // the word could end here
+        if !partial.placements.is_empty() && partial.touches_anchor && partial.word.len() > 1 {
+            if let Some(frequency) = cursor.frequency().filter(|f| *f >= self.min_frequency) {
+                self.record(row, partial, frequency);
+            }
+        }
+        if column == BOARD_SIZE {
+            return;
+        }
+        let check = self.cross_checks[row][column];
+        let (letter_multiplier, word_multiplier) = premium(row, column);
+        for letter in b'a'..=b'z' {
+            if check.allowed & (1 << (letter - b'a')) == 0 {
+                continue;
+            }
+            let next = match cursor.step(&[letter]) {
+                Some(next) => next,
+                None => continue,
+            };
+            let index = (letter - b'a') as usize;
+            for blank in [false, true] {
+                if blank {
+                    if rack.blanks == 0 {
+                        continue;
+                    }
+                    rack.blanks -= 1;
+                } else {
+                    if rack.counts[index] == 0 {
+                        continue;
+                    }
+                    rack.counts[index] -= 1;
+                }
+                let tile = Tile {
+                    letter: letter as char,
+                    blank,
+                };
+                let value = tile.value() * letter_multiplier;
+                let mut next_partial = partial.clone();
+                next_partial.word.push(tile.letter);
+                next_partial
+                    .placements
+                    .push(Placement { row, column, tile });
+                next_partial.main_score += value;
+                next_partial.word_multiplier *= word_multiplier;
+                if let Some(cross) = check.score {
+                    next_partial.cross_score += (cross + value) * word_multiplier;
+                }
+                next_partial.touches_anchor |= self.anchors[row][column];
+                self.extend(row, column + 1, next, rack, &mut next_partial);
+                if blank {
+                    rack.blanks += 1;
+                } else {
+                    rack.counts[index] += 1;
+                }
+            }
+        }
+    }
+
+    /// Finds every move across the rows of `board`.
+    fn generate(&mut self, rack: &Rack) {
+        let root = FstCursor::new(self.map.as_fst());
+        for row in 0..BOARD_SIZE {
+            if !self.anchors[row].iter().any(|a| *a) {
+                continue;
+            }
+            for start in 0..BOARD_SIZE {
+                // a word can't start right after a tile
+                if start > 0 && self.board.get(row, start - 1).is_some() {
+                    continue;
+                }
+                // there has to be an anchor close enough to reach
+                let empty_before_anchor = (start..BOARD_SIZE)
+                    .take_while(|c| !self.anchors[row][*c])
+                    .filter(|c| self.board.get(row, *c).is_none())
+                    .count();
+                let reaches_anchor = (start..BOARD_SIZE).any(|c| self.anchors[row][c]);
+                if !reaches_anchor || empty_before_anchor >= RACK_SIZE {
+                    continue;
+                }
+                let mut partial = Partial {
+                    start,
+                    word: String::new(),
+                    placements: vec![],
+                    main_score: 0,
+                    word_multiplier: 1,
+                    cross_score: 0,
+                    touches_anchor: false,
+                };
+                self.extend(row, start, root, &mut rack.clone(), &mut partial);
+            }
+        }
+    }
+}
+
+fn moves_across(
+    board: &Board,
+    map: &fst::Map<Mmap>,
+    rack: &Rack,
+    min_frequency: u64,
+    budget: &mut WorkBudget,
+) -> MoveResults {
+    let is_word = |word: &str| map.get(word).is_some_and(|f| f >= min_frequency);
+    let cross_checks = (0..BOARD_SIZE)
+        .map(|r| {
+            (0..BOARD_SIZE)
+                .map(|c| Generator::cross_check(board, r, c, is_word))
+                .collect()
+        })
+        .collect();
+    let anchors = (0..BOARD_SIZE)
+        .map(|r| (0..BOARD_SIZE).map(|c| board.is_anchor(r, c)).collect())
+        .collect();
+    let mut generator = Generator {
+        board,
+        map,
+        min_frequency,
+        cross_checks,
+        anchors,
+        budget,
+        truncated: false,
+        moves: vec![],
+    };
+    generator.generate(rack);
+    MoveResults {
+        moves: generator.moves,
+        truncated: generator.truncated,
+    }
+}
+
+/// Finds every legal move for `rack` on `board`, where only words with at
+/// least `min_frequency` (including crossing words) count as valid.
+pub fn generate_moves(
+    dictionary: &Dictionary,
+    board: &Board,
+    rack: &Rack,
+    min_frequency: u64,
+    ranking: Ranking,
+    limits: &QueryLimits,
+) -> Result<MoveResults, String> {
+    let map = dictionary.fst_map()?;
+    // both directions share one budget
+    let mut budget = WorkBudget::new(limits);
+    let mut results = moves_across(board, map, rack, min_frequency, &mut budget);
+    // moves down are moves across on the board flipped over its diagonal,
+    // which leaves the premium squares where they were
+    let down = moves_across(&board.transposed(), map, rack, min_frequency, &mut budget);
+    results.truncated |= down.truncated;
+    results.moves.extend(down.moves.into_iter().map(|m| {
+        Move {
+            row: m.column,
+            column: m.row,
+            direction: Direction::Down,
+            placements: m
+                .placements
+                .iter()
+                .map(|p| Placement {
+                    row: p.column,
+                    column: p.row,
+                    tile: p.tile,
+                })
+                .collect(),
+            ..m
+        }
+    }));
+    // a single tile makes words both ways, so it's found both ways
+    let mut seen = HashSet::new();
+    results
+        .moves
+        .retain(|m| m.placements.len() > 1 || seen.insert(m.placements.clone()));
+    results.moves.sort_by(|a, b| {
+        let by_score = b.score.cmp(&a.score);
+        let by_frequency = b.frequency.cmp(&a.frequency);
+        match ranking {
+            Ranking::Score => by_score.then(by_frequency),
+            Ranking::Frequency => by_frequency.then(by_score),
+        }
+        .then(a.word.cmp(&b.word))
+    });
+    Ok(results)
+}
+
+/// Handles a query string with mode `Scrabble`, where `pattern` is the
+/// board (see `Board::parse()`), `rack` is the player's tiles, and
+/// `min_frequency` and `rank` (`score` or `frequency`) are optional. Each
+/// move lists the tiles it places from the rack.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let board = Board::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    )?;
+    let rack = Rack::parse(
+        query_parts
+            .get("rack")
+            .ok_or_else(|| String::from("Internal error - no rack specified!"))?,
+    )?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let ranking = match query_parts.get("rank").map(|r| r.as_str()) {
+        None | Some("score") => Ranking::Score,
+        Some("frequency") => Ranking::Frequency,
+        Some(_) => return Err("Internal error - invalid rank!".to_string()),
+    };
+    let results = generate_moves(
+        &Dictionary::open()?,
+        &board,
+        &rack,
+        min_frequency,
+        ranking,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .moves
+            .iter()
+            .take(MAX_MOVES_RETURNED)
+            .map(|m| {
+                json::object! {
+                    "row" => m.row,
+                    "column" => m.column,
+                    "direction" => match m.direction {
+                        Direction::Across => "across",
+                        Direction::Down => "down",
+                    },
+                    "word" => m.word.clone(),
+                    "score" => m.score,
+                    "frequency" => m.frequency,
+                    "placements" => json::JsonValue::Array(m.placements.iter().map(|p| json::object! {
+                        "row" => p.row,
+                        "column" => p.column,
+                        "letter" => p.tile.letter.to_string(),
+                        "is_blank" => p.tile.blank
+                    }).collect())
+                }
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(words: &[(usize, usize, Direction, &str)]) -> Board {
+        let mut board = Board::empty();
+        for (row, column, direction, word) in words {
+            for (i, letter) in word.chars().enumerate() {
+                let (r, c) = match direction {
+                    Direction::Across => (*row, column + i),
+                    Direction::Down => (row + i, *column),
+                };
+                board.cells[r][c] = Some(Tile {
+                    letter,
+                    blank: false,
+                });
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn test_premium() {
+        assert_eq!((1, 3), premium(0, 0));
+        assert_eq!((1, 3), premium(14, 7));
+        assert_eq!((1, 2), premium(7, 7));
+        assert_eq!((3, 1), premium(13, 9));
+        assert_eq!((2, 1), premium(11, 14));
+        assert_eq!((1, 1), premium(7, 8));
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut rows = vec!["..............."; BOARD_SIZE];
+        rows[7] = ".....thE.......";
+        let board = Board::parse(&rows.join("/")).unwrap();
+        assert_eq!(
+            Some(Tile {
+                letter: 'e',
+                blank: true
+            }),
+            board.get(7, 7)
+        );
+        assert_eq!(None, board.get(0, 0));
+        assert!(Board::parse("...").is_err());
+        assert_eq!(
+            Rack {
+                counts: {
+                    let mut counts = [0; 26];
+                    counts[0] = 2;
+                    counts
+                },
+                blanks: 1
+            },
+            Rack::parse("aA?").unwrap()
+        );
+        assert!(Rack::parse("abcdefgh").is_err());
+        assert!(Rack::parse("ab1").is_err());
+    }
+
+    #[test]
+    fn test_first_move() {
+        let dictionary = Dictionary::open().unwrap();
+        let results = generate_moves(
+            &dictionary,
+            &Board::empty(),
+            &Rack::parse("the").unwrap(),
+            0,
+            Ranking::Score,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!results.truncated);
+        // every move has to cover the center square
+        assert!(results
+            .moves
+            .iter()
+            .all(|m| m.placements.iter().any(|p| (p.row, p.column) == (7, 7))));
+        let the = results
+            .moves
+            .iter()
+            .find(|m| m.word == "the" && m.direction == Direction::Across && m.column == 5)
+            .unwrap();
+        // (4 + 4 + 1) on the double word center square
+        assert_eq!(12, the.score);
+        assert!(results.moves.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn test_both_directions_share_a_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        // enough for the moves across or the moves down, but not both
+        let limits = QueryLimits {
+            max_scanned_entries: 80,
+            ..QueryLimits::default()
+        };
+        let results = generate_moves(
+            &dictionary,
+            &Board::empty(),
+            &Rack::parse("the").unwrap(),
+            0,
+            Ranking::Score,
+            &limits,
+        )
+        .unwrap();
+        assert!(results.truncated);
+    }
+
+    #[test]
+    fn test_cross_words_and_blanks() {
+        let dictionary = Dictionary::open().unwrap();
+        let board = board_with(&[(7, 6, Direction::Across, "he")]);
+        let results = generate_moves(
+            &dictionary,
+            &board,
+            &Rack::parse("t?").unwrap(),
+            0,
+            Ranking::Score,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        // adding a t before "he" makes "the", with no premium squares
+        let the = results
+            .moves
+            .iter()
+            .find(|m| m.word == "the" && m.placements.len() == 1 && !m.placements[0].tile.blank)
+            .unwrap();
+        assert_eq!((7, 5), (the.row, the.column));
+        assert_eq!(6, the.score);
+        // a blank t is worth nothing
+        let blank_the = results
+            .moves
+            .iter()
+            .find(|m| m.word == "the" && m.placements.len() == 1 && m.placements[0].tile.blank)
+            .unwrap();
+        assert_eq!(5, blank_the.score);
+        // every placed tile is on an empty square
+        for m in &results.moves {
+            for p in &m.placements {
+                assert_eq!(None, board.get(p.row, p.column));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cross_check_rejects_non_words() {
+        let dictionary = Dictionary::open().unwrap();
+        let board = board_with(&[(7, 7, Direction::Down, "he")]);
+        let results = generate_moves(
+            &dictionary,
+            &board,
+            &Rack::parse("xq").unwrap(),
+            0,
+            Ranking::Score,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(results.moves.is_empty());
+    }
+
+    #[test]
+    fn test_frequency_cutoff() {
+        let dictionary = Dictionary::open().unwrap();
+        let all = generate_moves(
+            &dictionary,
+            &Board::empty(),
+            &Rack::parse("theb").unwrap(),
+            0,
+            Ranking::Frequency,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(all
+            .moves
+            .windows(2)
+            .all(|w| w[0].frequency >= w[1].frequency));
+        let cutoff = all.moves[0].frequency;
+        let common = generate_moves(
+            &dictionary,
+            &Board::empty(),
+            &Rack::parse("theb").unwrap(),
+            cutoff,
+            Ranking::Score,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(common.moves.iter().all(|m| m.frequency >= cutoff));
+        assert!(common.moves.len() < all.moves.len());
+    }
+}
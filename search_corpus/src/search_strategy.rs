@@ -0,0 +1,168 @@
+use crate::WORD_ALPHABET;
+
+/// The ways `search_pattern()` can find the words matching a pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Intersect bitsets from `word_index.bin`.
+    WordIndex,
+    /// Run a DFA built from the pattern over `word_frequency.fst`.
+    FstDfa,
+    /// Match a regex against every line of `word_frequency.txt`.
+    LinearScan,
+}
+
+impl SearchStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchStrategy::WordIndex => "WordIndex",
+            SearchStrategy::FstDfa => "FstDfa",
+            SearchStrategy::LinearScan => "LinearScan",
+        }
+    }
+}
+
+/// What we know about the dictionary when estimating costs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DictionaryStats {
+    pub total_words: usize,
+    /// The number of words with the same length as the pattern.
+    pub words_of_length: usize,
+}
+
+// Rough relative costs of the unit of work each strategy does. Only the
+// ratios matter.
+const SCAN_ENTRY_COST: f64 = 1.0;
+const FST_NODE_COST: f64 = 0.3;
+const DFA_STATE_COST: f64 = 50.0;
+const BITSET_BLOCK_COST: f64 = 0.05;
+const INDEX_OPEN_COST: f64 = 200.0;
+// Looking at a candidate word (checking cryptogram consistency, copying it
+// into the results) costs the same whichever strategy found it.
+const CANDIDATE_COST: f64 = 0.5;
+// The FST yields words in alphabetical order, so they need sorting
+// by frequency afterwards.
+const SORT_COST: f64 = 0.1;
+
+const LETTER_COUNT: f64 = 26.0;
+
+/// Estimates how many words of the pattern's length match `masks`, assuming
+/// each position is independent.
+fn estimate_matches(masks: &[u32], stats: &DictionaryStats) -> f64 {
+    masks
+        .iter()
+        .fold(stats.words_of_length as f64, |matches, mask| {
+            matches * (mask.count_ones() as f64 / LETTER_COUNT).min(1.0)
+        })
+}
+
+/// Estimates the cost of finding the words matching `masks` (see
+/// `position_masks()`) with `strategy`.
+pub fn estimate_cost(strategy: SearchStrategy, masks: &[u32], stats: &DictionaryStats) -> f64 {
+    let matches = estimate_matches(masks, stats);
+    let work = match strategy {
+        SearchStrategy::LinearScan => stats.total_words as f64 * SCAN_ENTRY_COST,
+        SearchStrategy::FstDfa => {
+            // The search visits every FST node whose prefix the DFA hasn't
+            // rejected yet, and there can't be more of those at a depth than
+            // there are words.
+            let mut prefixes = 1.0;
+            let mut nodes = 0.0;
+            for mask in masks {
+                prefixes = (prefixes * mask.count_ones() as f64).min(stats.total_words as f64);
+                nodes += prefixes;
+            }
+            masks.len() as f64 * DFA_STATE_COST
+                + nodes * FST_NODE_COST
+                + matches * matches.max(2.0).log2() * SORT_COST
+        }
+        SearchStrategy::WordIndex => {
+            let blocks = stats.words_of_length.div_ceil(64) as f64;
+            let alphabet_len = WORD_ALPHABET.len() as u32;
+            let lookups: u32 = masks
+                .iter()
+                .map(|mask| mask.count_ones().min(alphabet_len - mask.count_ones()))
+                .sum();
+            INDEX_OPEN_COST + lookups as f64 * blocks * BITSET_BLOCK_COST
+        }
+    };
+    work + matches * CANDIDATE_COST
+}
+
+/// Picks the cheapest of the `available` strategies, returning it along with
+/// the estimated cost of each one.
+pub fn choose_strategy(
+    masks: &[u32],
+    stats: &DictionaryStats,
+    available: &[SearchStrategy],
+) -> Option<(SearchStrategy, Vec<(SearchStrategy, f64)>)> {
+    let costs = available
+        .iter()
+        .map(|strategy| (*strategy, estimate_cost(*strategy, masks, stats)))
+        .collect::<Vec<_>>();
+    let best = costs
+        .iter()
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())?
+        .0;
+    Some((best, costs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: &[SearchStrategy] = &[
+        SearchStrategy::WordIndex,
+        SearchStrategy::FstDfa,
+        SearchStrategy::LinearScan,
+    ];
+    // Roughly the shape of the real dictionary.
+    const STATS: DictionaryStats = DictionaryStats {
+        total_words: 1_000_000,
+        words_of_length: 100_000,
+    };
+
+    fn letter(c: u8) -> u32 {
+        1 << WORD_ALPHABET.iter().position(|a| *a == c).unwrap()
+    }
+
+    fn any_letter() -> u32 {
+        (0..26).fold(0, |acc, i| acc | (1 << i))
+    }
+
+    #[test]
+    fn test_mostly_fixed_letters_uses_fst() {
+        // ?etwee?
+        let masks = [
+            any_letter(),
+            letter(b'e'),
+            letter(b't'),
+            letter(b'w'),
+            letter(b'e'),
+            letter(b'e'),
+            any_letter(),
+        ];
+        let (strategy, _) = choose_strategy(&masks, &STATS, ALL).unwrap();
+        assert_eq!(SearchStrategy::FstDfa, strategy);
+    }
+
+    #[test]
+    fn test_all_unknown_letters_uses_index() {
+        let masks = [any_letter(); 7];
+        let (strategy, _) = choose_strategy(&masks, &STATS, ALL).unwrap();
+        assert_eq!(SearchStrategy::WordIndex, strategy);
+    }
+
+    #[test]
+    fn test_only_available_strategy_is_chosen() {
+        let masks = [any_letter(); 7];
+        let (strategy, costs) =
+            choose_strategy(&masks, &STATS, &[SearchStrategy::LinearScan]).unwrap();
+        assert_eq!(SearchStrategy::LinearScan, strategy);
+        assert_eq!(1, costs.len());
+    }
+
+    #[test]
+    fn test_nothing_available() {
+        assert!(choose_strategy(&[any_letter()], &STATS, &[]).is_none());
+    }
+}
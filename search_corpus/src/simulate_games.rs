@@ -0,0 +1,36 @@
+use search_corpus::{
+    simulator::{parse_puzzles, simulate, RandomStrategy, SolverStrategy, Strategy, Wheel},
+    Dictionary,
+};
+use std::{env, fs};
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        return Err(format!("usage: {} <puzzle file> [seed]", args[0]));
+    }
+    let puzzles = parse_puzzles(&fs::read_to_string(&args[1]).map_err(|e| e.to_string())?);
+    let seed = match args.get(2) {
+        Some(seed) => seed.parse::<u64>().map_err(|e| e.to_string())?,
+        None => 0,
+    };
+    let dictionary = Dictionary::open()?;
+    let mut players: Vec<Box<dyn Strategy>> = vec![
+        Box::new(RandomStrategy::new(seed)),
+        Box::new(SolverStrategy::new(&dictionary, 0.5)),
+        Box::new(SolverStrategy::new(&dictionary, 0.9)),
+    ];
+    let stats = simulate(&puzzles, &mut players, &Wheel::default(), seed);
+    println!("Played {} puzzles", puzzles.len());
+    for strategy_stats in stats {
+        println!(
+            "{}: won {}/{} rounds, average winnings ${:.0}, average turns to solve {:.1}",
+            strategy_stats.name,
+            strategy_stats.rounds_won,
+            strategy_stats.rounds,
+            strategy_stats.average_winnings(),
+            strategy_stats.average_turns_to_solve()
+        );
+    }
+    Ok(())
+}
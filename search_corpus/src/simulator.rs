@@ -0,0 +1,745 @@
+use crate::{letter_stats::letter_weights, Dictionary, PatternMode, QueryLimits};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+pub const VOWEL_COST: u32 = 250;
+// The winner of a round always gets at least this much.
+pub const HOUSE_MINIMUM: u32 = 1000;
+// Give up on a round if nobody has solved it after this many turns.
+const MAX_TURNS: u32 = 100;
+
+pub(crate) const VOWELS: &str = "aeiou";
+// Most to least common in English text, for when there's nothing better to go on.
+pub(crate) const CONSONANTS_BY_FREQUENCY: &str = "tnshrdlcmwfgypbvkjxqz";
+
+fn is_vowel(c: char) -> bool {
+    VOWELS.contains(c)
+}
+
+/// Whether `c` can be part of a dictionary search - punctuation like `&` or
+/// a trailing `,` is on the board but isn't part of any word.
+fn is_searchable(c: char) -> bool {
+    c.is_ascii_alphabetic() || "?'-".contains(c)
+}
+
+/// The part of `word` from the board that can be searched for.
+fn searchable_part(word: &str) -> String {
+    word.chars().filter(|c| is_searchable(*c)).collect()
+}
+
+/// `word` from the board with its searchable characters replaced by `guess`.
+fn fill_in(word: &str, guess: &str) -> String {
+    let mut guess = guess.chars();
+    word.chars()
+        .map(|c| {
+            if is_searchable(c) {
+                guess.next().unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    pub category: String,
+    pub phrase: String,
+}
+
+/// Parses a puzzle file, which has one puzzle per line: either just the
+/// phrase, or the category and phrase separated by a tab. Blank lines and
+/// lines starting with # are ignored.
+pub fn parse_puzzles(contents: &str) -> Vec<Puzzle> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once('\t') {
+            Some((category, phrase)) => Puzzle {
+                category: category.trim().to_string(),
+                phrase: phrase.trim().to_string(),
+            },
+            None => Puzzle {
+                category: String::new(),
+                phrase: line.to_string(),
+            },
+        })
+        .collect()
+}
+
+/// The puzzle board, as the players see it.
+#[derive(Clone, Debug)]
+pub struct Board {
+    phrase: String,
+    guessed: [bool; 26],
+}
+
+impl Board {
+    pub fn new(phrase: &str) -> Board {
+        Board {
+            phrase: phrase.to_ascii_lowercase(),
+            guessed: [false; 26],
+        }
+    }
+
+    fn letter_index(letter: char) -> usize {
+        (letter.to_ascii_lowercase() as u8 - b'a') as usize
+    }
+
+    pub fn is_guessed(&self, letter: char) -> bool {
+        self.guessed[Board::letter_index(letter)]
+    }
+
+    /// Marks `letter` as guessed, returning how many times it appears.
+    pub fn guess(&mut self, letter: char) -> usize {
+        self.guessed[Board::letter_index(letter)] = true;
+        self.count(letter)
+    }
+
+    fn count(&self, letter: char) -> usize {
+        let letter = letter.to_ascii_lowercase();
+        self.phrase.chars().filter(|c| *c == letter).count()
+    }
+
+    /// All the letters that have been called, whether or not they were in the puzzle.
+    pub fn guessed_letters(&self) -> String {
+        ('a'..='z').filter(|c| self.is_guessed(*c)).collect()
+    }
+
+    /// The board with unrevealed letters replaced by `?`.
+    pub fn display(&self) -> String {
+        self.phrase
+            .chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() && !self.is_guessed(c) {
+                    '?'
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+
+    /// The displayed board split into words, without punctuation, suitable
+    /// for WheelOfFortune searches.
+    pub fn word_patterns(&self) -> Vec<String> {
+        self.display()
+            .split_whitespace()
+            .map(searchable_part)
+            .filter(|pattern| !pattern.is_empty())
+            .collect()
+    }
+
+    pub fn is_fully_revealed(&self) -> bool {
+        !self.display().contains('?')
+    }
+
+    fn has_hidden(&self, letters: &str) -> bool {
+        letters
+            .chars()
+            .any(|c| !self.is_guessed(c) && self.count(c) > 0)
+    }
+
+    pub fn has_hidden_consonants(&self) -> bool {
+        self.has_hidden(CONSONANTS_BY_FREQUENCY)
+    }
+
+    pub fn is_solution(&self, guess: &str) -> bool {
+        guess.trim().to_ascii_lowercase() == self.phrase
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Spin,
+    BuyVowel(char),
+    Solve(String),
+}
+
+/// A way of playing the game.
+pub trait Strategy {
+    fn name(&self) -> String;
+    /// Called at the start of each of the player's moves.
+    fn choose_action(&mut self, board: &Board, round_bank: u32) -> Action;
+    /// Called after spinning and landing on a cash wedge.
+    fn choose_consonant(&mut self, board: &Board, round_bank: u32) -> char;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wedge {
+    Cash(u32),
+    Bankrupt,
+    LoseATurn,
+}
+
+pub struct Wheel {
+    pub wedges: Vec<Wedge>,
+}
+
+impl Default for Wheel {
+    /// A typical first-round wheel.
+    fn default() -> Self {
+        use Wedge::*;
+        Wheel {
+            wedges: vec![
+                Cash(2500),
+                Cash(600),
+                Cash(700),
+                Cash(600),
+                Cash(650),
+                Cash(500),
+                Cash(700),
+                Bankrupt,
+                Cash(600),
+                Cash(550),
+                Cash(500),
+                Cash(600),
+                Bankrupt,
+                Cash(650),
+                Cash(700),
+                LoseATurn,
+                Cash(800),
+                Cash(500),
+                Cash(650),
+                Cash(500),
+                Cash(900),
+                Cash(500),
+                Cash(600),
+                Cash(700),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundResult {
+    /// The player who solved the puzzle, if anyone did.
+    pub winner: Option<usize>,
+    /// What each player banked from the round.
+    pub winnings: Vec<u32>,
+    pub turns: u32,
+}
+
+/// Plays one round of `puzzle`, with `players[starting_player]` going first.
+/// With no players, nobody plays and nobody wins.
+pub fn play_round<R: Rng>(
+    puzzle: &Puzzle,
+    players: &mut [Box<dyn Strategy + '_>],
+    starting_player: usize,
+    wheel: &Wheel,
+    rng: &mut R,
+) -> RoundResult {
+    if players.is_empty() {
+        return RoundResult {
+            winner: None,
+            winnings: vec![],
+            turns: 0,
+        };
+    }
+    let mut board = Board::new(&puzzle.phrase);
+    let mut banks = vec![0u32; players.len()];
+    let mut player = starting_player % players.len();
+    for turn in 1..=MAX_TURNS {
+        // keep going until the player does something that ends their turn
+        loop {
+            let bank = banks[player];
+            match players[player].choose_action(&board, bank) {
+                Action::Solve(guess) => {
+                    if board.is_solution(&guess) {
+                        let mut winnings = vec![0; players.len()];
+                        winnings[player] = banks[player].max(HOUSE_MINIMUM);
+                        return RoundResult {
+                            winner: Some(player),
+                            winnings,
+                            turns: turn,
+                        };
+                    }
+                    break;
+                }
+                Action::BuyVowel(vowel) => {
+                    let vowel = vowel.to_ascii_lowercase();
+                    if bank < VOWEL_COST || !is_vowel(vowel) || board.is_guessed(vowel) {
+                        break;
+                    }
+                    banks[player] -= VOWEL_COST;
+                    if board.guess(vowel) == 0 {
+                        break;
+                    }
+                }
+                Action::Spin => {
+                    if !board.has_hidden_consonants() {
+                        break;
+                    }
+                    match *wheel.wedges.choose(rng).unwrap() {
+                        Wedge::Bankrupt => {
+                            banks[player] = 0;
+                            break;
+                        }
+                        Wedge::LoseATurn => break,
+                        Wedge::Cash(value) => {
+                            let consonant = players[player]
+                                .choose_consonant(&board, bank)
+                                .to_ascii_lowercase();
+                            if !consonant.is_ascii_lowercase()
+                                || is_vowel(consonant)
+                                || board.is_guessed(consonant)
+                            {
+                                break;
+                            }
+                            let count = board.guess(consonant);
+                            if count == 0 {
+                                break;
+                            }
+                            banks[player] += value * count as u32;
+                        }
+                    }
+                }
+            }
+        }
+        player = (player + 1) % players.len();
+    }
+    RoundResult {
+        winner: None,
+        winnings: vec![0; players.len()],
+        turns: MAX_TURNS,
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StrategyStats {
+    pub name: String,
+    pub rounds: u32,
+    pub rounds_won: u32,
+    pub total_winnings: u64,
+    /// Summed over the rounds this strategy won.
+    pub total_turns_to_solve: u64,
+}
+
+impl StrategyStats {
+    pub fn average_winnings(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        self.total_winnings as f64 / self.rounds as f64
+    }
+
+    pub fn average_turns_to_solve(&self) -> f64 {
+        if self.rounds_won == 0 {
+            return 0.0;
+        }
+        self.total_turns_to_solve as f64 / self.rounds_won as f64
+    }
+}
+
+/// Plays every puzzle once with each player going first, and returns
+/// statistics for each player's strategy.
+pub fn simulate(
+    puzzles: &[Puzzle],
+    players: &mut [Box<dyn Strategy + '_>],
+    wheel: &Wheel,
+    seed: u64,
+) -> Vec<StrategyStats> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut stats = players
+        .iter()
+        .map(|player| StrategyStats {
+            name: player.name(),
+            ..StrategyStats::default()
+        })
+        .collect::<Vec<_>>();
+    for puzzle in puzzles {
+        for starting_player in 0..players.len() {
+            let result = play_round(puzzle, players, starting_player, wheel, &mut rng);
+            for (i, player_stats) in stats.iter_mut().enumerate() {
+                player_stats.rounds += 1;
+                player_stats.total_winnings += result.winnings[i] as u64;
+                if result.winner == Some(i) {
+                    player_stats.rounds_won += 1;
+                    player_stats.total_turns_to_solve += result.turns as u64;
+                }
+            }
+        }
+    }
+    stats
+}
+
+/// Calls random consonants and never buys vowels - a baseline to compare against.
+pub struct RandomStrategy {
+    rng: StdRng,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> RandomStrategy {
+        RandomStrategy {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose_action(&mut self, board: &Board, _round_bank: u32) -> Action {
+        if board.has_hidden_consonants() {
+            Action::Spin
+        } else {
+            // nothing else we can do, so take a stab at it
+            Action::Solve(board.display())
+        }
+    }
+
+    fn choose_consonant(&mut self, board: &Board, _round_bank: u32) -> char {
+        let unguessed = CONSONANTS_BY_FREQUENCY
+            .chars()
+            .filter(|c| !board.is_guessed(*c))
+            .collect::<Vec<_>>();
+        *unguessed.choose(&mut self.rng).unwrap_or(&'t')
+    }
+}
+
+/// What `SolverStrategy` knows about the board after searching the dictionary.
+#[derive(Clone, Debug)]
+struct Analysis {
+    /// The probability that each letter is somewhere in the puzzle.
+    letter_probabilities: [f64; 26],
+    /// The most likely solution and how likely it is, if every word has
+    /// at least one candidate.
+    best_guess: Option<(String, f64)>,
+}
+
+/// Searches the dictionary for each word on the board and calls the
+/// letters most likely to be in the puzzle, solving once the most likely
+/// answer is at least `solve_threshold` likely.
+pub struct SolverStrategy<'a> {
+    dictionary: &'a Dictionary,
+    solve_threshold: f64,
+    // the last board analyzed (as displayed, and the letters called) and
+    // what we found, since spinning and then picking a consonant both need
+    // it for the same board
+    last_analysis: Option<(String, String, Analysis)>,
+}
+
+impl<'a> SolverStrategy<'a> {
+    pub fn new(dictionary: &'a Dictionary, solve_threshold: f64) -> SolverStrategy<'a> {
+        SolverStrategy {
+            dictionary,
+            solve_threshold,
+            last_analysis: None,
+        }
+    }
+
+    fn analysis(&mut self, board: &Board) -> Analysis {
+        let (display, guessed) = (board.display(), board.guessed_letters());
+        if let Some((last_display, last_guessed, analysis)) = &self.last_analysis {
+            if *last_display == display && *last_guessed == guessed {
+                return analysis.clone();
+            }
+        }
+        let analysis = self.analyze(board);
+        self.last_analysis = Some((display, guessed, analysis.clone()));
+        analysis
+    }
+
+    fn analyze(&self, board: &Board) -> Analysis {
+        let absent_letters = board.guessed_letters();
+        let mut not_present = [1.0f64; 26];
+        let mut guess_words = vec![];
+        let mut guess_probability = Some(1.0);
+        for word in board.display().split_whitespace() {
+            let pattern = searchable_part(word);
+            if !pattern.contains('?') {
+                guess_words.push(word.to_string());
+                continue;
+            }
+            let results = self
+                .dictionary
+                .search(
+                    PatternMode::WheelOfFortune,
+                    &pattern,
+                    &absent_letters,
+                    &QueryLimits::default(),
+                )
+                .ok()
+                .filter(|results| !results.truncated && !results.words.is_empty());
+            let results = match results {
+                Some(results) => results,
+                None => {
+                    // not a word we know, so we can't solve it yet
+                    guess_probability = None;
+                    continue;
+                }
+            };
+            for (letter, weight) in letter_weights(&pattern, &results.words) {
+                not_present[Board::letter_index(letter)] *= 1.0 - weight;
+            }
+            let total: u64 = results.words.iter().map(|w| w.1).sum();
+            let best = &results.words[0];
+            guess_probability = guess_probability.map(|p| p * best.1 as f64 / total as f64);
+            guess_words.push(fill_in(word, &best.0));
+        }
+        let mut letter_probabilities = [0.0; 26];
+        for (probability, not_present) in letter_probabilities.iter_mut().zip(not_present.iter()) {
+            *probability = 1.0 - not_present;
+        }
+        Analysis {
+            letter_probabilities,
+            best_guess: guess_probability.map(|p| (guess_words.join(" "), p)),
+        }
+    }
+
+    fn best_letter(
+        &self,
+        board: &Board,
+        analysis: &Analysis,
+        letters: &str,
+    ) -> Option<(char, f64)> {
+        // ties go to whichever comes first in `letters`
+        letters
+            .chars()
+            .filter(|c| !board.is_guessed(*c))
+            .map(|c| (c, analysis.letter_probabilities[Board::letter_index(c)]))
+            .fold(None, |best: Option<(char, f64)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            })
+    }
+}
+
+impl<'a> Strategy for SolverStrategy<'a> {
+    fn name(&self) -> String {
+        format!("solver (solve at {:.0}%)", self.solve_threshold * 100.0)
+    }
+
+    fn choose_action(&mut self, board: &Board, round_bank: u32) -> Action {
+        if board.is_fully_revealed() {
+            return Action::Solve(board.display());
+        }
+        let analysis = self.analysis(board);
+        if let Some((guess, probability)) = &analysis.best_guess {
+            if *probability >= self.solve_threshold {
+                return Action::Solve(guess.clone());
+            }
+        }
+        let consonant = if board.has_hidden_consonants() {
+            self.best_letter(board, &analysis, CONSONANTS_BY_FREQUENCY)
+        } else {
+            None
+        };
+        let vowel = self.best_letter(board, &analysis, VOWELS);
+        match (consonant, vowel) {
+            (Some(consonant), Some(vowel)) if round_bank >= VOWEL_COST && vowel.1 > consonant.1 => {
+                Action::BuyVowel(vowel.0)
+            }
+            (Some(_), _) => Action::Spin,
+            (None, Some(vowel)) if round_bank >= VOWEL_COST => Action::BuyVowel(vowel.0),
+            _ => Action::Solve(
+                analysis
+                    .best_guess
+                    .map_or_else(|| board.display(), |guess| guess.0),
+            ),
+        }
+    }
+
+    fn choose_consonant(&mut self, board: &Board, _round_bank: u32) -> char {
+        let analysis = self.analysis(board);
+        self.best_letter(board, &analysis, CONSONANTS_BY_FREQUENCY)
+            .map_or('t', |best| best.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Plays a fixed list of actions and consonants, then keeps trying to solve.
+    struct ScriptedStrategy {
+        actions: Vec<Action>,
+        consonants: Vec<char>,
+    }
+
+    impl Strategy for ScriptedStrategy {
+        fn name(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn choose_action(&mut self, board: &Board, _round_bank: u32) -> Action {
+            if self.actions.is_empty() {
+                Action::Solve(board.display())
+            } else {
+                self.actions.remove(0)
+            }
+        }
+
+        fn choose_consonant(&mut self, _board: &Board, _round_bank: u32) -> char {
+            self.consonants.remove(0)
+        }
+    }
+
+    /// Keeps trying to solve, and remembers the letters that had been
+    /// called each time it was its turn.
+    struct WatchingStrategy {
+        guessed: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Strategy for WatchingStrategy {
+        fn name(&self) -> String {
+            "watching".to_string()
+        }
+
+        fn choose_action(&mut self, board: &Board, _round_bank: u32) -> Action {
+            self.guessed.borrow_mut().push(board.guessed_letters());
+            Action::Solve(board.display())
+        }
+
+        fn choose_consonant(&mut self, _board: &Board, _round_bank: u32) -> char {
+            unreachable!()
+        }
+    }
+
+    fn puzzle(phrase: &str) -> Puzzle {
+        Puzzle {
+            category: String::new(),
+            phrase: phrase.to_string(),
+        }
+    }
+
+    fn one_wedge_wheel(wedge: Wedge) -> Wheel {
+        Wheel {
+            wedges: vec![wedge],
+        }
+    }
+
+    #[test]
+    fn test_parse_puzzles() {
+        let puzzles = parse_puzzles("# comment\nPhrase\tTHE END\n\nTHE START\n");
+        assert_eq!(
+            vec![
+                Puzzle {
+                    category: "Phrase".to_string(),
+                    phrase: "THE END".to_string()
+                },
+                puzzle("THE START")
+            ],
+            puzzles
+        );
+    }
+
+    #[test]
+    fn test_board_display() {
+        let mut board = Board::new("Can't Stop");
+        assert_eq!("???'? ????", board.display());
+        assert_eq!(2, board.guess('t'));
+        assert_eq!(vec!["???'t", "?t??"], board.word_patterns());
+        assert!(board.is_solution("CAN'T STOP"));
+        assert!(!board.is_fully_revealed());
+        let board = Board::new("Rock & roll, baby");
+        assert_eq!(vec!["????", "????", "????"], board.word_patterns());
+        assert_eq!("roll,", fill_in("????,", "roll"));
+    }
+
+    #[test]
+    fn test_solver_strategy_solves_punctuated_phrase() {
+        let dictionary = Dictionary::open().unwrap();
+        let mut solver = SolverStrategy::new(&dictionary, 0.5);
+        let mut board = Board::new("the, tree & house");
+        for letter in "thersou".chars() {
+            board.guess(letter);
+        }
+        assert_eq!(
+            Action::Solve("the, tree & house".to_string()),
+            solver.choose_action(&board, 0)
+        );
+        board = Board::new("the tree & house");
+        for letter in "thr".chars() {
+            board.guess(letter);
+        }
+        let analysis = solver.analysis(&board);
+        assert!(analysis.best_guess.unwrap().0.contains(" & "));
+    }
+
+    #[test]
+    fn test_cash_and_vowel_then_solve() {
+        let mut players: Vec<Box<dyn Strategy>> = vec![Box::new(ScriptedStrategy {
+            actions: vec![Action::Spin, Action::BuyVowel('e')],
+            consonants: vec!['t'],
+        })];
+        let wheel = one_wedge_wheel(Wedge::Cash(900));
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = play_round(&puzzle("tee tee"), &mut players, 0, &wheel, &mut rng);
+        assert_eq!(Some(0), result.winner);
+        // 2 t's at $900, minus a vowel
+        assert_eq!(vec![1800 - VOWEL_COST], result.winnings);
+        assert_eq!(1, result.turns);
+    }
+
+    #[test]
+    fn test_bankrupt_loses_bank_and_turn() {
+        let mut players: Vec<Box<dyn Strategy>> = vec![
+            Box::new(ScriptedStrategy {
+                actions: vec![Action::Spin, Action::Spin],
+                consonants: vec![],
+            }),
+            Box::new(ScriptedStrategy {
+                actions: vec![Action::Solve("the end".to_string())],
+                consonants: vec![],
+            }),
+        ];
+        let wheel = one_wedge_wheel(Wedge::Bankrupt);
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = play_round(&puzzle("the end"), &mut players, 0, &wheel, &mut rng);
+        assert_eq!(Some(1), result.winner);
+        assert_eq!(vec![0, HOUSE_MINIMUM], result.winnings);
+        assert_eq!(2, result.turns);
+    }
+
+    #[test]
+    fn test_cant_buy_vowel_without_money() {
+        let guessed = Rc::new(RefCell::new(vec![]));
+        let mut players: Vec<Box<dyn Strategy>> = vec![
+            Box::new(ScriptedStrategy {
+                actions: vec![Action::BuyVowel('e')],
+                consonants: vec![],
+            }),
+            Box::new(WatchingStrategy {
+                guessed: guessed.clone(),
+            }),
+        ];
+        let wheel = one_wedge_wheel(Wedge::LoseATurn);
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = play_round(&puzzle("e"), &mut players, 0, &wheel, &mut rng);
+        // the second player's solve attempt fails since the e wasn't revealed,
+        // then the first player solves with "?"
+        assert_eq!(None, result.winner);
+        assert_eq!("", guessed.borrow()[0]);
+        assert!(guessed.borrow().iter().all(|letters| letters.is_empty()));
+    }
+
+    #[test]
+    fn test_no_players() {
+        let mut players: Vec<Box<dyn Strategy>> = vec![];
+        let wheel = Wheel::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = play_round(&puzzle("the end"), &mut players, 0, &wheel, &mut rng);
+        assert_eq!(None, result.winner);
+        assert!(result.winnings.is_empty());
+    }
+
+    #[test]
+    fn test_solver_strategy_wins_against_random() {
+        let dictionary = Dictionary::open().unwrap();
+        let mut players: Vec<Box<dyn Strategy>> = vec![
+            Box::new(RandomStrategy::new(1)),
+            Box::new(SolverStrategy::new(&dictionary, 0.5)),
+        ];
+        let puzzles = parse_puzzles("THE SCHOOL\nWHAT WILL BE\nSUCH GOOD PEOPLE\n");
+        let stats = simulate(&puzzles, &mut players, &Wheel::default(), 0);
+        assert_eq!(6, stats[0].rounds);
+        assert_eq!(6, stats[1].rounds);
+        assert!(stats[1].rounds_won > stats[0].rounds_won);
+        assert!(stats[1].average_winnings() > 0.0);
+        assert!(stats[1].average_turns_to_solve() >= 1.0);
+    }
+}
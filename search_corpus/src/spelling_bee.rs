@@ -0,0 +1,202 @@
+use crate::{build_dfa, parse_min_frequency, Dictionary, QueryLimits, WorkBudget};
+use fst::{IntoStreamer, Streamer};
+use std::collections::HashMap;
+
+pub const MIN_WORD_LENGTH: usize = 4;
+const PUZZLE_LETTERS: usize = 7;
+const PANGRAM_BONUS: u32 = 7;
+// The cutoff when a query doesn't give one. The dictionary goes all the way
+// down to words seen 10,000 times, far rarer than a puzzle would accept.
+pub const DEFAULT_MIN_FREQUENCY: u64 = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpellingBeeWord {
+    pub word: String,
+    pub frequency: u64,
+    pub score: u32,
+    /// Whether the word uses all seven letters.
+    pub is_pangram: bool,
+}
+
+/// The standard scoring: four-letter words are worth 1 point, longer words
+/// are worth 1 point per letter, and pangrams get 7 extra points.
+pub fn score(word: &str, is_pangram: bool) -> u32 {
+    let base = if word.len() == MIN_WORD_LENGTH {
+        1
+    } else {
+        word.len() as u32
+    };
+    if is_pangram {
+        base + PANGRAM_BONUS
+    } else {
+        base
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpellingBeeResults {
+    /// Sorted by descending score, then descending frequency.
+    pub words: Vec<SpellingBeeWord>,
+    pub truncated: bool,
+}
+
+impl SpellingBeeResults {
+    pub fn total_score(&self) -> u32 {
+        self.words.iter().map(|w| w.score).sum()
+    }
+}
+
+/// Finds every word at least `MIN_WORD_LENGTH` long that's made only from
+/// `letters` (seven different letters) and uses `center` (one of them).
+/// Words with a frequency below `min_frequency` are left out, since the
+/// dictionary has plenty of obscure words a puzzle wouldn't accept.
+pub fn solve_spelling_bee(
+    dictionary: &Dictionary,
+    letters: &str,
+    center: char,
+    min_frequency: u64,
+    limits: &QueryLimits,
+) -> Result<SpellingBeeResults, String> {
+    let letters = letters.to_ascii_lowercase();
+    let center = center.to_ascii_lowercase();
+    let mut distinct = letters.chars().collect::<Vec<_>>();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if distinct.len() != PUZZLE_LETTERS
+        || letters.len() != PUZZLE_LETTERS
+        || !distinct.iter().all(|c| c.is_ascii_lowercase())
+    {
+        return Err(format!("Need exactly {} different letters", PUZZLE_LETTERS));
+    }
+    if !letters.contains(center) {
+        return Err("The center letter must be one of the letters".to_string());
+    }
+    let map = dictionary.fst_map()?;
+    let class = format!("[{}]", letters);
+    let dfa = build_dfa(&format!("(?-u){}*{}{}*", class, center, class), limits)?;
+    let mut stream = map.search(&dfa).into_stream();
+    let mut budget = WorkBudget::new(limits);
+    let mut results = SpellingBeeResults::default();
+    while let Some((word, frequency)) = stream.next() {
+        if !budget.charge() {
+            results.truncated = true;
+            break;
+        }
+        if word.len() < MIN_WORD_LENGTH || frequency < min_frequency {
+            continue;
+        }
+        let word = String::from_utf8(word.to_vec()).map_err(|e| e.to_string())?;
+        let is_pangram = distinct.iter().all(|c| word.contains(*c));
+        results.words.push(SpellingBeeWord {
+            score: score(&word, is_pangram),
+            word,
+            frequency,
+            is_pangram,
+        });
+    }
+    results.words.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.frequency.cmp(&a.frequency))
+            .then(a.word.cmp(&b.word))
+    });
+    Ok(results)
+}
+
+/// Handles a query string with mode `SpellingBee`, where `pattern` is the
+/// seven letters, `center` is the required letter and `min_frequency`
+/// (optional, `DEFAULT_MIN_FREQUENCY` if not given) is the frequency cutoff.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let letters = query_parts
+        .get("pattern")
+        .ok_or_else(|| String::from("Internal error - no pattern specified!"))?;
+    let center = query_parts
+        .get("center")
+        .ok_or_else(|| String::from("Internal error - no center letter specified!"))?;
+    let mut center_chars = center.chars();
+    let center = match (center_chars.next(), center_chars.next()) {
+        (Some(center), None) => center,
+        _ => return Err("The center must be a single letter".to_string()),
+    };
+    let min_frequency = parse_min_frequency(query_parts, DEFAULT_MIN_FREQUENCY)?;
+    let results = solve_spelling_bee(
+        &Dictionary::open()?,
+        letters,
+        center,
+        min_frequency,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::object! {
+        "words" => json::JsonValue::Array(results.words.iter().map(|w| json::object! {
+            "word" => w.word.clone(),
+            "frequency" => w.frequency,
+            "score" => w.score,
+            "pangram" => w.is_pangram
+        }).collect()),
+        "total_score" => results.total_score()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert_eq!(1, score("tree", false));
+        assert_eq!(5, score("there", false));
+        assert_eq!(14, score("clothes", true));
+    }
+
+    #[test]
+    fn test_solve_spelling_bee() {
+        let dictionary = Dictionary::open().unwrap();
+        let results =
+            solve_spelling_bee(&dictionary, "thercab", 'e', 0, &QueryLimits::default()).unwrap();
+        assert!(!results.truncated);
+        let words = results
+            .words
+            .iter()
+            .map(|w| w.word.as_str())
+            .collect::<Vec<_>>();
+        assert!(words.contains(&"there"));
+        assert!(words.contains(&"tree"));
+        for word in &results.words {
+            assert!(word.word.len() >= MIN_WORD_LENGTH);
+            assert!(word.word.contains('e'));
+            assert!(word.word.chars().all(|c| "thercab".contains(c)));
+        }
+        assert!(results.words.windows(2).all(|w| w[0].score > w[1].score
+            || (w[0].score == w[1].score && w[0].frequency >= w[1].frequency)));
+        assert_eq!(
+            results.words.iter().map(|w| w.score).sum::<u32>(),
+            results.total_score()
+        );
+    }
+
+    #[test]
+    fn test_frequency_cutoff() {
+        let dictionary = Dictionary::open().unwrap();
+        let all =
+            solve_spelling_bee(&dictionary, "thercab", 'e', 0, &QueryLimits::default()).unwrap();
+        let cutoff = all.words.iter().map(|w| w.frequency).max().unwrap();
+        let common =
+            solve_spelling_bee(&dictionary, "thercab", 'e', cutoff, &QueryLimits::default())
+                .unwrap();
+        assert_eq!(1, common.words.len());
+        assert_eq!(cutoff, common.words[0].frequency);
+    }
+
+    #[test]
+    fn test_invalid_letters() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        assert!(solve_spelling_bee(&dictionary, "therca", 'e', 0, &limits).is_err());
+        assert!(solve_spelling_bee(&dictionary, "thereca", 'e', 0, &limits).is_err());
+        assert!(solve_spelling_bee(&dictionary, "thercab", 'z', 0, &limits).is_err());
+        assert!(solve_spelling_bee(&dictionary, "therc?b", 'e', 0, &limits).is_err());
+    }
+}
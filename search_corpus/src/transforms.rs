@@ -0,0 +1,505 @@
+use crate::{parse_min_frequency, parse_usize_param, Dictionary, QueryLimits, WorkBudget};
+use fst::Streamer;
+use memmap::Mmap;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+};
+
+pub const DEFAULT_MIN_HIDDEN_LENGTH: usize = 3;
+// Only this many pairs are returned from a query.
+const MAX_PAIRS_RETURNED: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Deleting one letter leaves a word.
+    DeleteLetter,
+    /// Inserting one letter makes a word.
+    InsertLetter,
+    /// Changing one letter makes a word.
+    ChangeLetter,
+    /// A shorter word appears inside the word.
+    HiddenWord,
+    /// The word backwards is a different word (a semordnilap).
+    Reversal,
+    /// Both words use exactly the same set of letters, like `her` and `here`.
+    LetterBank,
+}
+
+impl TryFrom<&str> for Transform {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "delete" => Ok(Transform::DeleteLetter),
+            "insert" => Ok(Transform::InsertLetter),
+            "change" => Ok(Transform::ChangeLetter),
+            "hidden" => Ok(Transform::HiddenWord),
+            "reversal" => Ok(Transform::Reversal),
+            "letterbank" => Ok(Transform::LetterBank),
+            _ => Err(format!("Unrecognized transform {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransformPair {
+    pub word: String,
+    pub frequency: u64,
+    /// What `word` turns into.
+    pub result: String,
+    pub result_frequency: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransformResults {
+    /// Sorted by the frequency of the rarer word in each pair, descending.
+    pub pairs: Vec<TransformPair>,
+    pub truncated: bool,
+}
+
+struct Transformer<'a> {
+    map: &'a fst::Map<Mmap>,
+    min_frequency: u64,
+    min_hidden_length: usize,
+    budget: WorkBudget<'a>,
+    truncated: bool,
+}
+
+impl<'a> Transformer<'a> {
+    /// The frequency of `word` if it's in the dictionary and common enough.
+    fn lookup(&mut self, word: &str) -> Option<u64> {
+        if !self.budget.charge() {
+            self.truncated = true;
+            return None;
+        }
+        self.map.get(word).filter(|f| *f >= self.min_frequency)
+    }
+
+    /// The words one letter different from `word` - `deletions` are one
+    /// letter shorter, and otherwise they're the same length.
+    fn one_letter_away(&mut self, word: &str, deletions: bool) -> Vec<(String, u64)> {
+        let mut results = vec![];
+        for i in 0..word.len() {
+            if deletions {
+                let candidate = format!("{}{}", &word[..i], &word[i + 1..]);
+                if let Some(frequency) = self.lookup(&candidate) {
+                    results.push((candidate, frequency));
+                }
+                continue;
+            }
+            for letter in b'a'..=b'z' {
+                if letter == word.as_bytes()[i] {
+                    continue;
+                }
+                let candidate = format!("{}{}{}", &word[..i], letter as char, &word[i + 1..]);
+                if let Some(frequency) = self.lookup(&candidate) {
+                    results.push((candidate, frequency));
+                }
+            }
+        }
+        results
+    }
+
+    fn insertions(&mut self, word: &str) -> Vec<(String, u64)> {
+        let mut results = vec![];
+        for i in 0..=word.len() {
+            for letter in b'a'..=b'z' {
+                let candidate = format!("{}{}{}", &word[..i], letter as char, &word[i..]);
+                if let Some(frequency) = self.lookup(&candidate) {
+                    results.push((candidate, frequency));
+                }
+            }
+        }
+        results
+    }
+
+    fn hidden_words(&mut self, word: &str) -> Vec<(String, u64)> {
+        let mut results = vec![];
+        for length in self.min_hidden_length..word.len() {
+            for start in 0..=word.len() - length {
+                let candidate = &word[start..start + length];
+                if let Some(frequency) = self.lookup(candidate) {
+                    results.push((candidate.to_string(), frequency));
+                }
+            }
+        }
+        results
+    }
+
+    fn reversal(&mut self, word: &str) -> Vec<(String, u64)> {
+        let reversed = word.chars().rev().collect::<String>();
+        if reversed == word {
+            return vec![];
+        }
+        self.lookup(&reversed)
+            .map(|frequency| (reversed, frequency))
+            .into_iter()
+            .collect()
+    }
+
+    /// What `word` turns into under `transform`, not counting letter banks
+    /// (which need the whole dictionary).
+    fn apply(&mut self, transform: Transform, word: &str) -> Vec<(String, u64)> {
+        match transform {
+            Transform::DeleteLetter => self.one_letter_away(word, true),
+            Transform::InsertLetter => self.insertions(word),
+            Transform::ChangeLetter => self.one_letter_away(word, false),
+            Transform::HiddenWord => self.hidden_words(word),
+            Transform::Reversal => self.reversal(word),
+            Transform::LetterBank => vec![],
+        }
+    }
+}
+
+fn letter_set(word: &str) -> BTreeSet<char> {
+    word.chars().collect()
+}
+
+/// Finds pairs of words related by `transform`, both with at least
+/// `min_frequency`. If `word` is given, only pairs starting from it are
+/// found (however rare it is); otherwise the whole dictionary is scanned,
+/// and each pair is only listed once (so insertions are found as deletions,
+/// the other way round). The search stops, and the results are truncated,
+/// once `MAX_PAIRS_RETURNED` pairs have been found.
+pub fn find_transformations(
+    dictionary: &Dictionary,
+    transform: Transform,
+    word: Option<&str>,
+    min_frequency: u64,
+    min_hidden_length: usize,
+    limits: &QueryLimits,
+) -> Result<TransformResults, String> {
+    let map = dictionary.fst_map()?;
+    let mut transformer = Transformer {
+        map,
+        min_frequency,
+        min_hidden_length: min_hidden_length.max(1),
+        budget: WorkBudget::new(limits),
+        truncated: false,
+    };
+    let word = word.map(|word| word.to_ascii_lowercase());
+    if word
+        .as_ref()
+        .is_some_and(|word| word.is_empty() || !word.chars().all(|c| c.is_ascii_lowercase()))
+    {
+        return Err("Disallowed characters in word".to_string());
+    }
+    let needs_scan = word.is_none() || transform == Transform::LetterBank;
+    let mut words = vec![];
+    if needs_scan {
+        let mut stream = map.stream();
+        while let Some((key, frequency)) = stream.next() {
+            if !transformer.budget.charge() {
+                transformer.truncated = true;
+                break;
+            }
+            if frequency >= min_frequency {
+                if let Ok(key) = std::str::from_utf8(key) {
+                    words.push((key.to_string(), frequency));
+                }
+            }
+        }
+    }
+
+    let mut pairs = vec![];
+    // returns whether there's room for more pairs
+    let mut push = |(word, frequency): (&str, u64), (result, result_frequency): (String, u64)| {
+        pairs.push(TransformPair {
+            word: word.to_string(),
+            frequency,
+            result,
+            result_frequency,
+        });
+        pairs.len() < MAX_PAIRS_RETURNED
+    };
+    let mut full = false;
+    match (&word, transform) {
+        (Some(word), Transform::LetterBank) => {
+            let frequency = map.get(word).unwrap_or(0);
+            let letters = letter_set(word);
+            for (other, other_frequency) in words {
+                if &other != word
+                    && letter_set(&other) == letters
+                    && !push((word.as_str(), frequency), (other, other_frequency))
+                {
+                    full = true;
+                    break;
+                }
+            }
+        }
+        (Some(word), _) => {
+            let frequency = map.get(word).unwrap_or(0);
+            let mut seen = HashSet::new();
+            for result in transformer.apply(transform, word) {
+                if seen.insert(result.0.clone()) && !push((word.as_str(), frequency), result) {
+                    full = true;
+                    break;
+                }
+            }
+        }
+        (None, Transform::LetterBank) => {
+            let mut groups: HashMap<BTreeSet<char>, Vec<(String, u64)>> = HashMap::new();
+            for (word, frequency) in words {
+                if !transformer.budget.charge() {
+                    transformer.truncated = true;
+                    break;
+                }
+                groups
+                    .entry(letter_set(&word))
+                    .or_default()
+                    .push((word, frequency));
+            }
+            'banks: for group in groups.values() {
+                for a in group {
+                    for b in group {
+                        if !transformer.budget.charge() {
+                            transformer.truncated = true;
+                            break 'banks;
+                        }
+                        if (a.0.len(), &a.0) < (b.0.len(), &b.0)
+                            && !push((a.0.as_str(), a.1), b.clone())
+                        {
+                            full = true;
+                            break 'banks;
+                        }
+                    }
+                }
+            }
+        }
+        (None, Transform::ChangeLetter) => {
+            // words one letter apart share a pattern with a ? in that spot
+            let mut groups: HashMap<String, Vec<(String, u64)>> = HashMap::new();
+            'grouping: for (word, frequency) in &words {
+                for i in 0..word.len() {
+                    if !transformer.budget.charge() {
+                        transformer.truncated = true;
+                        break 'grouping;
+                    }
+                    groups
+                        .entry(format!("{}?{}", &word[..i], &word[i + 1..]))
+                        .or_default()
+                        .push((word.clone(), *frequency));
+                }
+            }
+            let mut seen = HashSet::new();
+            'changes: for group in groups.values() {
+                for a in group {
+                    for b in group {
+                        if !transformer.budget.charge() {
+                            transformer.truncated = true;
+                            break 'changes;
+                        }
+                        if a.0 < b.0
+                            && seen.insert((a.0.clone(), b.0.clone()))
+                            && !push((a.0.as_str(), a.1), b.clone())
+                        {
+                            full = true;
+                            break 'changes;
+                        }
+                    }
+                }
+            }
+        }
+        (None, _) => {
+            let scan_transform = match transform {
+                Transform::InsertLetter => Transform::DeleteLetter,
+                transform => transform,
+            };
+            'words: for (word, frequency) in &words {
+                if transformer.truncated {
+                    break;
+                }
+                let mut seen = HashSet::new();
+                for result in transformer.apply(scan_transform, word) {
+                    if !seen.insert(result.0.clone()) {
+                        continue;
+                    }
+                    let room = match transform {
+                        Transform::InsertLetter => {
+                            push((result.0.as_str(), result.1), (word.clone(), *frequency))
+                        }
+                        // each reversal would be found from both ends
+                        Transform::Reversal if &result.0 < word => true,
+                        _ => push((word.as_str(), *frequency), result),
+                    };
+                    if !room {
+                        full = true;
+                        break 'words;
+                    }
+                }
+            }
+        }
+    }
+    pairs.sort_by(|a, b| {
+        b.frequency
+            .min(b.result_frequency)
+            .cmp(&a.frequency.min(a.result_frequency))
+            .then(a.word.cmp(&b.word))
+            .then(a.result.cmp(&b.result))
+    });
+    Ok(TransformResults {
+        pairs,
+        truncated: transformer.truncated || full,
+    })
+}
+
+/// Handles a query string with mode `Transform`, where `transform` is
+/// `delete`, `insert`, `change`, `hidden`, `reversal` or `letterbank`, and
+/// `pattern` (the word to start from), `min_frequency` and `min_length` (for
+/// hidden words) are optional. If `truncated` is set in the response, the
+/// pairs are only the ones found before the search stopped.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let transform = Transform::try_from(
+        query_parts
+            .get("transform")
+            .ok_or_else(|| String::from("Internal error - no transform specified!"))?
+            .as_str(),
+    )?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let min_length = parse_usize_param(query_parts, "min_length", DEFAULT_MIN_HIDDEN_LENGTH)?;
+    let results = find_transformations(
+        &Dictionary::open()?,
+        transform,
+        query_parts
+            .get("pattern")
+            .map(|word| word.as_str())
+            .filter(|word| !word.is_empty()),
+        min_frequency,
+        min_length,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated && results.pairs.is_empty() {
+        return Err("Query took too long - try a higher frequency threshold".to_string());
+    }
+    Ok(json::object! {
+        "pairs" => json::JsonValue::Array(results
+            .pairs
+            .into_iter()
+            .map(|pair| {
+                json::object! {
+                    "word" => pair.word,
+                    "frequency" => pair.frequency,
+                    "result" => pair.result,
+                    "result_frequency" => pair.result_frequency
+                }
+            })
+            .collect()),
+        "truncated" => results.truncated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(
+        transform: Transform,
+        word: Option<&str>,
+        min_frequency: u64,
+    ) -> Vec<(String, String)> {
+        let results = find_transformations(
+            &Dictionary::open().unwrap(),
+            transform,
+            word,
+            min_frequency,
+            DEFAULT_MIN_HIDDEN_LENGTH,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert!(!results.truncated);
+        results
+            .pairs
+            .into_iter()
+            .map(|pair| (pair.word, pair.result))
+            .collect()
+    }
+
+    fn pair(word: &str, result: &str) -> (String, String) {
+        (word.to_string(), result.to_string())
+    }
+
+    #[test]
+    fn test_one_word() {
+        assert!(results(Transform::DeleteLetter, Some("there"), 0).contains(&pair("there", "here")));
+        let inserted = results(Transform::InsertLetter, Some("he"), 0);
+        assert!(inserted.contains(&pair("he", "the")));
+        assert!(inserted.contains(&pair("he", "she")));
+        let changed = results(Transform::ChangeLetter, Some("the"), 0);
+        assert!(changed.contains(&pair("the", "tie")));
+        assert!(!changed.contains(&pair("the", "the")));
+        let hidden = results(Transform::HiddenWord, Some("there"), 0);
+        assert!(hidden.contains(&pair("there", "the")));
+        assert!(hidden.contains(&pair("there", "here")));
+        // too short
+        assert!(!hidden.contains(&pair("there", "he")));
+        assert_eq!(
+            vec![pair("on", "no")],
+            results(Transform::Reversal, Some("on"), 0)
+        );
+        assert_eq!(
+            vec![pair("her", "here")],
+            results(Transform::LetterBank, Some("her"), 0)
+        );
+        assert!(find_transformations(
+            &Dictionary::open().unwrap(),
+            Transform::Reversal,
+            Some("o n"),
+            0,
+            DEFAULT_MIN_HIDDEN_LENGTH,
+            &QueryLimits::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_scan() {
+        let reversals = results(Transform::Reversal, None, 0);
+        assert_eq!(vec![pair("no", "on"), pair("saw", "was")], {
+            let mut reversals = reversals;
+            reversals.sort();
+            reversals
+        });
+        let deleted = results(Transform::DeleteLetter, None, 0);
+        assert!(deleted.contains(&pair("there", "here")));
+        let inserted = results(Transform::InsertLetter, None, 0);
+        assert!(inserted.contains(&pair("here", "there")));
+        let changed = results(Transform::ChangeLetter, None, 0);
+        assert!(changed.contains(&pair("she", "the")));
+        assert!(!changed.contains(&pair("the", "she")));
+        let banks = results(Transform::LetterBank, None, 0);
+        assert!(banks.contains(&pair("her", "here")));
+        assert!(banks.contains(&pair("there", "three")));
+    }
+
+    #[test]
+    fn test_frequency_threshold() {
+        let the = Dictionary::open().unwrap().map.unwrap().get("the").unwrap();
+        // only "the" is that common, so there's nothing to pair it with
+        assert!(results(Transform::ChangeLetter, None, the).is_empty());
+        assert!(results(Transform::ChangeLetter, Some("the"), the).is_empty());
+    }
+
+    #[test]
+    fn test_grouping_is_charged() {
+        let dictionary = Dictionary::open().unwrap();
+        let words = dictionary.map.as_ref().unwrap().len();
+        for transform in [Transform::LetterBank, Transform::ChangeLetter] {
+            // enough to read the dictionary, but not to group it
+            let limits = QueryLimits {
+                max_scanned_entries: words + 10,
+                ..QueryLimits::default()
+            };
+            let results = find_transformations(
+                &dictionary,
+                transform,
+                None,
+                0,
+                DEFAULT_MIN_HIDDEN_LENGTH,
+                &limits,
+            )
+            .unwrap();
+            assert!(results.truncated, "{:?}", transform);
+        }
+    }
+}
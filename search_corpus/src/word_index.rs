@@ -0,0 +1,227 @@
+use crate::{find_processed_file, WORD_ALPHABET};
+use memmap::Mmap;
+use std::{convert::TryInto, fs::File};
+
+// See process_corpus/src/word_index.rs for the file format.
+const MAGIC: &[u8; 4] = b"WIDX";
+const VERSION: u32 = 1;
+
+struct Group {
+    length: usize,
+    count: usize,
+    offset: usize,
+}
+
+/// The dictionary grouped by word length, with a bitset for each
+/// (position, character) pair saying which words of that length have that
+/// character at that position. Answering a pattern query is then just a
+/// matter of intersecting bitsets.
+pub struct WordIndex {
+    mmap: Mmap,
+    groups: Vec<Group>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "Word index is truncated".to_string())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "Word index is truncated".to_string())
+}
+
+impl WordIndex {
+    pub fn open() -> Result<WordIndex, String> {
+        let mmap = unsafe {
+            Mmap::map(
+                &File::open(find_processed_file("word_index.bin")).map_err(|e| e.to_string())?,
+            )
+            .map_err(|e| e.to_string())?
+        };
+        if mmap.get(0..4) != Some(&MAGIC[..]) || read_u32(&mmap, 4)? != VERSION {
+            return Err("Word index has the wrong format".to_string());
+        }
+        let alphabet_len = read_u32(&mmap, 8)? as usize;
+        if mmap.get(12..12 + alphabet_len) != Some(WORD_ALPHABET) {
+            return Err("Word index has the wrong alphabet".to_string());
+        }
+        let mut offset = 12 + alphabet_len;
+        let group_count = read_u32(&mmap, offset)? as usize;
+        offset += 4;
+        let mut groups = Vec::with_capacity(group_count);
+        for _ in 0..group_count {
+            let group = Group {
+                length: read_u32(&mmap, offset)? as usize,
+                count: read_u32(&mmap, offset + 4)? as usize,
+                offset: read_u64(&mmap, offset + 8)? as usize,
+            };
+            let blocks = group.count.div_ceil(64);
+            let end = group.offset
+                + group.count * (group.length + 8)
+                + group.length * WORD_ALPHABET.len() * blocks * 8;
+            if end > mmap.len() {
+                return Err("Word index is truncated".to_string());
+            }
+            groups.push(group);
+            offset += 16;
+        }
+        Ok(WordIndex { mmap, groups })
+    }
+
+    /// The number of words in the index.
+    pub fn total_words(&self) -> usize {
+        self.groups.iter().map(|group| group.count).sum()
+    }
+
+    /// The number of words of the given length.
+    pub fn word_count(&self, length: usize) -> usize {
+        self.group(length).map_or(0, |group| group.count)
+    }
+
+    fn group(&self, length: usize) -> Option<&Group> {
+        self.groups.iter().find(|group| group.length == length)
+    }
+
+    /// Returns the words whose character at each position is in the
+    /// corresponding mask (see `position_masks()`), in descending
+    /// frequency order.
+    pub fn matching_words<'a>(&'a self, masks: &[u32]) -> MatchingWords<'a> {
+        let group = match self.group(masks.len()) {
+            Some(group) => group,
+            None => {
+                return MatchingWords {
+                    index: self,
+                    group: None,
+                    candidates: vec![],
+                    block: 0,
+                }
+            }
+        };
+        let blocks = group.count.div_ceil(64);
+        let mut candidates = vec![u64::MAX; blocks];
+        if group.count % 64 != 0 {
+            candidates[blocks - 1] = (1 << (group.count % 64)) - 1;
+        }
+        let all_characters = (1u32 << WORD_ALPHABET.len()) - 1;
+        for (position, mask) in masks.iter().enumerate() {
+            let mask = mask & all_characters;
+            if mask == all_characters {
+                continue;
+            }
+            // Every word has exactly one character at each position, so if
+            // most characters are allowed it's cheaper to remove the words
+            // with the disallowed ones.
+            let invert = mask.count_ones() as usize > WORD_ALPHABET.len() / 2;
+            let lookup_mask = if invert { !mask & all_characters } else { mask };
+            let mut position_matches = vec![0u64; blocks];
+            for character in 0..WORD_ALPHABET.len() {
+                if lookup_mask & (1 << character) != 0 {
+                    let bitset = self.bitset(group, position, character);
+                    for (i, matches) in position_matches.iter_mut().enumerate() {
+                        *matches |= read_u64(bitset, i * 8).unwrap();
+                    }
+                }
+            }
+            for (candidate, matches) in candidates.iter_mut().zip(position_matches) {
+                *candidate &= if invert { !matches } else { matches };
+            }
+        }
+        MatchingWords {
+            index: self,
+            group: Some(group),
+            candidates,
+            block: 0,
+        }
+    }
+
+    fn bitset(&self, group: &Group, position: usize, character: usize) -> &[u8] {
+        let blocks = group.count.div_ceil(64);
+        let start = group.offset
+            + group.count * (group.length + 8)
+            + (position * WORD_ALPHABET.len() + character) * blocks * 8;
+        &self.mmap[start..start + blocks * 8]
+    }
+}
+
+/// Iterator over the results of `WordIndex::matching_words()`.
+pub struct MatchingWords<'a> {
+    index: &'a WordIndex,
+    group: Option<&'a Group>,
+    candidates: Vec<u64>,
+    block: usize,
+}
+
+impl<'a> Iterator for MatchingWords<'a> {
+    type Item = (&'a [u8], u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let group = self.group?;
+        while self.block < self.candidates.len() {
+            let bits = self.candidates[self.block];
+            if bits == 0 {
+                self.block += 1;
+                continue;
+            }
+            let bit = bits.trailing_zeros() as usize;
+            self.candidates[self.block] &= bits - 1;
+            let i = self.block * 64 + bit;
+            let word_start = group.offset + i * group.length;
+            let word = &self.index.mmap[word_start..word_start + group.length];
+            let frequency = read_u64(
+                &self.index.mmap,
+                group.offset + group.count * group.length + i * 8,
+            )
+            .unwrap();
+            return Some((word, frequency));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(characters: &str) -> u32 {
+        characters
+            .bytes()
+            .map(|c| 1 << WORD_ALPHABET.iter().position(|a| *a == c).unwrap())
+            .fold(0, |acc, bit| acc | bit)
+    }
+
+    #[test]
+    fn test_matching_words_fixed_letters() {
+        let index = WordIndex::open().unwrap();
+        let words = index
+            .matching_words(&[mask("t"), mask("h"), mask("e")])
+            .collect::<Vec<_>>();
+        assert_eq!(1, words.len());
+        assert_eq!(b"the", words[0].0);
+    }
+
+    #[test]
+    fn test_matching_words_in_frequency_order() {
+        let index = WordIndex::open().unwrap();
+        let any_letter = mask("abcdefghijklmnopqrstuvwxyz");
+        let words = index
+            .matching_words(&[mask("t"), any_letter, any_letter, any_letter])
+            .collect::<Vec<_>>();
+        assert!(words.len() > 3);
+        assert_eq!(b"that", words[0].0);
+        for pair in words.windows(2) {
+            assert!(pair[0].1 >= pair[1].1);
+        }
+    }
+
+    #[test]
+    fn test_matching_words_no_words_of_length() {
+        let index = WordIndex::open().unwrap();
+        assert_eq!(0, index.word_count(100));
+        assert_eq!(0, index.matching_words(&[mask("a"); 100]).count());
+    }
+}
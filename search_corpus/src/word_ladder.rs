@@ -0,0 +1,302 @@
+use crate::{parse_min_frequency, Dictionary, PatternMode, QueryLimits, WorkBudget};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// The words one letter change away from `word` with at least
+/// `min_frequency`, found by searching for `word` with each letter in turn
+/// replaced by a Crossword-mode `?`.
+pub fn neighbors(
+    dictionary: &Dictionary,
+    word: &str,
+    min_frequency: u64,
+    limits: &QueryLimits,
+) -> Result<Vec<(String, u64)>, String> {
+    let (result, truncated) = neighbors_with_budget(
+        dictionary,
+        word,
+        min_frequency,
+        &mut WorkBudget::new(limits),
+    )?;
+    if truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(result)
+}
+
+/// Like `neighbors()`, but charges the searches to `budget`. The second
+/// value is true if it ran out, in which case the neighbors are incomplete.
+fn neighbors_with_budget(
+    dictionary: &Dictionary,
+    word: &str,
+    min_frequency: u64,
+    budget: &mut WorkBudget,
+) -> Result<(Vec<(String, u64)>, bool), String> {
+    let mut result = vec![];
+    for i in 0..word.len() {
+        let pattern = format!("{}?{}", &word[..i], &word[i + 1..]);
+        let results =
+            dictionary.search_with_budget(PatternMode::Crossword, &pattern, "", budget)?;
+        result.extend(
+            results
+                .words
+                .into_iter()
+                .filter(|(neighbor, frequency)| neighbor != word && *frequency >= min_frequency),
+        );
+        if results.truncated {
+            return Ok((result, true));
+        }
+    }
+    Ok((result, false))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LadderResult {
+    /// The words from start to end, or `None` if there's no chain.
+    pub chain: Option<Vec<(String, u64)>>,
+    /// Whether the search gave up before finding a chain.
+    pub truncated: bool,
+}
+
+/// How much a word counts against a chain when preferring common words -
+/// the number of bits less frequent it is than the most common word.
+fn rarity(frequency: u64) -> u64 {
+    ((64.0 - ((frequency + 1) as f64).log2()) * 1000.0) as u64
+}
+
+/// The best way found so far of getting to a word.
+struct Visit {
+    /// The length of the chain, and how rare its words are.
+    cost: (usize, u64),
+    previous: Option<String>,
+    frequency: u64,
+}
+
+/// Finds the shortest chain of words from `start` to `end` changing one
+/// letter at a time, using only words with at least `min_frequency` in
+/// between. If `prefer_common` is set, ties between chains of the same
+/// length go to the one with more common words.
+pub fn find_ladder(
+    dictionary: &Dictionary,
+    start: &str,
+    end: &str,
+    min_frequency: u64,
+    prefer_common: bool,
+    limits: &QueryLimits,
+) -> Result<LadderResult, String> {
+    let start = start.to_ascii_lowercase();
+    let end = end.to_ascii_lowercase();
+    if start.len() != end.len() {
+        return Err("Start and end words must be the same length".to_string());
+    }
+    if start.is_empty()
+        || !start
+            .chars()
+            .chain(end.chars())
+            .all(|c| c.is_ascii_lowercase())
+    {
+        return Err("Disallowed characters in words".to_string());
+    }
+    // every search along the way shares one budget
+    let mut budget = WorkBudget::new(limits);
+    // Dijkstra's algorithm, where the cost of a chain is its length and then
+    // (if preferring common words) how rare its words are.
+    let start_frequency = dictionary
+        .search_with_budget(PatternMode::Crossword, &start, "", &mut budget)?
+        .words
+        .first()
+        .map_or(0, |w| w.1);
+    let mut best: HashMap<String, Visit> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    best.insert(
+        start.clone(),
+        Visit {
+            cost: (0, 0),
+            previous: None,
+            frequency: start_frequency,
+        },
+    );
+    queue.push(Reverse(((0usize, 0u64), start.clone())));
+    while let Some(Reverse((cost, word))) = queue.pop() {
+        if best[&word].cost < cost {
+            continue;
+        }
+        if word == end {
+            let mut chain = vec![];
+            let mut current = Some(word);
+            while let Some(word) = current {
+                let visit = &best[&word];
+                current = visit.previous.clone();
+                chain.push((word, visit.frequency));
+            }
+            chain.reverse();
+            return Ok(LadderResult {
+                chain: Some(chain),
+                truncated: false,
+            });
+        }
+        let (word_neighbors, truncated) = neighbors_with_budget(dictionary, &word, 0, &mut budget)?;
+        if truncated {
+            return Ok(LadderResult {
+                chain: None,
+                truncated: true,
+            });
+        }
+        for (neighbor, frequency) in word_neighbors {
+            if frequency < min_frequency && neighbor != end {
+                continue;
+            }
+            let neighbor_cost = (
+                cost.0 + 1,
+                if prefer_common {
+                    cost.1 + rarity(frequency)
+                } else {
+                    0
+                },
+            );
+            if best
+                .get(&neighbor)
+                .is_none_or(|existing| neighbor_cost < existing.cost)
+            {
+                best.insert(
+                    neighbor.clone(),
+                    Visit {
+                        cost: neighbor_cost,
+                        previous: Some(word.clone()),
+                        frequency,
+                    },
+                );
+                queue.push(Reverse((neighbor_cost, neighbor)));
+            }
+        }
+    }
+    Ok(LadderResult::default())
+}
+
+/// Handles a query string with mode `WordLadder`, with `start` and `end`
+/// words and optional `min_frequency` and `prefer_common`.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let start = query_parts
+        .get("start")
+        .ok_or_else(|| String::from("Internal error - no start word specified!"))?;
+    let end = query_parts
+        .get("end")
+        .ok_or_else(|| String::from("Internal error - no end word specified!"))?;
+    let min_frequency = parse_min_frequency(query_parts, 0)?;
+    let prefer_common = query_parts
+        .get("prefer_common")
+        .is_some_and(|value| value == "1" || value == "true");
+    let result = find_ladder(
+        &Dictionary::open()?,
+        start,
+        end,
+        min_frequency,
+        prefer_common,
+        &QueryLimits::default(),
+    )?;
+    if result.truncated {
+        return Err("Query took too long".to_string());
+    }
+    let chain = result
+        .chain
+        .ok_or_else(|| "No chain found - try a lower frequency threshold".to_string())?;
+    Ok(json::JsonValue::Array(
+        chain
+            .into_iter()
+            .map(|(word, frequency)| json::object! { "word" => word, "frequency" => frequency })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_ladder(chain: &[(String, u64)]) -> bool {
+        chain.windows(2).all(|pair| {
+            pair[0].0.len() == pair[1].0.len()
+                && pair[0]
+                    .0
+                    .chars()
+                    .zip(pair[1].0.chars())
+                    .filter(|(a, b)| a != b)
+                    .count()
+                    == 1
+        })
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let dictionary = Dictionary::open().unwrap();
+        let words = neighbors(&dictionary, "the", 0, &QueryLimits::default()).unwrap();
+        let words = words.iter().map(|w| w.0.as_str()).collect::<Vec<_>>();
+        assert!(words.contains(&"she"));
+        assert!(words.contains(&"tie"));
+        assert!(!words.contains(&"the"));
+    }
+
+    #[test]
+    fn test_find_ladder() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        let result = find_ladder(&dictionary, "the", "she", 0, false, &limits).unwrap();
+        let chain = result.chain.unwrap();
+        assert_eq!(
+            vec!["the", "she"],
+            chain.iter().map(|w| w.0.as_str()).collect::<Vec<_>>()
+        );
+        assert!(chain.iter().all(|w| w.1 > 0));
+        let result = find_ladder(&dictionary, "tie", "she", 0, true, &limits).unwrap();
+        let chain = result.chain.unwrap();
+        assert_eq!(3, chain.len());
+        assert!(is_ladder(&chain));
+    }
+
+    #[test]
+    fn test_find_ladder_same_word() {
+        let dictionary = Dictionary::open().unwrap();
+        let result =
+            find_ladder(&dictionary, "the", "the", 0, false, &QueryLimits::default()).unwrap();
+        assert_eq!(1, result.chain.unwrap().len());
+    }
+
+    #[test]
+    fn test_find_ladder_frequency_threshold() {
+        let dictionary = Dictionary::open().unwrap();
+        // nothing in between is common enough
+        let result = find_ladder(
+            &dictionary,
+            "tie",
+            "she",
+            u64::MAX,
+            false,
+            &QueryLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(None, result.chain);
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn test_find_ladder_budget() {
+        let dictionary = Dictionary::open().unwrap();
+        // enough for a few searches, but not for all of them together
+        let limits = QueryLimits {
+            max_scanned_entries: 10,
+            ..QueryLimits::default()
+        };
+        let result = find_ladder(&dictionary, "tie", "she", 0, false, &limits).unwrap();
+        assert_eq!(None, result.chain);
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn test_find_ladder_invalid() {
+        let dictionary = Dictionary::open().unwrap();
+        let limits = QueryLimits::default();
+        assert!(find_ladder(&dictionary, "the", "tree", 0, false, &limits).is_err());
+        assert!(find_ladder(&dictionary, "", "", 0, false, &limits).is_err());
+        assert!(find_ladder(&dictionary, "t?e", "she", 0, false, &limits).is_err());
+    }
+}
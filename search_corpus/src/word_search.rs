@@ -0,0 +1,740 @@
+use crate::{
+    fst_walk::FstCursor, parse_min_frequency, parse_usize_param, Dictionary, QueryLimits,
+    WorkBudget,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_MIN_WORD_LENGTH: usize = 4;
+pub const DEFAULT_GRID_SIZE: usize = 12;
+// Filler that happens to spell a word at least this long gets replaced.
+pub const DEFAULT_MIN_ACCIDENTAL_LENGTH: usize = 5;
+const MAX_GRID_SIZE: usize = 30;
+// How many times to start over placing the words before giving up.
+const MAX_ATTEMPTS: usize = 100;
+// How many times to redo the filler for one placement of the words.
+const MAX_REFILLS: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Right,
+    Left,
+    Down,
+    Up,
+    DownRight,
+    DownLeft,
+    UpRight,
+    UpLeft,
+}
+
+pub const ALL_DIRECTIONS: [Direction; 8] = [
+    Direction::Right,
+    Direction::Left,
+    Direction::Down,
+    Direction::Up,
+    Direction::DownRight,
+    Direction::DownLeft,
+    Direction::UpRight,
+    Direction::UpLeft,
+];
+
+impl Direction {
+    /// The change in (row, column) for each step.
+    fn offset(&self) -> (isize, isize) {
+        match self {
+            Direction::Right => (0, 1),
+            Direction::Left => (0, -1),
+            Direction::Down => (1, 0),
+            Direction::Up => (-1, 0),
+            Direction::DownRight => (1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::UpLeft => (-1, -1),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Right => "right",
+            Direction::Left => "left",
+            Direction::Down => "down",
+            Direction::Up => "up",
+            Direction::DownRight => "down_right",
+            Direction::DownLeft => "down_left",
+            Direction::UpRight => "up_right",
+            Direction::UpLeft => "up_left",
+        }
+    }
+}
+
+/// A rectangular grid of letters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetterGrid {
+    rows: usize,
+    columns: usize,
+    letters: Vec<u8>,
+}
+
+impl LetterGrid {
+    /// Parses a grid written as rows separated by commas or whitespace, like
+    /// `tree,hxxx,exxx`.
+    pub fn parse(grid: &str) -> Result<LetterGrid, String> {
+        let rows = grid
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|row| !row.is_empty())
+            .map(|row| row.to_ascii_lowercase().into_bytes())
+            .collect::<Vec<_>>();
+        let columns = rows.first().map_or(0, |row| row.len());
+        if rows.is_empty() || rows.len() > MAX_GRID_SIZE || columns > MAX_GRID_SIZE {
+            return Err(format!(
+                "Grid must have between 1 and {} rows and columns",
+                MAX_GRID_SIZE
+            ));
+        }
+        if rows.iter().any(|row| row.len() != columns) {
+            return Err("Grid rows must all be the same length".to_string());
+        }
+        if rows.iter().flatten().any(|c| !c.is_ascii_lowercase()) {
+            return Err("Disallowed characters in grid".to_string());
+        }
+        Ok(LetterGrid {
+            rows: rows.len(),
+            columns,
+            letters: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The grid's rows as strings.
+    pub fn row_strings(&self) -> Vec<String> {
+        self.letters
+            .chunks(self.columns)
+            .map(|row| String::from_utf8(row.to_vec()).unwrap())
+            .collect()
+    }
+
+    /// The cells (as `row * columns + column`) from `start` in `direction`
+    /// to the edge of the grid.
+    fn line(&self, start: usize, direction: Direction) -> Vec<usize> {
+        let (row_step, column_step) = direction.offset();
+        let (mut row, mut column) = (
+            (start / self.columns) as isize,
+            (start % self.columns) as isize,
+        );
+        let mut cells = vec![];
+        while row >= 0 && row < self.rows as isize && column >= 0 && column < self.columns as isize
+        {
+            cells.push(row as usize * self.columns + column as usize);
+            row += row_step;
+            column += column_step;
+        }
+        cells
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridWord {
+    pub word: String,
+    pub frequency: u64,
+    pub row: usize,
+    pub column: usize,
+    pub direction: Direction,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WordSearchResults {
+    /// Sorted by descending frequency, then descending length.
+    pub words: Vec<GridWord>,
+    pub truncated: bool,
+}
+
+/// A word found in the grid, as the cells it uses.
+struct Occurrence {
+    word: String,
+    frequency: u64,
+    start: usize,
+    direction: Direction,
+    cells: Vec<usize>,
+}
+
+/// Finds every dictionary word at least `min_length` letters long along a
+/// straight line in `grid`. The second value is true if `budget` ran out,
+/// in which case only the words found so far are returned.
+fn find_occurrences(
+    cursor: &FstCursor<memmap::Mmap>,
+    grid: &LetterGrid,
+    min_length: usize,
+    budget: &mut WorkBudget,
+) -> (Vec<Occurrence>, bool) {
+    let mut found = vec![];
+    for start in 0..grid.letters.len() {
+        for direction in ALL_DIRECTIONS {
+            let line = grid.line(start, direction);
+            let mut cursor = *cursor;
+            for (i, cell) in line.iter().enumerate() {
+                if !budget.charge() {
+                    return (found, true);
+                }
+                cursor = match cursor.step(&[grid.letters[*cell]]) {
+                    Some(cursor) => cursor,
+                    None => break,
+                };
+                if i + 1 >= min_length {
+                    if let Some(frequency) = cursor.frequency() {
+                        found.push(Occurrence {
+                            word: line[..=i]
+                                .iter()
+                                .map(|c| grid.letters[*c] as char)
+                                .collect(),
+                            frequency,
+                            start,
+                            direction,
+                            cells: line[..=i].to_vec(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    (found, false)
+}
+
+/// Finds every dictionary word at least `min_length` letters long written
+/// in any of the eight directions in `grid`.
+pub fn solve_word_search(
+    dictionary: &Dictionary,
+    grid: &LetterGrid,
+    min_length: usize,
+    limits: &QueryLimits,
+) -> Result<WordSearchResults, String> {
+    let map = dictionary.fst_map()?;
+    let mut budget = WorkBudget::new(limits);
+    let (occurrences, truncated) = find_occurrences(
+        &FstCursor::new(map.as_fst()),
+        grid,
+        min_length.max(1),
+        &mut budget,
+    );
+    let mut words = occurrences
+        .into_iter()
+        .map(|o| GridWord {
+            word: o.word,
+            frequency: o.frequency,
+            row: o.start / grid.columns,
+            column: o.start % grid.columns,
+            direction: o.direction,
+        })
+        .collect::<Vec<_>>();
+    words.sort_by(|a, b| {
+        b.frequency
+            .cmp(&a.frequency)
+            .then(b.word.len().cmp(&a.word.len()))
+            .then(a.word.cmp(&b.word))
+    });
+    Ok(WordSearchResults { words, truncated })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorOptions {
+    pub rows: usize,
+    pub columns: usize,
+    /// Words that must not show up anywhere in the grid, unless they're
+    /// part of a placed word.
+    pub avoid: Vec<String>,
+    /// Dictionary words at least this long that show up in the filler are
+    /// replaced.
+    pub min_accidental_length: usize,
+    /// ...as long as they're at least this common.
+    pub min_accidental_frequency: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            rows: DEFAULT_GRID_SIZE,
+            columns: DEFAULT_GRID_SIZE,
+            avoid: vec![],
+            min_accidental_length: DEFAULT_MIN_ACCIDENTAL_LENGTH,
+            min_accidental_frequency: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlacedWord {
+    pub word: String,
+    pub row: usize,
+    pub column: usize,
+    pub direction: Direction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedGrid {
+    pub grid: LetterGrid,
+    pub placed: Vec<PlacedWord>,
+}
+
+/// Lowercases `words` and checks they're only letters.
+fn clean_words(words: &[String]) -> Result<Vec<String>, String> {
+    words
+        .iter()
+        .map(|word| {
+            let word = word.trim().to_ascii_lowercase();
+            if word.is_empty() || !word.bytes().all(|c| c.is_ascii_lowercase()) {
+                return Err(format!("\"{}\" isn't a word", word));
+            }
+            Ok(word)
+        })
+        .collect()
+}
+
+/// Puts each of `words` (longest first) at a random place and direction
+/// where it only crosses matching letters, or returns `None` if one doesn't
+/// fit.
+fn place_words(
+    words: &[String],
+    grid: &mut LetterGrid,
+    filled: &mut [bool],
+    rng: &mut StdRng,
+) -> Option<Vec<PlacedWord>> {
+    let mut placed = vec![];
+    for word in words {
+        let mut candidates = (0..grid.letters.len())
+            .flat_map(|start| ALL_DIRECTIONS.iter().map(move |d| (start, *d)))
+            .collect::<Vec<_>>();
+        candidates.shuffle(rng);
+        let (start, direction, cells) = candidates.into_iter().find_map(|(start, direction)| {
+            let cells = grid.line(start, direction);
+            if cells.len() < word.len() {
+                return None;
+            }
+            let cells = cells[..word.len()].to_vec();
+            let fits = cells
+                .iter()
+                .zip(word.bytes())
+                .all(|(cell, letter)| !filled[*cell] || grid.letters[*cell] == letter);
+            // running entirely over other words would hide it
+            let adds_letters = cells.iter().any(|cell| !filled[*cell]);
+            (fits && adds_letters).then_some((start, direction, cells))
+        })?;
+        for (cell, letter) in cells.iter().zip(word.bytes()) {
+            grid.letters[*cell] = letter;
+            filled[*cell] = true;
+        }
+        placed.push(PlacedWord {
+            word: word.clone(),
+            row: start / grid.columns,
+            column: start % grid.columns,
+            direction,
+        });
+    }
+    Some(placed)
+}
+
+/// Makes a word search grid containing `words`, with random letters in the
+/// rest of the cells. Filler that spells a word from `options.avoid`, or a
+/// long enough dictionary word, is redone so the grid doesn't hide any
+/// words it shouldn't.
+pub fn generate_word_search(
+    dictionary: &Dictionary,
+    words: &[String],
+    options: &GeneratorOptions,
+    seed: u64,
+    limits: &QueryLimits,
+) -> Result<GeneratedGrid, String> {
+    let map = dictionary.fst_map()?;
+    if !(1..=MAX_GRID_SIZE).contains(&options.rows)
+        || !(1..=MAX_GRID_SIZE).contains(&options.columns)
+    {
+        return Err(format!(
+            "Grid must have between 1 and {} rows and columns",
+            MAX_GRID_SIZE
+        ));
+    }
+    let mut words = clean_words(words)?;
+    if words.is_empty() {
+        return Err("No words to place".to_string());
+    }
+    if let Some(word) = words
+        .iter()
+        .find(|w| w.len() > options.rows.max(options.columns))
+    {
+        return Err(format!("{} doesn't fit in the grid", word));
+    }
+    words.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    words.dedup();
+    let avoid = clean_words(&options.avoid)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let longest_avoided = avoid.iter().map(|w| w.len()).max().unwrap_or(0);
+    let cursor = FstCursor::new(map.as_fst());
+    let mut budget = WorkBudget::new(limits);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for _ in 0..MAX_ATTEMPTS {
+        let mut grid = LetterGrid {
+            rows: options.rows,
+            columns: options.columns,
+            letters: vec![b'a'; options.rows * options.columns],
+        };
+        let mut filled = vec![false; grid.letters.len()];
+        let placed = match place_words(&words, &mut grid, &mut filled, &mut rng) {
+            Some(placed) => placed,
+            None => continue,
+        };
+        let filler = (0..grid.letters.len())
+            .filter(|cell| !filled[*cell])
+            .collect::<Vec<_>>();
+        for cell in &filler {
+            grid.letters[*cell] = rng.gen_range(b'a'..=b'z');
+        }
+        for _ in 0..MAX_REFILLS {
+            let (occurrences, truncated) = find_occurrences(
+                &cursor,
+                &grid,
+                options.min_accidental_length.max(1),
+                &mut budget,
+            );
+            // without every occurrence, there's no telling whether the grid
+            // hides a word
+            if truncated {
+                return Err("Query took too long".to_string());
+            }
+            let mut accidental = occurrences
+                .into_iter()
+                .filter(|o| o.frequency >= options.min_accidental_frequency)
+                .map(|o| o.cells)
+                .collect::<Vec<_>>();
+            // avoided words might not be in the dictionary, so look for them
+            // directly
+            for start in 0..grid.letters.len() {
+                for direction in ALL_DIRECTIONS {
+                    let line = grid.line(start, direction);
+                    for length in 1..=line.len().min(longest_avoided) {
+                        let cells = &line[..length];
+                        let word = cells
+                            .iter()
+                            .map(|c| grid.letters[*c] as char)
+                            .collect::<String>();
+                        if avoid.contains(&word) {
+                            accidental.push(cells.to_vec());
+                        }
+                    }
+                }
+            }
+            let accidental = accidental
+                .into_iter()
+                .filter(|cells| {
+                    // words made only of placed letters can't be fixed by
+                    // changing the filler, so only avoided ones matter
+                    cells.iter().any(|c| !filled[*c])
+                        || avoid.contains(
+                            &cells
+                                .iter()
+                                .map(|c| grid.letters[*c] as char)
+                                .collect::<String>(),
+                        )
+                })
+                .collect::<Vec<_>>();
+            if accidental.is_empty() {
+                return Ok(GeneratedGrid { grid, placed });
+            }
+            if accidental
+                .iter()
+                .any(|cells| cells.iter().all(|c| filled[*c]))
+            {
+                break;
+            }
+            for cell in accidental.into_iter().flatten() {
+                if !filled[cell] {
+                    grid.letters[cell] = rng.gen_range(b'a'..=b'z');
+                }
+            }
+        }
+    }
+    Err("Couldn't make a grid without accidental words - try a bigger grid".to_string())
+}
+
+fn grid_word_to_json(
+    word: String,
+    row: usize,
+    column: usize,
+    direction: Direction,
+) -> json::JsonValue {
+    json::object! {
+        "word" => word,
+        "row" => row,
+        "column" => column,
+        "direction" => direction.name()
+    }
+}
+
+/// Handles a query string with mode `WordSearch`, where `pattern` is the
+/// grid (see `LetterGrid::parse()`) and `min_length` is optional.
+pub fn process_query(query_parts: &HashMap<String, String>) -> Result<json::JsonValue, String> {
+    let grid = LetterGrid::parse(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    )?;
+    let min_length = parse_usize_param(query_parts, "min_length", DEFAULT_MIN_WORD_LENGTH)?;
+    let results = solve_word_search(
+        &Dictionary::open()?,
+        &grid,
+        min_length,
+        &QueryLimits::default(),
+    )?;
+    if results.truncated {
+        return Err("Query took too long".to_string());
+    }
+    Ok(json::JsonValue::Array(
+        results
+            .words
+            .into_iter()
+            .map(|w| {
+                let mut entry = grid_word_to_json(w.word, w.row, w.column, w.direction);
+                entry["frequency"] = w.frequency.into();
+                entry
+            })
+            .collect(),
+    ))
+}
+
+/// Handles a query string with mode `WordSearchGenerator`, where `pattern`
+/// is the comma-separated words to place, and `rows`, `columns`, `avoid`
+/// (comma-separated), `min_length` (of accidental words), `min_frequency`
+/// (of accidental words) and `seed` are optional.
+pub fn process_generator_query(
+    query_parts: &HashMap<String, String>,
+) -> Result<json::JsonValue, String> {
+    let split_words = |words: &str| {
+        words
+            .split(',')
+            .filter(|w| !w.trim().is_empty())
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+    };
+    let words = split_words(
+        query_parts
+            .get("pattern")
+            .ok_or_else(|| String::from("Internal error - no pattern specified!"))?,
+    );
+    let options = GeneratorOptions {
+        rows: parse_usize_param(query_parts, "rows", DEFAULT_GRID_SIZE)?,
+        columns: parse_usize_param(query_parts, "columns", DEFAULT_GRID_SIZE)?,
+        avoid: query_parts
+            .get("avoid")
+            .map(|avoid| split_words(avoid))
+            .unwrap_or_default(),
+        min_accidental_length: parse_usize_param(
+            query_parts,
+            "min_length",
+            DEFAULT_MIN_ACCIDENTAL_LENGTH,
+        )?,
+        min_accidental_frequency: parse_min_frequency(query_parts, 0)?,
+    };
+    // pick a seed if there isn't one, but return it so the grid can be made
+    // again
+    let seed = match query_parts.get("seed") {
+        Some(seed) => seed.parse::<u64>().map_err(|e| e.to_string())?,
+        None => rand::random(),
+    };
+    let generated = generate_word_search(
+        &Dictionary::open()?,
+        &words,
+        &options,
+        seed,
+        &QueryLimits::default(),
+    )?;
+    Ok(json::object! {
+        "grid" => generated.grid.row_strings(),
+        "words" => json::JsonValue::Array(generated
+            .placed
+            .into_iter()
+            .map(|p| grid_word_to_json(p.word, p.row, p.column, p.direction))
+            .collect()),
+        "seed" => seed.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letters_at(grid: &LetterGrid, word: &PlacedWord) -> String {
+        grid.line(word.row * grid.columns() + word.column, word.direction)[..word.word.len()]
+            .iter()
+            .map(|c| grid.letters[*c] as char)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = LetterGrid::parse("TREE,hxxx\nexxx").unwrap();
+        assert_eq!((3, 4), (grid.rows(), grid.columns()));
+        assert_eq!(vec!["tree", "hxxx", "exxx"], grid.row_strings());
+        assert!(LetterGrid::parse("tree,hx").is_err());
+        assert!(LetterGrid::parse("tr3e").is_err());
+        assert!(LetterGrid::parse("").is_err());
+    }
+
+    #[test]
+    fn test_line() {
+        let grid = LetterGrid::parse("abc,def,ghi").unwrap();
+        assert_eq!(vec![0, 4, 8], grid.line(0, Direction::DownRight));
+        assert_eq!(vec![5, 4, 3], grid.line(5, Direction::Left));
+        assert_eq!(vec![6, 4, 2], grid.line(6, Direction::UpRight));
+        assert_eq!(vec![2], grid.line(2, Direction::Right));
+    }
+
+    #[test]
+    fn test_solve_word_search() {
+        let dictionary = Dictionary::open().unwrap();
+        // t r e e
+        // h x x x
+        // e x x x
+        let grid = LetterGrid::parse("tree,hxxx,exxx").unwrap();
+        let results = solve_word_search(&dictionary, &grid, 3, &QueryLimits::default()).unwrap();
+        assert!(!results.truncated);
+        let tree = results.words.iter().find(|w| w.word == "tree").unwrap();
+        assert_eq!(
+            (0, 0, Direction::Right),
+            (tree.row, tree.column, tree.direction)
+        );
+        let the = results.words.iter().find(|w| w.word == "the").unwrap();
+        assert_eq!(
+            (0, 0, Direction::Down),
+            (the.row, the.column, the.direction)
+        );
+        assert!(results.words.iter().all(|w| w.word.len() >= 3));
+        // "he" is a word, but too short
+        assert!(!results.words.iter().any(|w| w.word == "he"));
+    }
+
+    #[test]
+    fn test_solve_word_search_truncated() {
+        let dictionary = Dictionary::open().unwrap();
+        let grid = LetterGrid::parse("tree,hxxx,exxx").unwrap();
+        // enough to go right, left and down from the first cell
+        let limits = QueryLimits {
+            max_scanned_entries: 10,
+            ..QueryLimits::default()
+        };
+        let results = solve_word_search(&dictionary, &grid, 3, &limits).unwrap();
+        assert!(results.truncated);
+        // what was found before running out is kept
+        let mut words = results
+            .words
+            .iter()
+            .map(|w| w.word.as_str())
+            .collect::<Vec<_>>();
+        words.sort_unstable();
+        assert_eq!(vec!["the", "tree"], words);
+    }
+
+    #[test]
+    fn test_generate_word_search_truncated() {
+        let dictionary = Dictionary::open().unwrap();
+        let words = vec!["tree".to_string()];
+        let limits = QueryLimits {
+            max_scanned_entries: 10,
+            ..QueryLimits::default()
+        };
+        assert!(generate_word_search(
+            &dictionary,
+            &words,
+            &GeneratorOptions::default(),
+            0,
+            &limits
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_generate_word_search() {
+        let dictionary = Dictionary::open().unwrap();
+        let words = vec!["there".to_string(), "House".to_string(), "tree".to_string()];
+        let options = GeneratorOptions {
+            rows: 6,
+            columns: 6,
+            ..Default::default()
+        };
+        let generated =
+            generate_word_search(&dictionary, &words, &options, 0, &QueryLimits::default())
+                .unwrap();
+        assert_eq!(3, generated.placed.len());
+        for placed in &generated.placed {
+            assert_eq!(placed.word, letters_at(&generated.grid, placed));
+        }
+        // longest first
+        assert_eq!("house", generated.placed[0].word);
+        assert_eq!("there", generated.placed[1].word);
+        assert_eq!("tree", generated.placed[2].word);
+        // the same seed makes the same grid
+        assert_eq!(
+            generated,
+            generate_word_search(&dictionary, &words, &options, 0, &QueryLimits::default())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_avoids_words() {
+        let dictionary = Dictionary::open().unwrap();
+        let words = vec!["tree".to_string()];
+        let options = GeneratorOptions {
+            rows: 5,
+            columns: 5,
+            avoid: vec!["he".to_string(), "no".to_string()],
+            min_accidental_length: 3,
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let generated =
+                generate_word_search(&dictionary, &words, &options, seed, &QueryLimits::default())
+                    .unwrap();
+            let results =
+                solve_word_search(&dictionary, &generated.grid, 2, &QueryLimits::default())
+                    .unwrap();
+            assert!(!results
+                .words
+                .iter()
+                .any(|w| w.word == "he" || w.word == "no"));
+            // only "tree" and anything inside it
+            assert!(results
+                .words
+                .iter()
+                .all(|w| w.word.len() < 3 || "tree".contains(&w.word)));
+        }
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        let dictionary = Dictionary::open().unwrap();
+        let generate = |words: &[&str], options: &GeneratorOptions| {
+            let words = words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+            generate_word_search(&dictionary, &words, options, 0, &QueryLimits::default())
+        };
+        let small = GeneratorOptions {
+            rows: 3,
+            columns: 3,
+            ..Default::default()
+        };
+        assert!(generate(&["there"], &small).is_err());
+        assert!(generate(&["t3e"], &small).is_err());
+        assert!(generate(&[], &small).is_err());
+        // "the" can't be avoided when it's part of "there"
+        let avoid_the = GeneratorOptions {
+            avoid: vec!["the".to_string()],
+            ..Default::default()
+        };
+        assert!(generate(&["there"], &avoid_the).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::{char_mask, QueryLimits, WorkBudget};
+use std::collections::HashMap;
+
+/// Only this many of the most common candidates are tried as guesses by
+/// `best_guesses()`, since every guess has to be scored against every
+/// possible answer.
+pub const MAX_GUESS_POOL: usize = 500;
+
+const MAX_GUESSES: usize = 20;
+const MAX_LENGTH: usize = 20;
+
+/// What a Wordle-style game says about one letter of a guess.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feedback {
+    /// The letter is in the answer in this position.
+    Green,
+    /// The letter is in the answer, but not in this position.
+    Yellow,
+    /// The letter isn't in the answer (or, if it's repeated in the guess,
+    /// isn't in the answer any more times than it's been marked).
+    Gray,
+}
+
+impl Feedback {
+    /// Feedback is written `g` for green, `y` for yellow and `b` (for
+    /// "blank") for gray.
+    fn from_char(c: char) -> Option<Feedback> {
+        match c {
+            'g' => Some(Feedback::Green),
+            'y' => Some(Feedback::Yellow),
+            'b' => Some(Feedback::Gray),
+            _ => None,
+        }
+    }
+}
+
+/// The feedback a game would give for `guess` if the answer were `answer`.
+/// Both must be the same length.
+pub fn feedback(guess: &str, answer: &str) -> Vec<Feedback> {
+    let mut result = vec![Feedback::Gray; guess.len()];
+    fill_feedback(guess, answer, &mut result);
+    result
+}
+
+/// `feedback()` packed into a number (in base 3, one digit per letter), so
+/// outcomes can be counted without allocating. Only the first `MAX_LENGTH`
+/// letters are looked at, which is as long as a Wordle pattern can be.
+fn feedback_code(guess: &str, answer: &str) -> u32 {
+    let mut result = [Feedback::Gray; MAX_LENGTH];
+    let result = &mut result[..guess.len().min(MAX_LENGTH)];
+    fill_feedback(guess, answer, result);
+    result.iter().fold(0, |code, f| code * 3 + *f as u32)
+}
+
+fn fill_feedback(guess: &str, answer: &str, result: &mut [Feedback]) {
+    let guess = guess.as_bytes();
+    let answer = answer.as_bytes();
+    // letters of the answer that haven't been used up by a green or yellow
+    let mut unused = [0u8; 128];
+    for (i, (g, a)) in guess.iter().zip(answer).enumerate().take(result.len()) {
+        if g == a {
+            result[i] = Feedback::Green;
+        } else {
+            unused[(*a & 0x7f) as usize] += 1;
+        }
+    }
+    for (i, g) in guess.iter().enumerate().take(result.len()) {
+        let count = &mut unused[(*g & 0x7f) as usize];
+        if result[i] != Feedback::Green && *count > 0 {
+            result[i] = Feedback::Yellow;
+            *count -= 1;
+        }
+    }
+}
+
+/// Everything the past guesses say about the answer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WordleConstraints {
+    /// For each position, the letters the answer could have there.
+    masks: Vec<u32>,
+    /// The fewest times each letter can appear in the answer.
+    min_counts: [u8; 26],
+    /// The most times each letter can appear in the answer, if known.
+    max_counts: [Option<u8>; 26],
+}
+
+fn letter_index(c: char) -> usize {
+    (c as u8 - b'a') as usize
+}
+
+impl WordleConstraints {
+    /// Parses a Wordle pattern, which is a comma-separated list of past
+    /// guesses written as `guess:feedback` (for example `crane:bybbg`).
+    /// Entries can also be a pattern like `?r??e`, where the letters are
+    /// known to be in those positions; a pattern of all `?` just gives the
+    /// length of the answer.
+    pub fn parse(pattern: &str) -> Result<WordleConstraints, String> {
+        let pattern = pattern.to_ascii_lowercase();
+        let entries = pattern.split(',').collect::<Vec<_>>();
+        if entries.len() > MAX_GUESSES {
+            return Err("Too many guesses".to_string());
+        }
+        let length = entries[0].split(':').next().unwrap().len();
+        if length == 0 {
+            return Err("Pattern is empty".to_string());
+        }
+        if length > MAX_LENGTH {
+            return Err("Pattern too long".to_string());
+        }
+        let all_letters = ('a'..='z').map(char_mask).fold(0, |acc, m| acc | m);
+        let mut constraints = WordleConstraints {
+            masks: vec![all_letters; length],
+            min_counts: [0; 26],
+            max_counts: [None; 26],
+        };
+        for entry in entries {
+            let mut parts = entry.split(':');
+            let guess = parts.next().unwrap();
+            let feedback = match parts.next() {
+                Some(feedback) => feedback
+                    .chars()
+                    .map(|c| Feedback::from_char(c).map(Some))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| "Disallowed characters in feedback".to_string())?,
+                // a bare pattern - letters are green and ?s tell us nothing
+                None => guess
+                    .chars()
+                    .map(|c| {
+                        if c == '?' {
+                            None
+                        } else {
+                            Some(Feedback::Green)
+                        }
+                    })
+                    .collect(),
+            };
+            if parts.next().is_some() {
+                return Err("Too many colons in guess".to_string());
+            }
+            if guess.len() != length || feedback.len() != length {
+                return Err("All guesses must be the same length".to_string());
+            }
+            constraints.add_guess(guess, &feedback)?;
+        }
+        Ok(constraints)
+    }
+
+    fn add_guess(&mut self, guess: &str, feedback: &[Option<Feedback>]) -> Result<(), String> {
+        let mut marked = [0u8; 26];
+        let mut has_gray = [false; 26];
+        for (i, (c, f)) in guess.chars().zip(feedback).enumerate() {
+            let f = match f {
+                Some(f) => f,
+                None => continue,
+            };
+            if !c.is_ascii_lowercase() {
+                return Err("Disallowed characters in pattern".to_string());
+            }
+            let letter = letter_index(c);
+            match f {
+                Feedback::Green => {
+                    self.masks[i] &= char_mask(c);
+                    marked[letter] += 1;
+                }
+                Feedback::Yellow => {
+                    self.masks[i] &= !char_mask(c);
+                    marked[letter] += 1;
+                }
+                Feedback::Gray => {
+                    self.masks[i] &= !char_mask(c);
+                    has_gray[letter] = true;
+                }
+            }
+        }
+        for letter in 0..26 {
+            self.min_counts[letter] = self.min_counts[letter].max(marked[letter]);
+            if has_gray[letter] {
+                // a gray means the answer has no more of this letter than
+                // were marked green or yellow
+                self.max_counts[letter] =
+                    Some(self.max_counts[letter].map_or(marked[letter], |m| m.min(marked[letter])));
+            }
+        }
+        Ok(())
+    }
+
+    /// The length of the answer.
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// For each position, the letters the answer could have there, as a
+    /// bitmask over `WORD_ALPHABET`. `absent_letters` are letters known not
+    /// to be in the answer from somewhere other than the guesses (like a
+    /// keyboard display).
+    pub fn position_masks(&self, absent_letters: &str) -> Vec<u32> {
+        let mut excluded = 0;
+        for c in absent_letters.chars().map(|c| c.to_ascii_lowercase()) {
+            if c.is_ascii_lowercase() && self.min_counts[letter_index(c)] == 0 {
+                excluded |= char_mask(c);
+            }
+        }
+        let mut masks = self.masks.clone();
+        for (letter, max_count) in self.max_counts.iter().enumerate() {
+            let letter_mask = char_mask((b'a' + letter as u8) as char);
+            let known_positions = masks.iter().filter(|mask| **mask == letter_mask).count();
+            if *max_count == Some(known_positions as u8) {
+                // every copy of the letter has been found, so it can't be
+                // anywhere else
+                for mask in masks.iter_mut().filter(|mask| **mask != letter_mask) {
+                    *mask &= !letter_mask;
+                }
+            }
+        }
+        masks.iter().map(|mask| mask & !excluded).collect()
+    }
+
+    /// Whether `word` could be the answer. This checks letter counts as
+    /// well as positions.
+    pub fn allows(&self, word: &str) -> bool {
+        if word.len() != self.len() {
+            return false;
+        }
+        let mut counts = [0u8; 26];
+        for (c, mask) in word.chars().zip(&self.masks) {
+            if char_mask(c) & mask == 0 || !c.is_ascii_lowercase() {
+                return false;
+            }
+            counts[letter_index(c)] += 1;
+        }
+        counts
+            .iter()
+            .zip(self.min_counts.iter().zip(&self.max_counts))
+            .all(|(count, (min, max))| count >= min && max.is_none_or(|max| *count <= max))
+    }
+}
+
+/// The guesses that are expected to tell us the most about which of
+/// `candidates` (weighted by frequency) is the answer, with how many bits
+/// of information each is expected to give. Guesses are taken from the most
+/// common candidates; ties go to the more common word since it might be
+/// the answer. At most `count` guesses are returned.
+///
+/// Scoring a guess against an answer is charged to `limits`. If they run
+/// out, only the guesses scored so far are considered, and the second
+/// value is true.
+pub fn best_guesses(
+    candidates: &[(String, u64)],
+    count: usize,
+    limits: &QueryLimits,
+) -> (Vec<(String, f64)>, bool) {
+    let total = candidates.iter().map(|c| c.1).sum::<u64>() as f64;
+    if total == 0.0 {
+        return (vec![], false);
+    }
+    let mut pool = candidates.iter().collect::<Vec<_>>();
+    pool.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    pool.truncate(MAX_GUESS_POOL);
+    let mut budget = WorkBudget::new(limits);
+    let mut truncated = false;
+    let mut guesses = vec![];
+    'guesses: for (guess, frequency) in pool {
+        let mut outcomes: HashMap<u32, u64> = HashMap::new();
+        for (answer, answer_frequency) in candidates {
+            if !budget.charge() {
+                truncated = true;
+                break 'guesses;
+            }
+            *outcomes.entry(feedback_code(guess, answer)).or_insert(0) += answer_frequency;
+        }
+        // sum in a fixed order so guesses that split the candidates the
+        // same way tie exactly
+        let mut outcome_frequencies = outcomes.into_iter().map(|o| o.1).collect::<Vec<_>>();
+        outcome_frequencies.sort_unstable();
+        let information = outcome_frequencies
+            .iter()
+            .map(|outcome_frequency| {
+                let p = *outcome_frequency as f64 / total;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+        guesses.push((guess.clone(), information, *frequency));
+    }
+    guesses.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap()
+            .then(b.2.cmp(&a.2))
+            .then(a.0.cmp(&b.0))
+    });
+    let guesses = guesses
+        .into_iter()
+        .take(count)
+        .map(|(guess, information, _)| (guess, information))
+        .collect();
+    (guesses, truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Feedback::*;
+
+    #[test]
+    fn test_feedback() {
+        assert_eq!(
+            vec![Gray, Yellow, Gray, Gray, Green],
+            feedback("crane", "rouge")
+        );
+        assert_eq!(
+            vec![Green, Green, Green, Green, Green],
+            feedback("crane", "crane")
+        );
+    }
+
+    #[test]
+    fn test_feedback_repeated_letters() {
+        // there's only one e left over after the green one, so only the
+        // first non-green e gets a yellow
+        assert_eq!(
+            vec![Yellow, Gray, Yellow, Gray, Green],
+            feedback("eerie", "there")
+        );
+    }
+
+    #[test]
+    fn test_constraints_from_guesses() {
+        let constraints = WordleConstraints::parse("crane:bybbg,spite:bbbbg").unwrap();
+        assert_eq!(5, constraints.len());
+        assert!(constraints.allows("rouge"));
+        // r can't be in the second position
+        assert!(!constraints.allows("prude"));
+        // no r at all
+        assert!(!constraints.allows("bulge"));
+        // s was gray
+        assert!(!constraints.allows("rouse"));
+    }
+
+    #[test]
+    fn test_constraints_repeated_letter_gray() {
+        // the other e's are gray, so there's exactly one e
+        let constraints = WordleConstraints::parse("geese:bbgbb").unwrap();
+        assert!(constraints.allows("theft"));
+        assert!(!constraints.allows("there"));
+        let masks = constraints.position_masks("");
+        assert_eq!(char_mask('e'), masks[2]);
+        assert_eq!(0, masks[0] & char_mask('e'));
+        assert_ne!(0, masks[1] & char_mask('x'));
+    }
+
+    #[test]
+    fn test_constraints_bare_pattern() {
+        let constraints = WordleConstraints::parse("?r??e,?????").unwrap();
+        assert!(constraints.allows("crane"));
+        assert!(!constraints.allows("crank"));
+        let absent = constraints.position_masks("cr");
+        // r is known to be in the answer, so listing it as absent is ignored
+        assert_eq!(char_mask('r'), absent[1]);
+        assert_eq!(0, absent[0] & char_mask('c'));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(WordleConstraints::parse("").is_err());
+        assert!(WordleConstraints::parse("crane:bybb").is_err());
+        assert!(WordleConstraints::parse("crane:bybbx").is_err());
+        assert!(WordleConstraints::parse("crane:bybbg,tea:bbb").is_err());
+        assert!(WordleConstraints::parse("cr4ne:bybbg").is_err());
+    }
+
+    #[test]
+    fn test_best_guesses() {
+        let candidates = vec![
+            ("cat".to_string(), 40),
+            ("hat".to_string(), 20),
+            ("mat".to_string(), 20),
+            ("cab".to_string(), 20),
+        ];
+        let (guesses, truncated) = best_guesses(&candidates, 3, &QueryLimits::default());
+        assert!(!truncated);
+        assert_eq!(3, guesses.len());
+        // "cat" and "cab" split the candidates the same way, but "cat" is
+        // more likely to be the answer
+        assert_eq!("cat", guesses[0].0);
+        assert_eq!("cab", guesses[1].0);
+        assert_eq!(guesses[0].1, guesses[1].1);
+        // "hat" can't tell "cat" from "mat"
+        assert!(guesses[2].1 < guesses[1].1);
+        assert!(best_guesses(&[], 5, &QueryLimits::default()).0.is_empty());
+    }
+
+    #[test]
+    fn test_feedback_code() {
+        // the same feedback gets the same code
+        assert_eq!(
+            feedback_code("crane", "rouge"),
+            feedback_code("crane", "route")
+        );
+        assert_ne!(
+            feedback_code("crane", "rouge"),
+            feedback_code("crane", "crane")
+        );
+        assert_ne!(
+            feedback_code("eerie", "there"),
+            feedback_code("eerie", "eerie")
+        );
+    }
+
+    #[test]
+    fn test_best_guesses_budget() {
+        let candidates = vec![
+            ("cat".to_string(), 40),
+            ("hat".to_string(), 20),
+            ("mat".to_string(), 20),
+            ("cab".to_string(), 20),
+        ];
+        // enough to score two guesses against all four answers
+        let limits = QueryLimits {
+            max_scanned_entries: 8,
+            ..QueryLimits::default()
+        };
+        let (guesses, truncated) = best_guesses(&candidates, 3, &limits);
+        assert!(truncated);
+        assert_eq!(2, guesses.len());
+    }
+}